@@ -67,6 +67,13 @@ impl<Buffer: Send + Sync> AwaitableResponses<Buffer> {
 }
 
 /// Request Id
+///
+/// The id sent to the server is the index of the slot it occupies, so ids
+/// never wrap around no matter how many requests have been sent.
+///
+/// A slot is only reused after the [`Id`] is dropped and the response
+/// of the request it is used in, if any, has been received, even if the
+/// awaitable of the request is dropped before the response arrives.
 #[repr(transparent)]
 #[derive(Debug, destructure)]
 pub struct Id<Buffer: Send + Sync>(pub(crate) ArenaArc<Buffer>);
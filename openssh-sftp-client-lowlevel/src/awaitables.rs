@@ -51,24 +51,28 @@ impl<Buffer: Send + Sync> AwaitableInnerFuture<Buffer> {
 
         let waker = cx.waker().clone();
 
-        let res = self
-            .0
-            .as_ref()
-            .expect(errmsg)
-            .0
-            .install_waker(waker)
-            .expect("AwaitableResponse should either in state Ongoing or Done");
-
-        if !res {
-            return Poll::Pending;
+        let res = self.0.as_ref().expect(errmsg).0.install_waker(waker);
+
+        match res {
+            Ok(true) => (),
+            Ok(false) => return Poll::Pending,
+            Err(_) => {
+                return Poll::Ready(Err(Error::ProtocolViolation(
+                    &"AwaitableResponse is neither ongoing nor done",
+                )))
+            }
         }
 
         let awaitable = self.0.take().expect(errmsg);
 
-        let response = awaitable
-            .0
-            .take_output()
-            .expect("The request should be done by now");
+        let response = match awaitable.0.take_output() {
+            Some(response) => response,
+            None => {
+                return Poll::Ready(Err(Error::ProtocolViolation(
+                    &"AwaitableResponse is consumed before it is awaited",
+                )))
+            }
+        };
 
         // Reconstruct Id here so that it will be automatically
         // released on error.
@@ -156,6 +160,10 @@ macro_rules! def_awaitable {
             ///
             /// id can be reused in the next request.
             ///
+            /// The returned future does not borrow [`WriteEnd`] and is
+            /// `Send + 'static` as long as `Buffer` is, so it can be
+            /// spawned or stored for as long as needed.
+            ///
             /// # Cancel Safety
             ///
             /// It is perfectly safe to cancel the future.
@@ -279,3 +287,51 @@ def_awaitable!(AwaitableLimits, AwaitableLimitsFuture, Limits, |response| {
         _ => Err(Error::InvalidResponse(&"Expected extended reply response")),
     }
 });
+
+def_awaitable!(
+    AwaitableStatVfs,
+    AwaitableStatVfsFuture,
+    StatVfs,
+    |response| {
+        match response {
+            Response::ExtendedReply(boxed) => Ok(ssh_format::from_bytes(&boxed)?.0),
+            _ => Err(Error::InvalidResponse(&"Expected extended reply response")),
+        }
+    }
+);
+
+def_awaitable!(
+    AwaitableUsersGroups,
+    AwaitableUsersGroupsFuture,
+    UsersGroups,
+    |response| {
+        match response {
+            Response::ExtendedReply(boxed) => UsersGroups::decode(&boxed),
+            _ => Err(Error::InvalidResponse(&"Expected extended reply response")),
+        }
+    }
+);
+
+def_awaitable!(
+    AwaitableCheckFile,
+    AwaitableCheckFileFuture,
+    CheckFile,
+    |response| {
+        match response {
+            Response::ExtendedReply(boxed) => CheckFile::decode(&boxed),
+            _ => Err(Error::InvalidResponse(&"Expected extended reply response")),
+        }
+    }
+);
+
+def_awaitable!(
+    AwaitableSpaceAvailable,
+    AwaitableSpaceAvailableFuture,
+    SpaceAvailable,
+    |response| {
+        match response {
+            Response::ExtendedReply(boxed) => Ok(ssh_format::from_bytes(&boxed)?.0),
+            _ => Err(Error::InvalidResponse(&"Expected extended reply response")),
+        }
+    }
+);
@@ -1,11 +1,20 @@
 #![forbid(unsafe_code)]
 
+use std::{convert::TryFrom, io};
+
 use bytes::BytesMut;
 
 /// Any type that can act as a buffer.
 pub trait ToBuffer {
     /// Returned the buffer.
     fn get_buffer(&mut self) -> Buffer<'_>;
+
+    /// Called after `len` bytes of data are read into the [`Buffer::Slice`]
+    /// returned by [`ToBuffer::get_buffer`], does nothing by default.
+    #[inline(always)]
+    fn advance(&mut self, len: usize) {
+        let _ = len;
+    }
 }
 
 /// Buffer that can be used to write data into.
@@ -48,3 +57,21 @@ impl<const LEN: usize> ToBuffer for [u8; LEN] {
         Buffer::Slice(self)
     }
 }
+
+/// The data is written into the underlying slice starting at
+/// [`io::Cursor::position`], which is then advanced past the data.
+impl<T: AsMut<[u8]>> ToBuffer for io::Cursor<T> {
+    #[inline]
+    fn get_buffer(&mut self) -> Buffer<'_> {
+        let pos = self.position();
+        let slice = self.get_mut().as_mut();
+        let pos = usize::try_from(pos).map_or(slice.len(), |pos| pos.min(slice.len()));
+
+        Buffer::Slice(&mut slice[pos..])
+    }
+
+    #[inline]
+    fn advance(&mut self, len: usize) {
+        self.set_position(self.position() + len as u64);
+    }
+}
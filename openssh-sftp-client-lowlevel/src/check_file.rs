@@ -0,0 +1,38 @@
+#![forbid(unsafe_code)]
+
+use super::{ssh_string::take_string, Error};
+
+/// Extension name of [`crate::WriteEnd::send_check_file_handle_request`].
+pub(crate) const EXT_NAME_CHECK_FILE_HANDLE: &str = "check-file-handle";
+
+/// Extension name of [`crate::WriteEnd::send_check_file_name_request`].
+pub(crate) const EXT_NAME_CHECK_FILE_NAME: &str = "check-file-name";
+
+/// Hashes returned by [`crate::WriteEnd::send_check_file_handle_request`]
+/// and [`crate::WriteEnd::send_check_file_name_request`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CheckFile {
+    /// The hash algorithm chosen by the server, e.g. `"sha256"`.
+    pub algorithm: String,
+
+    /// Concatenation of the hash of each block of the requested range,
+    /// or the hash of the whole range if block size is `0`.
+    pub hashes: Vec<u8>,
+}
+
+impl CheckFile {
+    pub(crate) fn decode(mut bytes: &[u8]) -> Result<Self, Error> {
+        let malformed = || Error::InvalidResponse(&"Malformed check-file reply");
+
+        if take_string(&mut bytes).ok_or_else(malformed)? != b"check-file" {
+            return Err(malformed());
+        }
+
+        let algorithm = take_string(&mut bytes).ok_or_else(malformed)?;
+
+        Ok(Self {
+            algorithm: String::from_utf8_lossy(algorithm).into_owned(),
+            hashes: bytes.to_vec(),
+        })
+    }
+}
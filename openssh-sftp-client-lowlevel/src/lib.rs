@@ -15,6 +15,21 @@
 //!  - [`WriteEnd::send_fsync_request`]
 //!  - [`WriteEnd::send_hardlink_request`]
 //!  - [`WriteEnd::send_posix_rename_request`]
+//!  - [`WriteEnd::send_lsetstat_request`]
+//!  - [`WriteEnd::send_statvfs_request`]
+//!  - [`WriteEnd::send_fstatvfs_request`]
+//!  - [`WriteEnd::send_users_groups_by_id_request`]
+//!  - [`WriteEnd::send_home_directory_request`]
+//!  - [`WriteEnd::send_check_file_handle_request`]
+//!  - [`WriteEnd::send_check_file_name_request`]
+//!  - [`WriteEnd::send_space_available_request`]
+//!
+//! Instead of calling `WriteEnd::send_*_request` with positional arguments,
+//! requests can also be constructed using builders:
+//!  - [`OpenOptions`] for open requests
+//!  - [`ReadRequest`] for read requests
+//!  - [`WriteRequest`] for write requests
+//!  - [`SetStatRequest`] for setstat/fsetstat requests
 
 pub use openssh_sftp_error::{Error, SftpErrMsg, SftpErrorKind, UnixTimeStampError};
 pub use openssh_sftp_protocol::{
@@ -52,10 +67,12 @@ pub use awaitable_responses::Id;
 
 mod awaitables;
 pub use awaitables::{
-    AwaitableAttrs, AwaitableAttrsFuture, AwaitableData, AwaitableDataFuture, AwaitableHandle,
-    AwaitableHandleFuture, AwaitableLimits, AwaitableLimitsFuture, AwaitableName,
-    AwaitableNameEntries, AwaitableNameEntriesFuture, AwaitableNameFuture, AwaitableStatus,
-    AwaitableStatusFuture, Data,
+    AwaitableAttrs, AwaitableAttrsFuture, AwaitableCheckFile, AwaitableCheckFileFuture,
+    AwaitableData, AwaitableDataFuture, AwaitableHandle, AwaitableHandleFuture, AwaitableLimits,
+    AwaitableLimitsFuture, AwaitableName, AwaitableNameEntries, AwaitableNameEntriesFuture,
+    AwaitableNameFuture, AwaitableSpaceAvailable, AwaitableSpaceAvailableFuture, AwaitableStatVfs,
+    AwaitableStatVfsFuture, AwaitableStatus, AwaitableStatusFuture, AwaitableUsersGroups,
+    AwaitableUsersGroupsFuture, Data,
 };
 
 mod buffer;
@@ -67,10 +84,27 @@ pub use connection::{connect, SharedData};
 mod queue;
 pub use queue::Queue;
 
+mod ssh_string;
+
+mod statvfs;
+pub use statvfs::StatVfs;
+
+mod check_file;
+pub use check_file::CheckFile;
+
+mod space_available;
+pub use space_available::SpaceAvailable;
+
+mod users_groups;
+pub use users_groups::UsersGroups;
+
 mod read_end;
-pub use read_end::ReadEnd;
+pub use read_end::{ReadEnd, ServerHello};
 
 mod reader_buffered;
 
 mod write_end;
 pub use write_end::WriteEnd;
+
+mod request_builder;
+pub use request_builder::{ReadRequest, SetStatRequest, WriteRequest};
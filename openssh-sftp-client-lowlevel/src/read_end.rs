@@ -5,19 +5,65 @@ use super::{
     reader_buffered::ReaderBuffered, Error, Extensions, ToBuffer,
 };
 
-use std::{io, num::NonZeroUsize, pin::Pin};
+use std::{borrow::Cow, io, num::NonZeroUsize, pin::Pin};
 
 use openssh_sftp_error::RecursiveError;
 use openssh_sftp_protocol::{
     constants::SSH2_FILEXFER_VERSION,
     response::{self, ServerVersion},
-    serde::de::DeserializeOwned,
+    serde::{de::DeserializeOwned, Deserialize},
     ssh_format::{self, from_bytes},
 };
 use pin_project::pin_project;
 use tokio::io::{copy_buf, sink, AsyncBufReadExt, AsyncRead, AsyncReadExt};
 use tokio_io_utility::{read_exact_to_bytes, read_exact_to_vec};
 
+/// Maximum number of bytes of a malformed packet recorded in
+/// [`Error::MalformedPacket`].
+const MAX_RECORDED_PACKET_LEN: usize = 1024;
+
+fn malformed_packet(packet: &[u8], error: Error) -> Error {
+    Error::MalformedPacket {
+        packet: packet[..packet.len().min(MAX_RECORDED_PACKET_LEN)].into(),
+        error: Box::new(error),
+    }
+}
+
+/// Parse extension pairs of `SSH_FXP_VERSION` packet `bytes`, which has
+/// already been validated by [`ServerVersion::deserialize`].
+fn parse_extension_pairs(bytes: &[u8]) -> Result<Vec<(String, String)>, Error> {
+    let mut de = ssh_format::Deserializer::from_bytes(bytes);
+
+    // Skip packet type and version
+    <(u8, u32)>::deserialize(&mut de)?;
+
+    let mut pairs = Vec::new();
+    while de.has_remaining_data() {
+        let (name, data) = <(Cow<'_, [u8]>, Cow<'_, [u8]>)>::deserialize(&mut de)?;
+
+        pairs.push((
+            String::from_utf8_lossy(&name).into_owned(),
+            String::from_utf8_lossy(&data).into_owned(),
+        ));
+    }
+
+    Ok(pairs)
+}
+
+/// Hello message received from the server, returned by
+/// [`ReadEnd::receive_server_hello_with_pairs`].
+#[derive(Debug, Clone, Default)]
+pub struct ServerHello {
+    /// Extensions supported by this crate that the server advertises.
+    pub extensions: Extensions,
+
+    /// All `(name, data)` extension pairs the server advertises, in the
+    /// order they are sent, including ones unknown to this crate.
+    ///
+    /// Non-UTF8 bytes are replaced with `U+FFFD`.
+    pub extension_pairs: Vec<(String, String)>,
+}
+
 /// The ReadEnd for the lowlevel API.
 #[derive(Debug)]
 #[pin_project]
@@ -25,6 +71,8 @@ pub struct ReadEnd<R, Buffer, Q, Auxiliary = ()> {
     #[pin]
     reader: ReaderBuffered<R>,
     shared_data: SharedData<Buffer, Q, Auxiliary>,
+    strict_validation: bool,
+    max_packet_len: u32,
 }
 
 impl<R, Buffer, Q, Auxiliary> ReadEnd<R, Buffer, Q, Auxiliary>
@@ -43,14 +91,24 @@ where
         Self {
             reader: ReaderBuffered::new(reader, reader_buffer_len),
             shared_data,
+            strict_validation: false,
+            max_packet_len: u32::MAX,
         }
     }
 
     /// Must be called once right after [`ReadEnd::new`]
     /// to receive the hello message from the server.
-    pub async fn receive_server_hello_pinned(
+    pub async fn receive_server_hello_pinned(self: Pin<&mut Self>) -> Result<Extensions, Error> {
+        self.receive_server_hello_with_pairs_pinned()
+            .await
+            .map(|server_hello| server_hello.extensions)
+    }
+
+    /// Same as [`ReadEnd::receive_server_hello_pinned`], but also return
+    /// all extension pairs the server advertises.
+    pub async fn receive_server_hello_with_pairs_pinned(
         mut self: Pin<&mut Self>,
-    ) -> Result<Extensions, Error> {
+    ) -> Result<ServerHello, Error> {
         // Receive server version
         let len: u32 = self.as_mut().read_and_deserialize(4).await?;
         if (len as usize) > 4096 {
@@ -70,7 +128,10 @@ where
                 version: server_version.version,
             })
         } else {
-            Ok(server_version.extensions)
+            Ok(ServerHello {
+                extensions: server_version.extensions,
+                extension_pairs: parse_extension_pairs(&drain)?,
+            })
         }
     }
 
@@ -114,14 +175,37 @@ where
         len: u32,
         buffer: Option<Buffer>,
     ) -> Result<Response<Buffer>, Error> {
+        let strict_validation = self.strict_validation;
+
         // Since the data is sent as a string, we need to consume the 4-byte length first.
-        self.as_mut()
+        let drain = self
+            .as_mut()
             .project()
             .reader
             .read_exact_into_buffer(4)
             .await?;
 
-        let len = (len - 4) as usize;
+        let len = match len.checked_sub(4) {
+            Some(len) => len as usize,
+            None => {
+                return Err(malformed_packet(
+                    &drain,
+                    Error::InvalidResponse(&"Data packet is shorter than its length field"),
+                ))
+            }
+        };
+
+        if strict_validation {
+            let data_len: u32 = from_bytes(&drain)?.0;
+
+            if data_len as usize != len {
+                return Err(malformed_packet(
+                    &drain,
+                    Error::InvalidResponse(&"Data length does not match the packet length"),
+                ));
+            }
+        }
+        drop(drain);
 
         if let Some(mut buffer) = buffer {
             match buffer.get_buffer() {
@@ -131,7 +215,8 @@ where
                 }
                 super::Buffer::Slice(slice) => {
                     if slice.len() >= len {
-                        self.project().reader.read_exact(slice).await?;
+                        self.project().reader.read_exact(&mut slice[..len]).await?;
+                        buffer.advance(len);
                         Ok(Response::Buffer(buffer))
                     } else {
                         self.read_in_data_packet_fallback(len).await
@@ -149,9 +234,17 @@ where
 
     /// * `len` - includes packet_type and request_id.
     async fn read_in_packet(self: Pin<&mut Self>, len: u32) -> Result<Response<Buffer>, Error> {
-        let response: response::Response = self.read_and_deserialize(len as usize).await?;
-
-        Ok(Response::Header(response.response_inner))
+        let this = self.project();
+        let drain = this.reader.read_exact_into_buffer(len as usize).await?;
+
+        match from_bytes::<response::Response>(&drain) {
+            Ok((_, rest)) if *this.strict_validation && !rest.is_empty() => Err(malformed_packet(
+                &drain,
+                Error::InvalidResponse(&"Packet has trailing bytes"),
+            )),
+            Ok((response, _)) => Ok(Response::Header(response.response_inner)),
+            Err(err) => Err(malformed_packet(&drain, err.into())),
+        }
     }
 
     /// * `len` - excludes packet_type and request_id.
@@ -169,7 +262,8 @@ where
     /// Only when the returned error is [`Error::InvalidResponseId`] or
     /// [`Error::AwaitableError`], can the function be restarted.
     ///
-    /// Upon other errors [`Error::IOError`], [`Error::FormatError`] and
+    /// Upon other errors [`Error::IOError`], [`Error::FormatError`],
+    /// [`Error::MalformedPacket`], [`Error::ProtocolViolation`] and
     /// [`Error::RecursiveErrors`], the sftp session has to be discarded.
     ///
     /// # Example
@@ -200,13 +294,35 @@ where
         let drain = this.reader.read_exact_into_buffer(9).await?;
         let (len, packet_type, response_id): (u32, u8, u32) = from_bytes(&drain)?.0;
 
-        let len = len - 5;
+        if len > *this.max_packet_len {
+            return Err(malformed_packet(
+                &drain,
+                Error::InvalidResponse(&"Packet is longer than the maximum packet length"),
+            ));
+        }
+
+        let len = match len.checked_sub(5) {
+            Some(len) => len,
+            None => {
+                return Err(malformed_packet(
+                    &drain,
+                    Error::InvalidResponse(&"Packet is shorter than its header"),
+                ))
+            }
+        };
 
         let res = this.shared_data.responses().get(response_id);
 
         let callback = match res {
             Ok(callback) => callback,
 
+            Err(_) if *this.strict_validation => {
+                return Err(malformed_packet(
+                    &drain,
+                    Error::InvalidResponse(&"Response id does not match any outstanding request"),
+                ));
+            }
+
             // Invalid response_id
             Err(err) => {
                 drop(drain);
@@ -217,16 +333,39 @@ where
             }
         };
 
+        // In strict mode, the input is taken upfront to check that the
+        // request has been sent and is still waiting for its response:
+        // `take_input` fails if the request has never been sent or its
+        // response is already consumed, and returns `None` if it is done.
+        let input = if *this.strict_validation {
+            match callback.take_input() {
+                Ok(input) if !callback.is_done() => Some(input),
+                _ => {
+                    return Err(malformed_packet(
+                        &drain,
+                        Error::InvalidResponse(
+                            &"Response id does not match any outstanding request",
+                        ),
+                    ))
+                }
+            }
+        } else {
+            None
+        };
+
         let response = if response::Response::is_data(packet_type) {
             drop(drain);
 
-            let buffer = match callback.take_input() {
-                Ok(buffer) => buffer,
-                Err(err) => {
-                    // Consume the invalid data to return self to a valid state
-                    // where read_in_one_packet can be called again.
-                    return self.consume_packet(len, err.into()).await;
-                }
+            let buffer = match input {
+                Some(buffer) => buffer,
+                None => match callback.take_input() {
+                    Ok(buffer) => buffer,
+                    Err(err) => {
+                        // Consume the invalid data to return self to a valid state
+                        // where read_in_one_packet can be called again.
+                        return self.consume_packet(len, err.into()).await;
+                    }
+                },
             };
             self.read_in_data_packet(len, buffer).await?
         } else if response::Response::is_extended_reply(packet_type) {
@@ -295,12 +434,21 @@ where
         Pin::new(self).receive_server_hello_pinned().await
     }
 
+    /// Same as [`ReadEnd::receive_server_hello`], but also return
+    /// all extension pairs the server advertises.
+    pub async fn receive_server_hello_with_pairs(&mut self) -> Result<ServerHello, Error> {
+        Pin::new(self)
+            .receive_server_hello_with_pairs_pinned()
+            .await
+    }
+
     /// # Restart on Error
     ///
     /// Only when the returned error is [`Error::InvalidResponseId`] or
     /// [`Error::AwaitableError`], can the function be restarted.
     ///
-    /// Upon other errors [`Error::IOError`], [`Error::FormatError`] and
+    /// Upon other errors [`Error::IOError`], [`Error::FormatError`],
+    /// [`Error::MalformedPacket`], [`Error::ProtocolViolation`] and
     /// [`Error::RecursiveErrors`], the sftp session has to be discarded.
     ///
     /// # Cancel Safety
@@ -327,6 +475,31 @@ where
 }
 
 impl<R, Buffer, Q, Auxiliary> ReadEnd<R, Buffer, Q, Auxiliary> {
+    /// Enable or disable strict validation of responses, which is disabled
+    /// by default.
+    ///
+    /// Response ids and packet lengths are always validated. With strict
+    /// validation, packets are also rejected with
+    /// [`Error::MalformedPacket`] if:
+    ///  - the response id does not match any outstanding request, which
+    ///    would otherwise be [`Error::InvalidResponseId`] or only be
+    ///    detected after the packet is read in;
+    ///  - the length of the data in `SSH_FXP_DATA` does not match the
+    ///    length of the packet;
+    ///  - there are trailing bytes after the response.
+    pub fn set_strict_validation(&mut self, strict_validation: bool) {
+        self.strict_validation = strict_validation;
+    }
+
+    /// Set the maximum length of response packets, excluding the 4-byte
+    /// length field, which is unlimited by default.
+    ///
+    /// Longer packets are rejected with [`Error::MalformedPacket`] before
+    /// any memory is allocated for them.
+    pub fn set_max_packet_len(&mut self, max_packet_len: u32) {
+        self.max_packet_len = max_packet_len;
+    }
+
     /// Return the [`SharedData`] held by [`ReadEnd`].
     pub fn get_shared_data(&self) -> &SharedData<Buffer, Q, Auxiliary> {
         &self.shared_data
@@ -0,0 +1,170 @@
+#![forbid(unsafe_code)]
+
+use super::*;
+
+use std::{borrow::Cow, path::Path};
+
+/// Builder of read request, sent using [`ReadRequest::send`].
+///
+/// It is equivalent to [`WriteEnd::send_read_request`].
+#[derive(Debug, Clone)]
+pub struct ReadRequest<'a, Buffer> {
+    handle: Cow<'a, Handle>,
+    offset: u64,
+    len: u32,
+    buffer: Option<Buffer>,
+}
+
+impl<'a, Buffer> ReadRequest<'a, Buffer> {
+    /// Create a request that reads
+    /// [`OPENSSH_PORTABLE_DEFAULT_DOWNLOAD_BUFLEN`] bytes starting from
+    /// offset 0 of `handle`.
+    pub fn new(handle: Cow<'a, Handle>) -> Self {
+        Self {
+            handle,
+            offset: 0,
+            len: OPENSSH_PORTABLE_DEFAULT_DOWNLOAD_BUFLEN as u32,
+            buffer: None,
+        }
+    }
+
+    /// Set the offset to start reading from.
+    #[must_use]
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Set the maximum number of bytes to read.
+    #[must_use]
+    pub fn len(mut self, len: u32) -> Self {
+        self.len = len;
+        self
+    }
+
+    /// Set the buffer to read the data into.
+    ///
+    /// If it is not set or it is not large enough,
+    /// then [`Data::AllocatedBox`] will be returned.
+    #[must_use]
+    pub fn into_buffer(mut self, buffer: Buffer) -> Self {
+        self.buffer = Some(buffer);
+        self
+    }
+}
+
+impl<Buffer: Send + Sync> ReadRequest<'_, Buffer> {
+    /// Send the request using `write_end`.
+    pub fn send<Q: Queue, Auxiliary>(
+        self,
+        write_end: &mut WriteEnd<Buffer, Q, Auxiliary>,
+        id: Id<Buffer>,
+    ) -> Result<AwaitableData<Buffer>, Error> {
+        write_end.send_read_request(id, self.handle, self.offset, self.len, self.buffer)
+    }
+}
+
+/// Builder of write request, sent using [`WriteRequest::send`].
+///
+/// It is equivalent to [`WriteEnd::send_write_request_buffered`].
+#[derive(Debug, Clone)]
+pub struct WriteRequest<'a> {
+    handle: Cow<'a, Handle>,
+    offset: u64,
+    data: Cow<'a, [u8]>,
+}
+
+impl<'a> WriteRequest<'a> {
+    /// Create a request that writes nothing at offset 0 of `handle`.
+    pub fn new(handle: Cow<'a, Handle>) -> Self {
+        Self {
+            handle,
+            offset: 0,
+            data: Cow::Borrowed(&[]),
+        }
+    }
+
+    /// Set the offset to start writing at.
+    #[must_use]
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Set the data to write.
+    #[must_use]
+    pub fn data(mut self, data: Cow<'a, [u8]>) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// Send the request using `write_end`.
+    pub fn send<Buffer, Q, Auxiliary>(
+        self,
+        write_end: &mut WriteEnd<Buffer, Q, Auxiliary>,
+        id: Id<Buffer>,
+    ) -> Result<AwaitableStatus<Buffer>, Error>
+    where
+        Buffer: ToBuffer + Send + Sync + 'static,
+        Q: Queue,
+    {
+        write_end.send_write_request_buffered(id, self.handle, self.offset, self.data)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum SetStatTarget<'a> {
+    Path(Cow<'a, Path>),
+    Handle(Cow<'a, Handle>),
+}
+
+/// Builder of setstat request, sent using [`SetStatRequest::send`].
+///
+/// It is equivalent to [`WriteEnd::send_setstat_request`] or
+/// [`WriteEnd::send_fsetstat_request`], depending on how it is created.
+#[derive(Debug, Clone)]
+pub struct SetStatRequest<'a> {
+    target: SetStatTarget<'a>,
+    attrs: FileAttrs,
+}
+
+impl<'a> SetStatRequest<'a> {
+    /// Create a request that changes nothing of file at `path`.
+    pub fn path(path: Cow<'a, Path>) -> Self {
+        Self {
+            target: SetStatTarget::Path(path),
+            attrs: FileAttrs::new(),
+        }
+    }
+
+    /// Create a request that changes nothing of the opened `handle`.
+    ///
+    /// `handle` must be opened with [`OpenOptions::write`] set.
+    pub fn handle(handle: Cow<'a, Handle>) -> Self {
+        Self {
+            target: SetStatTarget::Handle(handle),
+            attrs: FileAttrs::new(),
+        }
+    }
+
+    /// Set the attributes to change.
+    #[must_use]
+    pub fn attrs(mut self, attrs: FileAttrs) -> Self {
+        self.attrs = attrs;
+        self
+    }
+
+    /// Send the request using `write_end`.
+    pub fn send<Buffer: Send + Sync, Q: Queue, Auxiliary>(
+        self,
+        write_end: &mut WriteEnd<Buffer, Q, Auxiliary>,
+        id: Id<Buffer>,
+    ) -> Result<AwaitableStatus<Buffer>, Error> {
+        match self.target {
+            SetStatTarget::Path(path) => write_end.send_setstat_request(id, path, self.attrs),
+            SetStatTarget::Handle(handle) => {
+                write_end.send_fsetstat_request(id, handle, self.attrs)
+            }
+        }
+    }
+}
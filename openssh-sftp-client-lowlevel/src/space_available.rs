@@ -0,0 +1,43 @@
+#![forbid(unsafe_code)]
+
+use openssh_sftp_protocol::serde::{Deserialize, Deserializer};
+
+/// Extension name of [`crate::WriteEnd::send_space_available_request`].
+pub(crate) const EXT_NAME_SPACE_AVAILABLE: &str = "space-available";
+
+/// Space available on a remote device, returned by
+/// [`crate::WriteEnd::send_space_available_request`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct SpaceAvailable {
+    /// Total size of the device in bytes.
+    pub bytes_on_device: u64,
+    /// Unused bytes on the device.
+    pub unused_bytes_on_device: u64,
+    /// Bytes available to the user, which might be smaller than
+    /// `bytes_on_device` due to quota.
+    pub bytes_available_to_user: u64,
+    /// Unused bytes available to the user.
+    pub unused_bytes_available_to_user: u64,
+    /// Size of an allocation unit of the device in bytes.
+    pub bytes_per_allocation_unit: u32,
+}
+
+impl<'de> Deserialize<'de> for SpaceAvailable {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (
+            bytes_on_device,
+            unused_bytes_on_device,
+            bytes_available_to_user,
+            unused_bytes_available_to_user,
+            bytes_per_allocation_unit,
+        ) = <(u64, u64, u64, u64, u32)>::deserialize(deserializer)?;
+
+        Ok(Self {
+            bytes_on_device,
+            unused_bytes_on_device,
+            bytes_available_to_user,
+            unused_bytes_available_to_user,
+            bytes_per_allocation_unit,
+        })
+    }
+}
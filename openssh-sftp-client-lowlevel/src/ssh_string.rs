@@ -0,0 +1,14 @@
+#![forbid(unsafe_code)]
+
+use std::convert::TryInto;
+
+/// Split the ssh string at the start of `bytes` from the rest.
+pub(crate) fn take_string<'a>(bytes: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = bytes.get(..4)?;
+    let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+
+    let string = bytes.get(4..)?.get(..len)?;
+    *bytes = &bytes[4 + len..];
+
+    Some(string)
+}
@@ -0,0 +1,81 @@
+#![forbid(unsafe_code)]
+
+use openssh_sftp_protocol::serde::{Deserialize, Deserializer};
+
+/// Statistics of a remote filesystem, mirroring `struct statvfs` of the
+/// server.
+///
+/// Returned by [`crate::WriteEnd::send_statvfs_request`] and
+/// [`crate::WriteEnd::send_fstatvfs_request`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct StatVfs {
+    /// Filesystem block size (`f_bsize`).
+    pub block_size: u64,
+    /// Fundamental filesystem block size (`f_frsize`), the unit of
+    /// `blocks`, `blocks_free` and `blocks_avail`.
+    pub fragment_size: u64,
+    /// Number of blocks (`f_blocks`).
+    pub blocks: u64,
+    /// Number of free blocks (`f_bfree`).
+    pub blocks_free: u64,
+    /// Number of free blocks for unprivileged users (`f_bavail`).
+    pub blocks_avail: u64,
+    /// Number of inodes (`f_files`).
+    pub files: u64,
+    /// Number of free inodes (`f_ffree`).
+    pub files_free: u64,
+    /// Number of free inodes for unprivileged users (`f_favail`).
+    pub files_avail: u64,
+    /// Filesystem id (`f_fsid`).
+    pub fsid: u64,
+    /// Mount flags (`f_flag`), see [`StatVfs::is_read_only`] and
+    /// [`StatVfs::is_nosuid`].
+    pub flags: u64,
+    /// Maximum length of filenames (`f_namemax`).
+    pub name_max: u64,
+}
+
+impl StatVfs {
+    /// `SSH_FXE_STATVFS_ST_RDONLY`
+    pub const ST_RDONLY: u64 = 0x1;
+
+    /// `SSH_FXE_STATVFS_ST_NOSUID`
+    pub const ST_NOSUID: u64 = 0x2;
+
+    /// Return `true` if the filesystem is mounted read-only.
+    pub fn is_read_only(&self) -> bool {
+        self.flags & Self::ST_RDONLY != 0
+    }
+
+    /// Return `true` if the filesystem is mounted with setuid/setgid
+    /// bits ignored.
+    pub fn is_nosuid(&self) -> bool {
+        self.flags & Self::ST_NOSUID != 0
+    }
+
+    /// Space available to unprivileged users in bytes.
+    pub fn available_space(&self) -> u64 {
+        self.blocks_avail.saturating_mul(self.fragment_size)
+    }
+}
+
+impl<'de> Deserialize<'de> for StatVfs {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let [block_size, fragment_size, blocks, blocks_free, blocks_avail, files, files_free, files_avail, fsid, flags, name_max] =
+            <[u64; 11]>::deserialize(deserializer)?;
+
+        Ok(Self {
+            block_size,
+            fragment_size,
+            blocks,
+            blocks_free,
+            blocks_avail,
+            files,
+            files_free,
+            files_avail,
+            fsid,
+            flags,
+            name_max,
+        })
+    }
+}
@@ -0,0 +1,53 @@
+#![forbid(unsafe_code)]
+
+use super::{ssh_string::take_string, Error};
+
+/// Extension name of [`crate::WriteEnd::send_users_groups_by_id_request`].
+pub(crate) const EXT_NAME_USERS_GROUPS_BY_ID: &str = "users-groups-by-id@openssh.com";
+
+/// Names returned by [`crate::WriteEnd::send_users_groups_by_id_request`].
+///
+/// `users` and `groups` are in the same order as the uids and gids in the
+/// request, an id the server cannot resolve is `None`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct UsersGroups {
+    /// Names of the requested uids.
+    pub users: Vec<Option<String>>,
+    /// Names of the requested gids.
+    pub groups: Vec<Option<String>>,
+}
+
+/// Encode `ids` as a string containing a sequence of uint32.
+pub(crate) fn encode_ids(ids: &[u32]) -> Vec<u8> {
+    ids.iter().flat_map(|id| id.to_be_bytes()).collect()
+}
+
+fn malformed() -> Error {
+    Error::InvalidResponse(&"Malformed users-groups-by-id reply")
+}
+
+/// Decode a string containing a sequence of names.
+fn decode_names(mut bytes: &[u8]) -> Result<Vec<Option<String>>, Error> {
+    let mut names = Vec::new();
+
+    while !bytes.is_empty() {
+        let name = take_string(&mut bytes).ok_or_else(malformed)?;
+
+        names.push(if name.is_empty() {
+            None
+        } else {
+            Some(String::from_utf8_lossy(name).into_owned())
+        });
+    }
+
+    Ok(names)
+}
+
+impl UsersGroups {
+    pub(crate) fn decode(mut bytes: &[u8]) -> Result<Self, Error> {
+        let users = decode_names(take_string(&mut bytes).ok_or_else(malformed)?)?;
+        let groups = decode_names(take_string(&mut bytes).ok_or_else(malformed)?)?;
+
+        Ok(Self { users, groups })
+    }
+}
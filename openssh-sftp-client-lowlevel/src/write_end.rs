@@ -16,7 +16,7 @@ use std::{
 
 use bytes::{BufMut, Bytes, BytesMut};
 use openssh_sftp_protocol::{
-    file_attrs::FileAttrs, request::*, serde::Serialize, ssh_format::Serializer, Handle,
+    constants, file_attrs::FileAttrs, request::*, serde::Serialize, ssh_format::Serializer, Handle,
 };
 
 /// It is recommended to create at most one `WriteEnd` per thread
@@ -106,13 +106,41 @@ where
         request: RequestInner<'_>,
         buffer: Option<Buffer>,
     ) -> Result<ArenaArc<Buffer>, Error> {
-        let serialized = Self::serialize(
-            &mut self.serializer,
+        let request_id = ArenaArc::slot(&id.0);
+
+        self.send_serializable(
+            id,
             Request {
-                request_id: ArenaArc::slot(&id.0),
+                request_id,
                 inner: request,
             },
-        )?;
+            buffer,
+        )
+    }
+
+    /// Send extended request that is not covered by [`RequestInner`].
+    fn send_extended_request<T: Serialize>(
+        &mut self,
+        id: Id<Buffer>,
+        extension: &str,
+        payload: T,
+    ) -> Result<ArenaArc<Buffer>, Error> {
+        let request_id = ArenaArc::slot(&id.0);
+
+        self.send_serializable(
+            id,
+            (constants::SSH_FXP_EXTENDED, request_id, extension, payload),
+            None,
+        )
+    }
+
+    fn send_serializable<T: Serialize>(
+        &mut self,
+        id: Id<Buffer>,
+        request: T,
+        buffer: Option<Buffer>,
+    ) -> Result<ArenaArc<Buffer>, Error> {
+        let serialized = Self::serialize(&mut self.serializer, request)?;
 
         id.0.reset(buffer);
         self.shared_data.queue().push(serialized);
@@ -268,6 +296,22 @@ where
             .map(AwaitableStatus::new)
     }
 
+    /// Same as [`WriteEnd::send_setstat_request`], except that it does not
+    /// follow symlink.
+    ///
+    /// # Precondition
+    ///
+    /// Requires `extensions::contains(Extensions::LSETSTAT)` to be true.
+    pub fn send_lsetstat_request(
+        &mut self,
+        id: Id<Buffer>,
+        path: Cow<'_, Path>,
+        attrs: FileAttrs,
+    ) -> Result<AwaitableStatus<Buffer>, Error> {
+        self.send_request(id, RequestInner::Lsetstat(path, attrs), None)
+            .map(AwaitableStatus::new)
+    }
+
     /// * `handle` - Must be opened with `OpenOptions::write` set.
     pub fn send_fsetstat_request(
         &mut self,
@@ -384,6 +428,145 @@ where
             .map(AwaitableStatus::new)
     }
 
+    /// Return statistics of the filesystem `path` resides on.
+    ///
+    /// # Precondition
+    ///
+    /// Requires `extensions::contains(Extensions::STATVFS)` to be true.
+    pub fn send_statvfs_request(
+        &mut self,
+        id: Id<Buffer>,
+        path: Cow<'_, Path>,
+    ) -> Result<AwaitableStatVfs<Buffer>, Error> {
+        self.send_extended_request(id, constants::EXT_NAME_STATVFS.0, path)
+            .map(AwaitableStatVfs::new)
+    }
+
+    /// Return statistics of the filesystem the file `handle` refers to
+    /// resides on.
+    ///
+    /// # Precondition
+    ///
+    /// Requires `extensions::contains(Extensions::FSTATVFS)` to be true.
+    pub fn send_fstatvfs_request(
+        &mut self,
+        id: Id<Buffer>,
+        handle: Cow<'_, Handle>,
+    ) -> Result<AwaitableStatVfs<Buffer>, Error> {
+        self.send_extended_request(id, constants::EXT_NAME_FSTATVFS.0, handle)
+            .map(AwaitableStatVfs::new)
+    }
+
+    /// Ask the server to hash `len` bytes of file `handle` starting from
+    /// `offset`, using the first algorithm in the comma-separated
+    /// `algorithms` (e.g. `"sha256,sha1,md5"`) it supports.
+    ///
+    /// If `len` is `0`, then the file is hashed till EOF.
+    ///
+    /// If `block_size` is not `0`, then each block of `block_size` bytes
+    /// is hashed separately.
+    ///
+    /// # Precondition
+    ///
+    /// Requires the server to advertise `check-file`, which
+    /// openssh-portable does not.
+    pub fn send_check_file_handle_request(
+        &mut self,
+        id: Id<Buffer>,
+        handle: Cow<'_, Handle>,
+        algorithms: &str,
+        offset: u64,
+        len: u64,
+        block_size: u32,
+    ) -> Result<AwaitableCheckFile<Buffer>, Error> {
+        self.send_extended_request(
+            id,
+            check_file::EXT_NAME_CHECK_FILE_HANDLE,
+            (handle, algorithms, offset, len, block_size),
+        )
+        .map(AwaitableCheckFile::new)
+    }
+
+    /// Same as [`WriteEnd::send_check_file_handle_request`], except that
+    /// the file is specified by `path`.
+    ///
+    /// # Precondition
+    ///
+    /// Requires the server to advertise `check-file`, which
+    /// openssh-portable does not.
+    pub fn send_check_file_name_request(
+        &mut self,
+        id: Id<Buffer>,
+        path: Cow<'_, Path>,
+        algorithms: &str,
+        offset: u64,
+        len: u64,
+        block_size: u32,
+    ) -> Result<AwaitableCheckFile<Buffer>, Error> {
+        self.send_extended_request(
+            id,
+            check_file::EXT_NAME_CHECK_FILE_NAME,
+            (path, algorithms, offset, len, block_size),
+        )
+        .map(AwaitableCheckFile::new)
+    }
+
+    /// Return space available on the device `path` resides on.
+    ///
+    /// # Precondition
+    ///
+    /// Requires the server to support `space-available`, which
+    /// openssh-portable does not, use
+    /// [`WriteEnd::send_statvfs_request`] instead.
+    pub fn send_space_available_request(
+        &mut self,
+        id: Id<Buffer>,
+        path: Cow<'_, Path>,
+    ) -> Result<AwaitableSpaceAvailable<Buffer>, Error> {
+        self.send_extended_request(id, space_available::EXT_NAME_SPACE_AVAILABLE, path)
+            .map(AwaitableSpaceAvailable::new)
+    }
+
+    /// Return the home directory of user `username`, or of the current
+    /// user if `username` is empty.
+    ///
+    /// This extension (named `home-directory`) is not advertised by
+    /// [`Extensions`], servers that do not support it respond with
+    /// [`crate::SftpErrorKind::OpUnsupported`].
+    pub fn send_home_directory_request(
+        &mut self,
+        id: Id<Buffer>,
+        username: &str,
+    ) -> Result<AwaitableName<Buffer>, Error> {
+        self.send_extended_request(id, "home-directory", username)
+            .map(AwaitableName::new)
+    }
+
+    /// Return names of users and groups with the given ids.
+    ///
+    /// This extension is not advertised by [`Extensions`], servers that do
+    /// not support it respond with [`crate::SftpErrorKind::OpUnsupported`].
+    ///
+    /// For [openssh-portable], this is available from V_8_7_P1.
+    ///
+    /// [openssh-portable]: https://github.com/openssh/openssh-portable
+    pub fn send_users_groups_by_id_request(
+        &mut self,
+        id: Id<Buffer>,
+        uids: &[u32],
+        gids: &[u32],
+    ) -> Result<AwaitableUsersGroups<Buffer>, Error> {
+        self.send_extended_request(
+            id,
+            users_groups::EXT_NAME_USERS_GROUPS_BY_ID,
+            (
+                users_groups::encode_ids(uids),
+                users_groups::encode_ids(gids),
+            ),
+        )
+        .map(AwaitableUsersGroups::new)
+    }
+
     /// The server MUST copy the data exactly as if the client had issued a
     /// series of [`RequestInner::Read`] requests on the `read_from_handle`
     /// starting at `read_from_offset` and totaling `read_data_length` bytes,
@@ -2,15 +2,25 @@ use lowlevel::*;
 use openssh_sftp_client_lowlevel as lowlevel;
 
 use std::{
-    borrow::Cow, env, fs, io, io::IoSlice, num::NonZeroUsize, ops::Deref, os::unix::fs::symlink,
+    borrow::Cow,
+    env,
+    fmt::Debug,
+    fs, io,
+    io::{Cursor, IoSlice},
+    num::NonZeroUsize,
+    ops::Deref,
+    os::unix::fs::symlink,
     path,
 };
 
 use sftp_test_common::*;
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use tempfile::{Builder, TempDir};
-use tokio::{io::AsyncWriteExt, sync::Mutex};
+use tokio::{
+    io::{duplex, AsyncWriteExt},
+    sync::Mutex,
+};
 
 mod queue;
 use queue::MpscQueue;
@@ -60,6 +70,313 @@ async fn test_connect() {
     assert!(child.wait().await.unwrap().success());
 }
 
+#[tokio::test]
+async fn test_malformed_packet() {
+    let write_end: lowlevel::WriteEnd<Vec<u8>, MpscQueue> =
+        lowlevel::connect(MpscQueue::default(), ()).unwrap();
+
+    let stdout: &[u8] = &[
+        // Server hello: len, packet type (SSH_FXP_VERSION) and version
+        0, 0, 0, 5, 2, 0, 0, 0, 3, //
+        // Packet with a length shorter than its header
+        0, 0, 0, 3, 101, 0, 0, 0, 0,
+    ];
+
+    let mut read_end = lowlevel::ReadEnd::<_, Vec<u8>, _, _>::new(
+        stdout,
+        NonZeroUsize::new(1000).unwrap(),
+        write_end.deref().clone(),
+    );
+
+    read_end.receive_server_hello().await.unwrap();
+
+    match read_end.read_in_one_packet().await.unwrap_err() {
+        Error::MalformedPacket { packet, .. } => {
+            assert_eq!(&*packet, &[0, 0, 0, 3, 101, 0, 0, 0, 0])
+        }
+        err => panic!("Unexpected error {:#?}", err),
+    }
+}
+
+#[tokio::test]
+async fn test_max_packet_len() {
+    let write_end: lowlevel::WriteEnd<Vec<u8>, MpscQueue> =
+        lowlevel::connect(MpscQueue::default(), ()).unwrap();
+
+    let stdout: &[u8] = &[
+        // Server hello: len, packet type (SSH_FXP_VERSION) and version
+        0, 0, 0, 5, 2, 0, 0, 0, 3, //
+        // Packet declaring a length of 4GiB - 1
+        255, 255, 255, 255, 103, 0, 0, 0, 0,
+    ];
+
+    let mut read_end = lowlevel::ReadEnd::<_, Vec<u8>, _, _>::new(
+        stdout,
+        NonZeroUsize::new(1000).unwrap(),
+        write_end.deref().clone(),
+    );
+    read_end.set_max_packet_len(256 * 1024);
+
+    read_end.receive_server_hello().await.unwrap();
+
+    match read_end.read_in_one_packet().await.unwrap_err() {
+        Error::MalformedPacket { packet, .. } => {
+            assert_eq!(&*packet, &[255, 255, 255, 255, 103, 0, 0, 0, 0])
+        }
+        err => panic!("Unexpected error {:#?}", err),
+    }
+}
+
+#[tokio::test]
+async fn test_strict_validation() {
+    for strict_validation in [false, true] {
+        let mut write_end: lowlevel::WriteEnd<Vec<u8>, MpscQueue> =
+            lowlevel::connect(MpscQueue::default(), ()).unwrap();
+
+        let id = write_end.create_response_id();
+        let awaitable = write_end
+            .send_remove_request(id, Cow::Borrowed(path::Path::new("a")))
+            .unwrap();
+
+        // Skip SSH_FXP_INIT, then the length and packet type of the request.
+        let requests: Vec<u8> = write_end.queue().consume().concat();
+        let request_id = &requests[9 + 5..9 + 9];
+
+        let mut stdout = vec![
+            // Server hello: len, packet type (SSH_FXP_VERSION) and version
+            0, 0, 0, 5, 2, 0, 0, 0, 3, //
+            // SSH_FXP_STATUS with SSH_FX_OK, empty message and language tag,
+            // followed by one trailing byte.
+            0, 0, 0, 18, 101,
+        ];
+        stdout.extend_from_slice(request_id);
+        stdout.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 7]);
+        let stdout: &[u8] = &stdout;
+
+        let mut read_end = lowlevel::ReadEnd::<_, Vec<u8>, _, _>::new(
+            stdout,
+            NonZeroUsize::new(1000).unwrap(),
+            write_end.deref().clone(),
+        );
+        read_end.set_strict_validation(strict_validation);
+
+        read_end.receive_server_hello().await.unwrap();
+
+        let res = read_end.read_in_one_packet().await;
+        if strict_validation {
+            match res.unwrap_err() {
+                Error::MalformedPacket { .. } => (),
+                err => panic!("Unexpected error {:#?}", err),
+            }
+        } else {
+            res.unwrap();
+            awaitable.wait().await.unwrap();
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_strict_validation_response_id() {
+    for strict_validation in [false, true] {
+        let write_end: lowlevel::WriteEnd<Vec<u8>, MpscQueue> =
+            lowlevel::connect(MpscQueue::default(), ()).unwrap();
+
+        let stdout: &[u8] = &[
+            // Server hello: len, packet type (SSH_FXP_VERSION) and version
+            0, 0, 0, 5, 2, 0, 0, 0, 3, //
+            // SSH_FXP_STATUS with SSH_FX_OK for request 1000, which is
+            // never sent.
+            0, 0, 0, 17, 101, 0, 0, 3, 232, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+
+        let mut read_end = lowlevel::ReadEnd::<_, Vec<u8>, _, _>::new(
+            stdout,
+            NonZeroUsize::new(1000).unwrap(),
+            write_end.deref().clone(),
+        );
+        read_end.set_strict_validation(strict_validation);
+
+        read_end.receive_server_hello().await.unwrap();
+
+        match read_end.read_in_one_packet().await.unwrap_err() {
+            Error::MalformedPacket { .. } if strict_validation => (),
+            Error::InvalidResponseId { response_id: 1000 } if !strict_validation => (),
+            err => panic!("Unexpected error {:#?}", err),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_strict_validation_unsent_request_id() {
+    for strict_validation in [false, true] {
+        let mut write_end: lowlevel::WriteEnd<Vec<u8>, MpscQueue> =
+            lowlevel::connect(MpscQueue::default(), ()).unwrap();
+        let (mut server, stdout) = duplex(4096);
+
+        let mut read_end = lowlevel::ReadEnd::new(
+            stdout,
+            NonZeroUsize::new(1000).unwrap(),
+            write_end.deref().clone(),
+        );
+        read_end.set_strict_validation(strict_validation);
+
+        // Server hello: len, packet type (SSH_FXP_VERSION) and version
+        server
+            .write_all(&[0, 0, 0, 5, 2, 0, 0, 0, 3])
+            .await
+            .unwrap();
+        read_end.receive_server_hello().await.unwrap();
+        write_end.queue().consume();
+
+        // Open a file and reply with SSH_FXP_HANDLE "h" to learn the id.
+        let id = write_end.create_response_id();
+        let awaitable = write_end
+            .send_open_file_request(
+                id,
+                OpenFileRequest::open(Cow::Borrowed(path::Path::new("a"))),
+            )
+            .unwrap();
+
+        let request_id = consume_last_request_id(&write_end);
+
+        let mut response = vec![0, 0, 0, 10, 102];
+        response.extend(&request_id);
+        response.extend([0, 0, 0, 1, b'h']);
+        server.write_all(&response).await.unwrap();
+
+        read_end.read_in_one_packet().await.unwrap();
+        let (id, _handle) = awaitable.wait().await.unwrap();
+
+        // Free the id and allocate it again without sending any request.
+        drop(id);
+        let _id = write_end.create_response_id();
+
+        // SSH_FXP_STATUS with SSH_FX_OK for the unsent request.
+        let mut response = vec![0, 0, 0, 17, 101];
+        response.extend(&request_id);
+        response.extend([0; 12]);
+        server.write_all(&response).await.unwrap();
+
+        match read_end.read_in_one_packet().await.unwrap_err() {
+            Error::MalformedPacket { .. } if strict_validation => (),
+            Error::AwaitableError(_) if !strict_validation => (),
+            err => panic!("Unexpected error {:#?}", err),
+        }
+    }
+}
+
+/// Return the id of the last request in the queue of `write_end`.
+fn consume_last_request_id<B: ToBuffer + Send + Sync>(
+    write_end: &lowlevel::WriteEnd<B, MpscQueue>,
+) -> Vec<u8> {
+    // Skip the length and packet type of the request.
+    write_end.queue().consume().concat()[5..9].to_vec()
+}
+
+/// Read `data` into `buffer` from an in-memory server.
+async fn read_into_buffer<B>(buffer: B, data: &[u8]) -> Data<B>
+where
+    B: ToBuffer + Debug + Send + Sync + 'static,
+{
+    let mut write_end: lowlevel::WriteEnd<B, MpscQueue> =
+        lowlevel::connect(MpscQueue::default(), ()).unwrap();
+    let (mut server, stdout) = duplex(4096);
+
+    let mut read_end = lowlevel::ReadEnd::new(
+        stdout,
+        NonZeroUsize::new(1000).unwrap(),
+        write_end.deref().clone(),
+    );
+
+    // Server hello: len, packet type (SSH_FXP_VERSION) and version
+    server
+        .write_all(&[0, 0, 0, 5, 2, 0, 0, 0, 3])
+        .await
+        .unwrap();
+    read_end.receive_server_hello().await.unwrap();
+    write_end.queue().consume();
+
+    // Open the file and reply with SSH_FXP_HANDLE "h"
+    let id = write_end.create_response_id();
+    let awaitable = write_end
+        .send_open_file_request(
+            id,
+            OpenFileRequest::open(Cow::Borrowed(path::Path::new("a"))),
+        )
+        .unwrap();
+
+    let mut response = vec![0, 0, 0, 10, 102];
+    response.extend(consume_last_request_id(&write_end));
+    response.extend([0, 0, 0, 1, b'h']);
+    server.write_all(&response).await.unwrap();
+
+    read_end.read_in_one_packet().await.unwrap();
+    let (id, handle) = awaitable.wait().await.unwrap();
+
+    // Read from it and reply with SSH_FXP_DATA
+    let awaitable = write_end
+        .send_read_request(
+            id,
+            Cow::Borrowed(&handle),
+            0,
+            data.len() as u32,
+            Some(buffer),
+        )
+        .unwrap();
+
+    let mut response = ((1 + 4 + 4 + data.len()) as u32).to_be_bytes().to_vec();
+    response.push(103);
+    response.extend(consume_last_request_id(&write_end));
+    response.extend((data.len() as u32).to_be_bytes());
+    response.extend(data);
+    server.write_all(&response).await.unwrap();
+
+    // Close the connection, so that reading past the end of the packet
+    // fails instead of blocking forever.
+    drop(server);
+
+    read_end.read_in_one_packet().await.unwrap();
+    awaitable.wait().await.unwrap().1
+}
+
+#[tokio::test]
+async fn test_read_into_larger_slice_buffer() {
+    let msg = b"Hello, world!";
+
+    match read_into_buffer(vec![0; 20].into_boxed_slice(), msg).await {
+        Data::Buffer(buffer) => assert_eq!(&buffer[..msg.len()], msg),
+        data => panic!("Unexpected data {:#?}", data),
+    }
+}
+
+#[tokio::test]
+async fn test_to_buffer() {
+    let msg = b"Hello, world!";
+
+    match read_into_buffer(BytesMut::with_capacity(msg.len()), msg).await {
+        Data::Buffer(buffer) => assert_eq!(&buffer[..], msg),
+        data => panic!("Unexpected data {:#?}", data),
+    }
+
+    // Buffer smaller than the data
+    match read_into_buffer(vec![0; 5].into_boxed_slice(), msg).await {
+        Data::AllocatedBox(buffer) => assert_eq!(&*buffer, msg),
+        data => panic!("Unexpected data {:#?}", data),
+    }
+
+    // Data is written starting at the position of the cursor,
+    // which is then advanced past the data.
+    let mut cursor = Cursor::new(vec![0; 20]);
+    cursor.set_position(4);
+    match read_into_buffer(cursor, msg).await {
+        Data::Buffer(cursor) => {
+            assert_eq!(&cursor.get_ref()[4..4 + msg.len()], msg);
+            assert_eq!(cursor.position(), (4 + msg.len()) as u64);
+        }
+        data => panic!("Unexpected data {:#?}", data),
+    }
+}
+
 fn create_tmpdir() -> TempDir {
     let path = get_path_for_tmp_files();
 
@@ -224,6 +541,17 @@ async fn test_write_impl(
     assert!(child.wait().await.unwrap().success());
 }
 
+#[tokio::test]
+async fn test_write_request_builder() {
+    test_write_impl(|write_end, id, handle, msg| {
+        WriteRequest::new(handle)
+            .data(Cow::Borrowed(msg))
+            .send(write_end, id)
+            .unwrap()
+    })
+    .await;
+}
+
 #[tokio::test]
 async fn test_write_buffered() {
     test_write_impl(|write_end, id, handle, msg| {
@@ -545,6 +873,76 @@ async fn test_lstat() {
     assert!(child.wait().await.unwrap().success());
 }
 
+#[tokio::test]
+async fn test_spawn_awaitable() {
+    let (mut write_end, mut read_end, mut child) = connect().await;
+
+    let id = write_end.create_response_id();
+
+    let tempdir = create_tmpdir();
+    let filename = tempdir.path().join("file");
+
+    fs::File::create(&filename).unwrap().set_len(2000).unwrap();
+
+    // The future returned by `wait` does not borrow `write_end`,
+    // so it can be spawned.
+    let handle = tokio::spawn(
+        write_end
+            .send_stat_request(id, Cow::Borrowed(&filename))
+            .unwrap()
+            .wait(),
+    );
+
+    read_one_packet(&mut read_end).await;
+    let (id, attrs) = handle.await.unwrap().unwrap();
+
+    assert_eq!(attrs.get_size().unwrap(), 2000);
+
+    drop(id);
+    drop(write_end);
+    drop(read_end);
+
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+async fn test_id_reuse_after_cancellation() {
+    let (mut write_end, mut read_end, mut child) = connect().await;
+
+    let tempdir = create_tmpdir();
+    let filename = tempdir.path().join("file");
+    fs::File::create(&filename).unwrap();
+
+    // Send a request that would succeed, then drop its awaitable before
+    // the response arrives.
+    let id = write_end.create_response_id();
+    let awaitable = write_end
+        .send_lstat_request(id, Cow::Borrowed(&filename))
+        .unwrap();
+    drop(awaitable);
+
+    // The id of the cancelled request must not be reused until its
+    // response is received, otherwise the response of this request
+    // would be misrouted.
+    let id = write_end.create_response_id();
+    let awaitable = write_end
+        .send_lstat_request(id, Cow::Borrowed(&tempdir.path().join("non-existent")))
+        .unwrap();
+
+    read_one_packet(&mut read_end).await;
+    read_one_packet(&mut read_end).await;
+
+    match awaitable.wait().await.unwrap_err() {
+        Error::SftpError(SftpErrorKind::NoSuchFile, _) => (),
+        err => panic!("Unexpected error {:#?}", err),
+    }
+
+    drop(write_end);
+    drop(read_end);
+
+    assert!(child.wait().await.unwrap().success());
+}
+
 #[tokio::test]
 async fn test_fstat() {
     let (mut write_end, mut read_end, mut child) = connect().await;
@@ -677,6 +1075,66 @@ async fn test_fsetstat() {
     assert!(child.wait().await.unwrap().success());
 }
 
+#[tokio::test]
+async fn test_setstat_read_request_builder() {
+    let (mut write_end, mut read_end, mut child) = connect().await;
+
+    let id = write_end.create_response_id();
+
+    let tempdir = create_tmpdir();
+    let filename = tempdir.path().join("file");
+
+    fs::write(&filename, "Hello, world!").unwrap();
+
+    // open
+    let awaitable = write_end
+        .send_open_file_request(
+            id,
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(Cow::Borrowed(&filename)),
+        )
+        .unwrap();
+
+    read_one_packet(&mut read_end).await;
+    let (id, handle) = awaitable.wait().await.unwrap();
+
+    // fsetstat
+    let mut fileattrs = FileAttrs::default();
+    fileattrs.set_size(5);
+
+    let awaitable = SetStatRequest::handle(Cow::Borrowed(&handle))
+        .attrs(fileattrs)
+        .send(&mut write_end, id)
+        .unwrap();
+
+    read_one_packet(&mut read_end).await;
+    let id = awaitable.wait().await.unwrap().0;
+
+    // read
+    let awaitable = ReadRequest::new(Cow::Borrowed(&handle))
+        .offset(1)
+        .len(100)
+        .into_buffer(Vec::with_capacity(100))
+        .send(&mut write_end, id)
+        .unwrap();
+
+    read_one_packet(&mut read_end).await;
+    let (id, data) = awaitable.wait().await.unwrap();
+
+    match data {
+        Data::Buffer(buffer) => assert_eq!(&*buffer, b"ello"),
+        _ => panic!("Unexpected data"),
+    };
+
+    drop(id);
+    drop(write_end);
+    drop(read_end);
+
+    assert!(child.wait().await.unwrap().success());
+}
+
 #[tokio::test]
 async fn test_readlink() {
     let (mut write_end, mut read_end, mut child) = connect().await;
@@ -793,6 +1251,111 @@ async fn test_limits() {
     assert!(child.wait().await.unwrap().success());
 }
 
+#[tokio::test]
+async fn test_statvfs() {
+    let (mut write_end, mut read_end, mut child, extensions) = connect_with_extensions().await;
+    assert!(extensions.contains(lowlevel::Extensions::STATVFS));
+    assert!(extensions.contains(lowlevel::Extensions::FSTATVFS));
+
+    let id = write_end.create_response_id();
+
+    let tempdir = create_tmpdir();
+    let filename = tempdir.path().join("file");
+
+    fs::File::create(&filename).unwrap();
+
+    // statvfs
+    let awaitable = write_end
+        .send_statvfs_request(id, Cow::Borrowed(tempdir.path()))
+        .unwrap();
+
+    read_one_packet(&mut read_end).await;
+    let (id, statvfs) = awaitable.wait().await.unwrap();
+
+    assert_ne!(statvfs.block_size, 0);
+    assert!(statvfs.blocks_free <= statvfs.blocks);
+
+    // open
+    let awaitable = write_end
+        .send_open_file_request(
+            id,
+            OpenOptions::new().read(true).open(Cow::Borrowed(&filename)),
+        )
+        .unwrap();
+
+    read_one_packet(&mut read_end).await;
+    let (id, handle) = awaitable.wait().await.unwrap();
+
+    // fstatvfs
+    let awaitable = write_end
+        .send_fstatvfs_request(id, Cow::Borrowed(&handle))
+        .unwrap();
+
+    read_one_packet(&mut read_end).await;
+    let (id, fstatvfs) = awaitable.wait().await.unwrap();
+
+    assert_eq!(fstatvfs.fsid, statvfs.fsid);
+    assert_eq!(fstatvfs.block_size, statvfs.block_size);
+
+    drop(id);
+    drop(write_end);
+    drop(read_end);
+
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+async fn test_home_directory() {
+    let home: path::PathBuf = env::var("HOME").unwrap().into();
+
+    let (mut write_end, mut read_end, mut child) = connect().await;
+
+    let id = write_end.create_response_id();
+
+    let awaitable = write_end.send_home_directory_request(id, "").unwrap();
+
+    read_one_packet(&mut read_end).await;
+    let (id, home_dir) = awaitable.wait().await.unwrap();
+
+    assert_eq!(&*home_dir, &*home);
+
+    drop(id);
+    drop(write_end);
+    drop(read_end);
+
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+async fn test_users_groups_by_id() {
+    use std::os::unix::fs::MetadataExt;
+
+    let (mut write_end, mut read_end, mut child) = connect().await;
+
+    let id = write_end.create_response_id();
+
+    let tempdir = create_tmpdir();
+    let metadata = fs::metadata(tempdir.path()).unwrap();
+
+    let awaitable = write_end
+        .send_users_groups_by_id_request(id, &[metadata.uid()], &[metadata.gid(), metadata.gid()])
+        .unwrap();
+
+    read_one_packet(&mut read_end).await;
+    let (id, users_groups) = awaitable.wait().await.unwrap();
+
+    assert_eq!(users_groups.users.len(), 1);
+    assert!(users_groups.users[0].is_some());
+    assert_eq!(users_groups.groups.len(), 2);
+    assert_eq!(users_groups.groups[0], users_groups.groups[1]);
+
+    drop(id);
+    drop(write_end);
+    drop(read_end);
+
+    assert!(child.wait().await.unwrap().success());
+}
+
 #[tokio::test]
 async fn test_expand_path() {
     let home: path::PathBuf = env::var("HOME").unwrap().into();
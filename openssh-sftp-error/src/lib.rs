@@ -1,6 +1,6 @@
 #![forbid(unsafe_code)]
 
-use std::{io, num::TryFromIntError, process::ExitStatus};
+use std::{io, num::TryFromIntError, path::PathBuf, process::ExitStatus, time::Duration};
 
 pub use awaitable_error::Error as AwaitableError;
 pub use openssh_sftp_protocol_error::{
@@ -94,6 +94,79 @@ pub enum Error {
         &'static &'static str,
     ),
 
+    /// Packet received from the sftp-server is malformed, e.g. its length is
+    /// impossible or it cannot be deserialized.
+    ///
+    /// The sftp session has to be discarded.
+    #[error("Received malformed packet {packet:?} from sftp server: {error}")]
+    MalformedPacket {
+        /// The offending bytes, might be truncated.
+        packet: Box<[u8]>,
+
+        /// Why the packet is considered to be malformed.
+        #[source]
+        error: Box<Error>,
+    },
+
+    /// The requested range starts at or beyond the end of the file,
+    /// i.e. no byte in it can be read.
+    #[error("Range of {len} bytes starting at offset {offset} is out of bounds of the file")]
+    RangeOutOfBounds {
+        /// Start of the requested range.
+        offset: u64,
+
+        /// Length of the requested range.
+        len: u64,
+    },
+
+    /// The file is larger than the limit set by the caller.
+    #[error("File is larger than the limit of {max_len} bytes")]
+    FileTooLarge {
+        /// The limit set by the caller.
+        max_len: u64,
+    },
+
+    /// The sftp server or the internal state machine violated the protocol,
+    /// e.g. a response is awaited after it has already been consumed.
+    ///
+    /// The sftp session has to be discarded.
+    #[error("Sftp protocol violated: {0}")]
+    ProtocolViolation(&'static &'static str),
+
+    /// No data can be written to the sftp server within the deadline,
+    /// e.g. the ssh process hangs and its pipe buffer is full.
+    ///
+    /// The sftp session has to be discarded.
+    #[error("Writing to sftp server made no progress in {deadline:?}")]
+    WriteStalled {
+        /// The deadline set by the user.
+        deadline: Duration,
+    },
+
+    /// The operation is cancelled by the caller, e.g. by cancelling
+    /// the scope it is run in.
+    #[error("Operation is cancelled")]
+    Cancelled,
+
+    /// The operation did not complete within the timeout set by the caller.
+    #[error("Operation timed out after {timeout:?}")]
+    TimedOut {
+        /// The timeout set by the caller.
+        timeout: Duration,
+    },
+
+    /// Error when walking a directory tree, e.g. in `Fs::walk_dir`,
+    /// `Fs::copy_dir`, `Sftp::upload_dir` or `Sftp::download_dir`.
+    #[error("Error when walking {path:?}: {error}")]
+    WalkDirError {
+        /// The path the error occurred on.
+        path: PathBuf,
+
+        /// The error occurred on `path`.
+        #[source]
+        error: Box<Error>,
+    },
+
     /// Handle returned by server is longer than the limit 256 bytes specified in sftp v3.
     #[error("Handle returned by server is longer than the limit 256 bytes specified in sftp v3")]
     HandleTooLong,
@@ -132,3 +205,39 @@ pub struct RecursiveError3 {
     #[source]
     pub err3: Error,
 }
+
+/// Convert [`Error`] into [`io::Error`], with [`io::ErrorKind`] derived
+/// from the sftp status code if there is one, or from the wrapped error
+/// for [`Error::WalkDirError`].
+///
+/// The original [`Error`] can be retrieved using [`io::Error::into_inner`],
+/// except for [`Error::IOError`], which is unwrapped.
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::IOError(io_error) => io_error,
+            err => io::Error::new(io_error_kind(&err), err),
+        }
+    }
+}
+
+fn io_error_kind(err: &Error) -> io::ErrorKind {
+    match err {
+        Error::IOError(io_error) => io_error.kind(),
+        Error::SftpError(kind, _) => match kind {
+            SftpErrorKind::NoSuchFile => io::ErrorKind::NotFound,
+            SftpErrorKind::PermDenied => io::ErrorKind::PermissionDenied,
+            SftpErrorKind::OpUnsupported => io::ErrorKind::Unsupported,
+            SftpErrorKind::BadMessage => io::ErrorKind::InvalidInput,
+            _ => io::ErrorKind::Other,
+        },
+        Error::UnsupportedExtension(_) => io::ErrorKind::Unsupported,
+        Error::TimedOut { .. } | Error::WriteStalled { .. } => io::ErrorKind::TimedOut,
+        Error::InvalidResponse(_)
+        | Error::MalformedPacket { .. }
+        | Error::ProtocolViolation(_)
+        | Error::InvalidResponseId { .. } => io::ErrorKind::InvalidData,
+        Error::WalkDirError { error, .. } => io_error_kind(error),
+        _ => io::ErrorKind::Other,
+    }
+}
@@ -1,21 +1,41 @@
-use crate::{lowlevel::Extensions, SftpAuxiliaryData};
-
-use std::sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use crate::{
+    cancel_error, events::EventSender, lowlevel::Extensions, Error, LruCache, SftpAuxiliaryData,
+    SftpOptions,
+};
+
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{
+        atomic::{AtomicU16, AtomicU64, AtomicU8, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use once_cell::sync::OnceCell;
-use tokio::{runtime::Handle, sync::Notify};
+use tokio::{
+    runtime::Handle,
+    sync::{Notify, Semaphore},
+};
 use tokio_util::sync::CancellationToken;
 
 #[derive(Debug, Copy, Clone)]
 pub(super) struct Limits {
     pub(super) read_len: u32,
     pub(super) write_len: u32,
+    /// Maximum length of a packet the server accepts.
+    pub(super) packet_len: u32,
+    /// Maximum number of handles the server allows to be opened at
+    /// the same time, 0 if unknown.
+    pub(super) open_handles: u64,
 }
 
 #[derive(Debug)]
 pub(super) struct ConnInfo {
     pub(super) limits: Limits,
     pub(super) extensions: Extensions,
+    pub(super) extension_pairs: BTreeMap<String, String>,
 }
 
 #[derive(Debug)]
@@ -25,6 +45,9 @@ pub(super) struct Auxiliary {
     /// cancel_token is used to cancel `Awaitable*Future`
     /// when the read_task/flush_task has failed.
     pub(super) cancel_token: CancellationToken,
+    /// Set via [`crate::Sftp::new_with_cancellation_token`], the read_task
+    /// and flush_task are aborted once it is cancelled.
+    pub(super) shutdown_token: Option<CancellationToken>,
 
     /// flush_end_notify is used to avoid unnecessary wakeup
     /// in flush_task.
@@ -36,11 +59,24 @@ pub(super) struct Auxiliary {
     /// There can be at most `u32::MAX` pending requests, since each request
     /// requires a request id that is 32 bits.
     pub(super) pending_requests: AtomicUsize,
-    pub(super) max_pending_requests: u16,
+    /// Can be changed at runtime via [`crate::Sftp::set_max_pending_requests`].
+    pub(super) max_pending_requests: AtomicU16,
+
+    /// Flush interval in nanoseconds, can be changed at runtime via
+    /// [`crate::Sftp::set_flush_interval`].
+    pub(super) flush_interval: AtomicU64,
 
     pub(super) read_end_notify: Notify,
     pub(super) requests_to_read: AtomicUsize,
 
+    /// Number of requests sent so far, used to implement `Sftp::drain`.
+    pub(super) requests_sent: AtomicU64,
+    /// Number of responses received so far, used to implement `Sftp::drain`.
+    pub(super) responses_received: AtomicU64,
+    /// `Notify::notify_waiters` is called after `responses_received`
+    /// is increased.
+    pub(super) responses_received_notify: Notify,
+
     /// 0 means no shutdown is requested
     /// 1 means the read task should shutdown
     /// 2 means the flush task should shutdown
@@ -54,40 +90,104 @@ pub(super) struct Auxiliary {
 
     pub(super) auxiliary_data: SftpAuxiliaryData,
 
-    pub(super) tokio_compat_file_write_limit: usize,
+    pub(super) tokio_compat_file_write_limit: AtomicUsize,
+
+    pub(super) read_cache: Option<LruCache>,
+
+    /// Set via [`crate::SftpOptions::max_outstanding_requests`], each
+    /// outstanding request holds one permit until its response is received.
+    pub(super) outstanding_requests: Option<Arc<Semaphore>>,
+
+    /// Set via [`crate::SftpOptions::strict_validation`].
+    pub(super) strict_validation: bool,
+
+    /// Set via [`crate::SftpOptions::max_response_len`].
+    pub(super) max_response_len: Option<u32>,
+
+    /// Request timeout in nanoseconds, `u64::MAX` means disabled.
+    ///
+    /// Set via [`crate::SftpOptions::request_timeout`] and can be changed
+    /// at runtime via [`crate::Sftp::set_request_timeout`].
+    pub(super) request_timeout: AtomicU64,
 
     pub(super) tokio_handle: Handle,
+
+    /// `Arc` is used so that the background tasks can emit
+    /// [`crate::ConnectionEvent::Closed`] after the shared data is dropped.
+    pub(super) events: Arc<EventSender>,
 }
 
 impl Auxiliary {
     pub(super) fn new(
-        max_pending_requests: u16,
+        options: &SftpOptions,
         auxiliary_data: SftpAuxiliaryData,
-        tokio_compat_file_write_limit: usize,
         tokio_handle: Handle,
+        shutdown_token: Option<CancellationToken>,
     ) -> Self {
         Self {
             conn_info: OnceCell::new(),
 
-            cancel_token: CancellationToken::new(),
+            // Cancelled along with the shutdown_token.
+            cancel_token: shutdown_token
+                .as_ref()
+                .map_or_else(CancellationToken::new, CancellationToken::child_token),
+            shutdown_token,
 
             flush_end_notify: Notify::new(),
             flush_immediately: Notify::new(),
 
             pending_requests: AtomicUsize::new(0),
-            max_pending_requests,
+            max_pending_requests: AtomicU16::new(options.get_max_pending_requests()),
+
+            flush_interval: AtomicU64::new(duration_to_nanos(options.get_flush_interval())),
 
             read_end_notify: Notify::new(),
             requests_to_read: AtomicUsize::new(0),
 
+            requests_sent: AtomicU64::new(0),
+            responses_received: AtomicU64::new(0),
+            responses_received_notify: Notify::new(),
+
             shutdown_stage: AtomicU8::new(0),
             active_user_count: AtomicU64::new(1),
 
             auxiliary_data,
 
-            tokio_compat_file_write_limit,
+            tokio_compat_file_write_limit: AtomicUsize::new(
+                options.get_tokio_compat_file_write_limit(),
+            ),
+
+            read_cache: options
+                .get_read_cache()
+                .map(|(max_entry_len, max_total_len, ttl)| {
+                    LruCache::new(max_entry_len, max_total_len, ttl)
+                }),
+
+            outstanding_requests: options
+                .get_max_outstanding_requests()
+                .map(|permits| Arc::new(Semaphore::new(permits))),
+
+            strict_validation: options.get_strict_validation(),
+
+            max_response_len: options.get_max_response_len(),
+
+            request_timeout: AtomicU64::new(
+                options
+                    .get_request_timeout()
+                    .map_or(u64::MAX, duration_to_nanos),
+            ),
 
             tokio_handle,
+
+            events: Arc::new(EventSender::new()),
+        }
+    }
+
+    /// Return the error for requests cancelled by `cancel_token`.
+    pub(super) fn cancel_error(&self) -> Error {
+        match &self.shutdown_token {
+            Some(token) if token.is_cancelled() => Error::Cancelled,
+            _ => cancel_error(),
         }
     }
 
@@ -95,6 +195,7 @@ impl Auxiliary {
         // Must increment requests_to_read first, since
         // flush_task might wakeup read_end once it done flushing.
         self.requests_to_read.fetch_add(1, Ordering::Relaxed);
+        self.requests_sent.fetch_add(1, Ordering::Relaxed);
 
         let pending_requests = self.pending_requests.fetch_add(1, Ordering::Relaxed);
 
@@ -117,6 +218,10 @@ impl Auxiliary {
         self.conn_info().extensions
     }
 
+    pub(super) fn extension_pairs(&self) -> &BTreeMap<String, String> {
+        &self.conn_info().extension_pairs
+    }
+
     pub(super) fn limits(&self) -> Limits {
         // since writing to conn_info is only done in `Sftp::new`,
         // reading these variable should never block.
@@ -124,7 +229,43 @@ impl Auxiliary {
     }
 
     pub(super) fn max_pending_requests(&self) -> usize {
-        self.max_pending_requests as usize
+        self.max_pending_requests.load(Ordering::Relaxed) as usize
+    }
+
+    pub(super) fn set_max_pending_requests(&self, max_pending_requests: u16) {
+        self.max_pending_requests
+            .store(max_pending_requests, Ordering::Relaxed);
+
+        // The pending requests might have already exceeded the new limit.
+        if self.get_pending_requests() >= max_pending_requests as usize {
+            self.flush_immediately.notify_one();
+        }
+    }
+
+    pub(super) fn flush_interval(&self) -> Duration {
+        Duration::from_nanos(self.flush_interval.load(Ordering::Relaxed))
+    }
+
+    pub(super) fn set_flush_interval(&self, flush_interval: Duration) {
+        self.flush_interval
+            .store(duration_to_nanos(flush_interval), Ordering::Relaxed);
+
+        // So that the next request is not delayed by the old interval.
+        self.flush_immediately.notify_one();
+    }
+
+    pub(super) fn request_timeout(&self) -> Option<Duration> {
+        match self.request_timeout.load(Ordering::Relaxed) {
+            u64::MAX => None,
+            nanos => Some(Duration::from_nanos(nanos)),
+        }
+    }
+
+    pub(super) fn set_request_timeout(&self, request_timeout: Option<Duration>) {
+        self.request_timeout.store(
+            request_timeout.map_or(u64::MAX, duration_to_nanos),
+            Ordering::Relaxed,
+        );
     }
 
     pub(super) fn order_shutdown(&self) {
@@ -153,6 +294,16 @@ impl Auxiliary {
         self.pending_requests.load(Ordering::Relaxed)
     }
 
+    /// Increment `active_user_count` unless it is already 0, in which case
+    /// the shutdown has been ordered.
+    pub(super) fn try_inc_active_user_count(&self) -> bool {
+        self.active_user_count
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                (count != 0).then(|| count + 1)
+            })
+            .is_ok()
+    }
+
     pub(super) fn inc_active_user_count(&self) {
         self.active_user_count.fetch_add(1, Ordering::Relaxed);
     }
@@ -165,10 +316,34 @@ impl Auxiliary {
     }
 
     pub(super) fn tokio_compat_file_write_limit(&self) -> usize {
+        self.tokio_compat_file_write_limit.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn set_tokio_compat_file_write_limit(&self, limit: usize) {
         self.tokio_compat_file_write_limit
+            .store(limit, Ordering::Relaxed);
+    }
+
+    pub(super) fn read_cache(&self) -> Option<&LruCache> {
+        self.read_cache.as_ref()
+    }
+
+    /// Invalidate the cached content of `path` and everything under it in
+    /// the read cache, if enabled.
+    ///
+    /// It must be called after the modification has completed, otherwise
+    /// a concurrent [`crate::fs::Fs::read`] could cache the old content.
+    pub(super) fn invalidate_read_cache(&self, path: &Path) {
+        if let Some(read_cache) = self.read_cache() {
+            read_cache.invalidate(path);
+        }
     }
 
     pub(super) fn tokio_handle(&self) -> &Handle {
         &self.tokio_handle
     }
 }
+
+fn duration_to_nanos(duration: Duration) -> u64 {
+    duration.as_nanos().try_into().unwrap_or(u64::MAX)
+}
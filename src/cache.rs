@@ -1,11 +1,13 @@
-use crate::{cancel_error, Auxiliary, Error, Id, WriteEnd};
+use crate::{scope, Auxiliary, Error, Id, WriteEnd};
 
 use std::{
-    future::Future,
+    future::{self, Future},
     ops::{Deref, DerefMut},
     pin::Pin,
 };
 
+use tokio::time;
+
 #[derive(Debug)]
 pub(super) struct WriteEndWithCachedId {
     pub(super) inner: WriteEnd,
@@ -79,8 +81,8 @@ impl WriteEndWithCachedId {
         &mut self,
         future: Pin<&mut (dyn Future<Output = Result<R, Error>> + Send)>,
     ) -> Result<R, Error> {
-        let cancel_err = || Err(cancel_error());
         let auxiliary = self.inner.get_auxiliary();
+        let cancel_err = || Err(auxiliary.cancel_error());
 
         let cancel_token = &auxiliary.cancel_token;
 
@@ -88,11 +90,25 @@ impl WriteEndWithCachedId {
             return cancel_err();
         }
 
+        let request_timeout = auxiliary
+            .request_timeout()
+            .filter(|_| !scope::is_request_timeout_overridden());
+        let timeout = async move {
+            match request_timeout {
+                Some(timeout) => {
+                    time::sleep(timeout).await;
+                    timeout
+                }
+                None => future::pending().await,
+            }
+        };
+
         tokio::select! {
             biased;
 
             _ = cancel_token.cancelled() => cancel_err(),
             res = future => res,
+            timeout = timeout => Err(Error::TimedOut { timeout }),
         }
     }
 
@@ -107,6 +123,22 @@ impl WriteEndWithCachedId {
         Func: FnOnce(&mut WriteEnd, Id) -> Result<F, Error> + Send,
         F: Future<Output = Result<(Id, R), Error>> + Send + 'static,
     {
+        // Held until the response is received.
+        let _permit = match self.get_auxiliary().outstanding_requests.clone() {
+            Some(semaphore) => Some(
+                self.cancel_if_task_failed(async move {
+                    Ok::<_, Error>(
+                        semaphore
+                            .acquire_owned()
+                            .await
+                            .expect("outstanding_requests is never closed"),
+                    )
+                })
+                .await?,
+            ),
+            None => None,
+        };
+
         let id = self.get_id_mut();
         let write_end = &mut self.inner;
 
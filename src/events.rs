@@ -0,0 +1,72 @@
+use std::sync::Mutex;
+
+use tokio::sync::watch;
+
+/// Latest event about the state of the connection,
+/// obtained via [`Sftp::connection_events`](crate::Sftp::connection_events).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectionEvent {
+    /// The sftp session is being initialized.
+    Connecting,
+
+    /// The sftp session is initialized and ready for use.
+    Connected,
+
+    /// No response is received within one
+    /// [`keepalive_interval`](crate::SftpOptions::keepalive_interval)
+    /// after a keepalive is sent.
+    ///
+    /// [`ConnectionEvent::Connected`] is emitted once any response
+    /// is received again.
+    KeepaliveMissed,
+
+    /// The connection is closed, no more event would be emitted.
+    Closed(CloseReason),
+}
+
+/// Why the connection is closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CloseReason {
+    /// [`Sftp`](crate::Sftp) is closed or dropped along with everything
+    /// created from it.
+    Requested,
+
+    /// The background tasks failed with the error message.
+    Failed(String),
+}
+
+#[derive(Debug)]
+pub(super) struct EventSender {
+    /// Serializes `send` so that no event can overwrite
+    /// [`ConnectionEvent::Closed`].
+    lock: Mutex<()>,
+    sender: watch::Sender<ConnectionEvent>,
+    /// Keep one receiver alive so that `send` always succeeds.
+    receiver: watch::Receiver<ConnectionEvent>,
+}
+
+impl EventSender {
+    pub(super) fn new() -> Self {
+        let (sender, receiver) = watch::channel(ConnectionEvent::Connecting);
+
+        Self {
+            lock: Mutex::new(()),
+            sender,
+            receiver,
+        }
+    }
+
+    pub(super) fn send(&self, event: ConnectionEvent) {
+        let _guard = self.lock.lock().unwrap_or_else(|err| err.into_inner());
+
+        if !matches!(*self.receiver.borrow(), ConnectionEvent::Closed(_)) {
+            self.sender.send(event).ok();
+        }
+    }
+
+    pub(super) fn subscribe(&self) -> watch::Receiver<ConnectionEvent> {
+        self.receiver.clone()
+    }
+}
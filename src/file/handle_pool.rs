@@ -0,0 +1,173 @@
+use super::{File, OpenOptions};
+use crate::{Error, SftpHandle};
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    sync::Mutex,
+};
+
+#[derive(Debug)]
+struct Entry {
+    file: File,
+    /// Key into `Inner::order`, bumped every time the entry is accessed.
+    tick: u64,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    entries: HashMap<Box<Path>, Entry>,
+    /// Entries ordered from the least recently used to the most
+    /// recently used.
+    order: BTreeMap<u64, Box<Path>>,
+    next_tick: u64,
+}
+
+impl Inner {
+    fn bump_tick(&mut self) -> u64 {
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        tick
+    }
+}
+
+/// A cache of read-only [`File`]s keyed by path, created by
+/// [`crate::Sftp::handle_pool`].
+///
+/// Opening a path that is already cached returns a new [`File`] sharing the
+/// cached handle, without sending any request to the server.
+///
+/// Once the pool is full, the least recently used handle is evicted and
+/// closed after all [`File`]s sharing it are dropped.
+///
+/// Since the handles are kept open, changes made to the remote file (e.g.
+/// it being replaced by a rename) after it is cached are not observed, use
+/// [`HandlePool::invalidate`] to drop the cached handle.
+#[derive(Debug)]
+pub struct HandlePool {
+    sftp: SftpHandle,
+    inner: Mutex<Inner>,
+    capacity: usize,
+}
+
+impl HandlePool {
+    pub(crate) fn new(sftp: SftpHandle, capacity: usize) -> Self {
+        Self {
+            sftp,
+            inner: Mutex::new(Inner::default()),
+            capacity,
+        }
+    }
+
+    /// Maximum number of handles kept open by the pool.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of handles currently kept open by the pool.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    /// Return `true` if the pool does not keep any handle open.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Open `path` in read-only mode, reusing the cached handle if there is
+    /// one.
+    ///
+    /// The returned [`File`] always starts at offset 0.
+    pub async fn open(&self, path: impl AsRef<Path>) -> Result<File, Error> {
+        let path = path.as_ref();
+
+        if let Some(file) = self.get(path) {
+            return Ok(file);
+        }
+
+        let file = OpenOptions::new(self.sftp.clone())
+            .read(true)
+            .open(path)
+            .await?;
+
+        self.insert(path, file.clone());
+
+        Ok(file)
+    }
+
+    fn get(&self, path: &Path) -> Option<File> {
+        let mut inner = self.inner.lock().unwrap();
+        let tick = inner.bump_tick();
+
+        let Inner { entries, order, .. } = &mut *inner;
+
+        let entry = entries.get_mut(path)?;
+        let path = order
+            .remove(&entry.tick)
+            .expect("every entry must have a corresponding order");
+        order.insert(tick, path);
+        entry.tick = tick;
+
+        Some(entry.file.clone())
+    }
+
+    fn insert(&self, path: &Path, file: File) {
+        // Evicted files are dropped after the lock is released, since
+        // dropping them closes the handle in the background.
+        let mut evicted = Vec::new();
+
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.entries.contains_key(path) {
+            // Another task has opened the same path concurrently,
+            // keep the existing handle.
+            return;
+        }
+
+        while inner.entries.len() >= self.capacity {
+            let lru_path = match inner.order.keys().next().copied() {
+                Some(tick) => inner.order.remove(&tick).unwrap(),
+                None => break,
+            };
+            let entry = inner
+                .entries
+                .remove(&lru_path)
+                .expect("every order must have a corresponding entry");
+            evicted.push(entry.file);
+        }
+
+        let tick = inner.bump_tick();
+        let path: Box<Path> = path.into();
+        inner.order.insert(tick, path.clone());
+        inner.entries.insert(path, Entry { file, tick });
+
+        drop(inner);
+        drop(evicted);
+    }
+
+    /// Drop the cached handle of `path`, if any.
+    ///
+    /// The handle is closed after all [`File`]s sharing it are dropped.
+    pub fn invalidate(&self, path: impl AsRef<Path>) {
+        let mut inner = self.inner.lock().unwrap();
+
+        let entry = inner.entries.remove(path.as_ref());
+        if let Some(entry) = &entry {
+            inner.order.remove(&entry.tick);
+        }
+
+        drop(inner);
+        drop(entry);
+    }
+
+    /// Drop all cached handles.
+    pub fn clear(&self) {
+        let entries = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.order.clear();
+            std::mem::take(&mut inner.entries)
+        };
+
+        drop(entries);
+    }
+}
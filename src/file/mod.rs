@@ -1,7 +1,7 @@
 use crate::{
-    lowlevel::{self, CreateFlags, Data, Extensions, FileAttrs, Handle},
+    lowlevel::{self, CheckFile, CreateFlags, Data, Extensions, FileAttrs, Handle, StatVfs},
     metadata::{MetaData, MetaDataBuilder, Permissions},
-    Auxiliary, Error, Id, OwnedHandle, SftpHandle, WriteEnd, WriteEndWithCachedId,
+    Auxiliary, Error, Id, IoStats, OwnedHandle, SftpHandle, WriteEnd, WriteEndWithCachedId,
 };
 
 use std::{
@@ -11,9 +11,12 @@ use std::{
     future::Future,
     io::{self, IoSlice},
     num::NonZeroU64,
+    ops::Range,
     path::Path,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
+    time::Instant,
 };
 
 use bytes::{Buf, Bytes, BytesMut};
@@ -23,6 +26,15 @@ use tokio_io_utility::IoSliceExt;
 mod tokio_compat_file;
 pub use tokio_compat_file::{TokioCompatFile, DEFAULT_BUFLEN};
 
+mod open_builder;
+pub use open_builder::OpenBuilder;
+
+mod read_only;
+pub use read_only::ReadOnlyFile;
+
+mod handle_pool;
+pub use handle_pool::HandlePool;
+
 mod utility;
 use utility::{take_bytes, take_io_slices};
 
@@ -34,6 +46,7 @@ pub struct OpenOptions {
     truncate: bool,
     create: bool,
     create_new: bool,
+    mode: Option<Permissions>,
 }
 
 impl OpenOptions {
@@ -44,6 +57,7 @@ impl OpenOptions {
             truncate: false,
             create: false,
             create_new: false,
+            mode: None,
         }
     }
 
@@ -124,32 +138,113 @@ impl OpenOptions {
         self
     }
 
+    /// Sets the permissions a new file is created with.
+    ///
+    /// Only take effect if the file is created, i.e. either
+    /// [`OpenOptions::create`] or [`OpenOptions::create_new`] is set to `true`
+    /// and the file does not exist.
+    ///
+    /// Like `open(2)`, the permissions are subject to the umask of
+    /// the sftp server. By default, the server decides the permissions.
+    pub fn mode(&mut self, mode: Permissions) -> &mut Self {
+        self.mode = Some(mode);
+        self
+    }
+
     /// # Cancel Safety
     ///
     /// This function is cancel safe.
     pub async fn open(&self, path: impl AsRef<Path>) -> Result<File, Error> {
-        OpenOptions::open_inner(
+        let filename = path.as_ref();
+        let mut write_end = self.sftp.clone().write_end();
+
+        let params = Self::open_params(
             self.options,
             self.truncate,
             self.create,
             self.create_new,
-            path.as_ref(),
-            self.sftp.clone().write_end(),
-        )
-        .await
+            self.mode,
+            filename,
+        );
+
+        let handle = write_end
+            .send_request(|write_end, id| Ok(write_end.send_open_file_request(id, params)?.wait()))
+            .await?;
+
+        Ok(Self::new_file(self.options, write_end, handle, filename))
     }
 
-    pub(super) async fn open_inner(
+    /// Open the file and query its metadata in one round trip.
+    ///
+    /// The open and stat requests are sent together, so it takes
+    /// about as long as [`OpenOptions::open`] while saving a
+    /// separate [`File::metadata`] call.
+    ///
+    /// NOTE that the metadata is queried on `path` instead of the
+    /// opened handle, it relies on the server processing requests in
+    /// order (which openssh-portable does) to return the metadata of
+    /// the file just opened or created.
+    ///
+    /// # Cancel Safety
+    ///
+    /// This function is cancel safe.
+    pub async fn open_with_metadata(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<(File, MetaData), Error> {
+        async fn inner(this: &OpenOptions, path: &Path) -> Result<(File, MetaData), Error> {
+            let mut write_end = this.sftp.clone().write_end();
+
+            let params = OpenOptions::open_params(
+                this.options,
+                this.truncate,
+                this.create,
+                this.create_new,
+                this.mode,
+                path,
+            );
+
+            let open_id = write_end.get_id_mut();
+            let stat_id = write_end.create_response_id();
+
+            let open_future = write_end.send_open_file_request(open_id, params)?.wait();
+            let stat_future = write_end
+                .send_stat_request(stat_id, Cow::Borrowed(path))?
+                .wait();
+
+            // Both requests are already added to write buffer, so wakeup
+            // the `flush_task` for each of them.
+            write_end.get_auxiliary().wakeup_flush_task();
+            write_end.get_auxiliary().wakeup_flush_task();
+
+            let (open_id, handle) = write_end.cancel_if_task_failed(open_future).await?;
+            write_end.cache_id_mut(open_id);
+
+            // Create the file first so that the handle is closed
+            // even if stat fails.
+            let mut file = OpenOptions::new_file(this.options, write_end, handle, path);
+
+            let write_end = &mut file.inner.write_end;
+            let (stat_id, attrs) = write_end.cancel_if_task_failed(stat_future).await?;
+            write_end.cache_id_mut(stat_id);
+
+            Ok((file, MetaData::new(attrs)))
+        }
+
+        inner(self, path.as_ref()).await
+    }
+
+    fn open_params<'a>(
         options: lowlevel::OpenOptions,
         truncate: bool,
         create: bool,
         create_new: bool,
-        filename: &Path,
-        mut write_end: WriteEndWithCachedId,
-    ) -> Result<File, Error> {
+        mode: Option<Permissions>,
+        filename: &'a Path,
+    ) -> lowlevel::OpenFileRequest<'a> {
         let filename = Cow::Borrowed(filename);
 
-        let params = if create || create_new {
+        if create || create_new {
             let flags = if create_new {
                 CreateFlags::Excl
             } else if truncate {
@@ -158,23 +253,55 @@ impl OpenOptions {
                 CreateFlags::None
             };
 
-            options.create(filename, flags, FileAttrs::new())
+            let mut attrs = MetaDataBuilder::new();
+            if let Some(mode) = mode {
+                attrs.permissions(mode);
+            }
+
+            options.create(filename, flags, attrs.create().into_inner())
         } else {
             options.open(filename)
-        };
+        }
+    }
 
-        let handle = write_end
-            .send_request(|write_end, id| Ok(write_end.send_open_file_request(id, params)?.wait()))
-            .await?;
+    fn new_file(
+        options: lowlevel::OpenOptions,
+        write_end: WriteEndWithCachedId,
+        handle: lowlevel::HandleOwned,
+        path: &Path,
+    ) -> File {
+        if options.get_write() {
+            // The file might have been truncated or created by the open request.
+            write_end.get_auxiliary().invalidate_read_cache(path);
+        }
 
-        Ok(File {
+        File {
             inner: OwnedHandle::new(write_end, handle),
+            path: path.into(),
+            options,
 
             is_readable: options.get_read(),
             is_writable: options.get_write(),
             need_flush: false,
             offset: 0,
-        })
+        }
+    }
+
+    pub(super) async fn open_inner(
+        options: lowlevel::OpenOptions,
+        truncate: bool,
+        create: bool,
+        create_new: bool,
+        filename: &Path,
+        mut write_end: WriteEndWithCachedId,
+    ) -> Result<File, Error> {
+        let params = Self::open_params(options, truncate, create, create_new, None, filename);
+
+        let handle = write_end
+            .send_request(|write_end, id| Ok(write_end.send_open_file_request(id, params)?.wait()))
+            .await?;
+
+        Ok(Self::new_file(options, write_end, handle, filename))
     }
 }
 
@@ -189,6 +316,9 @@ impl OpenOptions {
 #[derive(Debug)]
 pub struct File {
     inner: OwnedHandle,
+    /// Path and options the file is opened with, used by [`File::try_clone`].
+    path: Arc<Path>,
+    options: lowlevel::OpenOptions,
 
     is_readable: bool,
     is_writable: bool,
@@ -200,6 +330,8 @@ impl Clone for File {
     fn clone(&self) -> Self {
         Self {
             inner: self.inner.clone(),
+            path: self.path.clone(),
+            options: self.options,
             is_writable: self.is_writable,
             is_readable: self.is_readable,
             need_flush: false,
@@ -224,7 +356,6 @@ impl File {
     }
 }
 
-#[cfg(feature = "__ci-tests")]
 impl File {
     /// The maximum amount of bytes that can be written in one request.
     /// Writing more than that, then your write will be split into multiple requests
@@ -272,7 +403,17 @@ impl File {
     {
         self.check_for_writable()?;
 
-        self.inner.send_request(f).await
+        let res = self.inner.send_request(f).await;
+        self.invalidate_read_cache();
+        res
+    }
+
+    /// Invalidate the content of this file cached by
+    /// [`crate::fs::Fs::read`], called after every modification.
+    pub(super) fn invalidate_read_cache(&self) {
+        if self.is_writable {
+            self.get_auxiliary().invalidate_read_cache(&self.path);
+        }
     }
 
     fn check_for_readable_io_err(&self) -> Result<(), io::Error> {
@@ -309,7 +450,47 @@ impl File {
     ///
     /// This function is cancel safe.
     pub async fn close(self) -> Result<(), Error> {
-        self.inner.close().await
+        if !self.is_writable || self.get_auxiliary().read_cache().is_none() {
+            return self.inner.close().await;
+        }
+
+        let write_end = self.inner.write_end.clone();
+        let res = self.inner.close().await;
+        write_end.get_auxiliary().invalidate_read_cache(&self.path);
+        res
+    }
+
+    /// Creates a new [`File`] by opening the path this file is opened
+    /// with again, using the same read, write and append options.
+    ///
+    /// Unlike [`File::clone`], the returned [`File`] has its own file handle
+    /// on the remote, while it starts at the same offset as `self`.
+    ///
+    /// NOTE that if the file has been renamed or removed since it was
+    /// opened, then this would open whatever is at the path now or fail.
+    ///
+    /// # Cancel Safety
+    ///
+    /// This function is cancel safe.
+    pub async fn try_clone(&self) -> Result<Self, Error> {
+        let mut file = OpenOptions::open_inner(
+            self.options,
+            false,
+            false,
+            false,
+            &self.path,
+            self.inner.write_end.clone(),
+        )
+        .await?;
+        file.offset = self.offset;
+
+        Ok(file)
+    }
+
+    /// Return the I/O statistics of the underlying file handle,
+    /// which is shared with the clones of this [`File`].
+    pub fn stats(&self) -> IoStats {
+        self.inner.stats.get()
     }
 
     /// Change the metadata of a file or a directory.
@@ -395,6 +576,59 @@ impl File {
         .map(MetaData::new)
     }
 
+    /// Returns statistics of the filesystem the underlying file resides on.
+    ///
+    /// # Precondition
+    ///
+    /// Require extension `fstatvfs`
+    ///
+    /// You can check it with [`Sftp::support_statvfs`](crate::sftp::Sftp::support_statvfs).
+    pub async fn statvfs(&mut self) -> Result<StatVfs, Error> {
+        if !self
+            .get_auxiliary()
+            .extensions()
+            .contains(Extensions::FSTATVFS)
+        {
+            return Err(Error::UnsupportedExtension(&"fstatvfs"));
+        }
+
+        self.send_readable_request(|write_end, handle, id| {
+            Ok(write_end.send_fstatvfs_request(id, handle)?.wait())
+        })
+        .await
+    }
+
+    /// Ask the server to hash `len` bytes of the file starting from
+    /// `offset`, so that a transfer can be verified without downloading
+    /// the file again.
+    ///
+    /// The server uses the first algorithm it supports in the
+    /// comma-separated `algorithms`, e.g. `"sha256,sha1,md5"`.
+    ///
+    /// If `len` is `0`, then the file is hashed till EOF. If `block_size`
+    /// is not `0`, then each block of `block_size` bytes is hashed
+    /// separately.
+    ///
+    /// # Precondition
+    ///
+    /// Require extension `check-file`, which is not supported by
+    /// openssh-portable. Servers that do not support it return
+    /// [`lowlevel::SftpErrorKind::OpUnsupported`].
+    pub async fn check_file(
+        &mut self,
+        algorithms: &str,
+        offset: u64,
+        len: u64,
+        block_size: u32,
+    ) -> Result<CheckFile, Error> {
+        self.send_readable_request(|write_end, handle, id| {
+            Ok(write_end
+                .send_check_file_handle_request(id, handle, algorithms, offset, len, block_size)?
+                .wait())
+        })
+        .await
+    }
+
     /// * `n` - number of bytes to read in
     ///
     /// If the [`File`] has reached EOF or `n == 0`, then `None` is returned.
@@ -422,6 +656,8 @@ impl File {
             _ => std::unreachable!("Expect Data::Buffer"),
         };
 
+        self.inner.stats.record_read(buffer.len());
+
         // Adjust offset
         Pin::new(self).start_seek(io::SeekFrom::Current(n as i64))?;
 
@@ -457,6 +693,8 @@ impl File {
         })
         .await?;
 
+        self.inner.stats.record_written(n as usize);
+
         // Adjust offset
         Pin::new(self).start_seek(io::SeekFrom::Current(n as i64))?;
 
@@ -493,6 +731,8 @@ impl File {
         })
         .await?;
 
+        self.inner.stats.record_written(n as usize);
+
         // Adjust offset
         Pin::new(self).start_seek(io::SeekFrom::Current(n as i64))?;
 
@@ -527,6 +767,8 @@ impl File {
         })
         .await?;
 
+        self.inner.stats.record_written(n);
+
         // Adjust offset
         Pin::new(self).start_seek(io::SeekFrom::Current(n.try_into().unwrap()))?;
 
@@ -567,6 +809,184 @@ impl File {
         Ok(buffer)
     }
 
+    /// Read exactly `len` bytes starting at `offset` into `buffer`,
+    /// following the semantics of http range requests:
+    ///
+    /// - If the range extends beyond EOF, then it is clamped to EOF and
+    ///   the returned buffer is shorter than `len`.
+    /// - If `offset` is at or beyond EOF and `len != 0`, then
+    ///   [`Error::RangeOutOfBounds`] is returned.
+    ///
+    /// Short reads returned by the server are retried until either `len`
+    /// bytes are read or EOF is reached.
+    ///
+    /// Unlike [`File::read_all`], the offset of this [`File`] is not changed.
+    ///
+    /// # Cancel Safety
+    ///
+    /// This function is cancel safe.
+    pub async fn read_range(
+        &self,
+        offset: u64,
+        len: usize,
+        mut buffer: BytesMut,
+    ) -> Result<BytesMut, Error> {
+        if len == 0 {
+            return Ok(buffer);
+        }
+
+        let mut file = self.clone();
+        Pin::new(&mut file).start_seek(io::SeekFrom::Start(offset))?;
+
+        let start = buffer.len();
+        buffer.reserve(len);
+
+        let mut n = len;
+        while n > 0 {
+            let cnt = buffer.len();
+            if let Some(bytes) = file
+                .read(n.try_into().unwrap_or(u32::MAX), buffer.split_off(cnt))
+                .await?
+            {
+                n -= bytes.len();
+                buffer.unsplit(bytes);
+            } else {
+                break;
+            }
+        }
+
+        if buffer.len() == start {
+            Err(Error::RangeOutOfBounds {
+                offset,
+                len: len as u64,
+            })
+        } else {
+            Ok(buffer)
+        }
+    }
+
+    /// Read all of `ranges` of the file at once.
+    ///
+    /// Ranges that overlap or are adjacent are merged into one read
+    /// request as long as it does not exceed [`File::max_read_len`], and
+    /// the requests for all merged ranges are sent before waiting for
+    /// any of their responses, which saves round trips for readers that
+    /// issue many small reads, e.g. archive indexers.
+    ///
+    /// The returned buffers are in the same order as `ranges`. Like
+    /// [`File::read_range`], each of them is clamped to EOF, but a range
+    /// at or beyond EOF returns an empty buffer instead of an error.
+    ///
+    /// The offset of this [`File`] is not changed.
+    ///
+    /// # Cancel Safety
+    ///
+    /// This function is cancel safe.
+    pub async fn read_ranges(&self, ranges: &[Range<u64>]) -> Result<Vec<Bytes>, Error> {
+        self.check_for_readable()?;
+
+        let max_read_len = u64::from(self.max_read_len_impl());
+
+        let mut indices: Vec<usize> = (0..ranges.len())
+            .filter(|i| !ranges[*i].is_empty())
+            .collect();
+        indices.sort_unstable_by_key(|i| ranges[*i].start);
+
+        // Merged ranges to read and, for each of `ranges`, the index
+        // of the merged range containing it.
+        let mut spans: Vec<Range<u64>> = Vec::new();
+        let mut span_indices = vec![0; ranges.len()];
+
+        for i in indices {
+            let range = &ranges[i];
+
+            match spans.last_mut() {
+                Some(span)
+                    if range.start <= span.end
+                        && range.end.max(span.end) - span.start <= max_read_len =>
+                {
+                    span.end = span.end.max(range.end);
+                }
+                _ => spans.push(range.clone()),
+            }
+
+            span_indices[i] = spans.len() - 1;
+        }
+
+        let mut file = self.clone();
+
+        let mut futures = Vec::with_capacity(spans.len());
+        for span in &spans {
+            let len = min(span.end - span.start, max_read_len) as u32;
+
+            let id = file.inner.write_end.create_response_id();
+            let (write_end, handle) = file.get_inner();
+            futures.push((
+                write_end
+                    .send_read_request(id, handle, span.start, len, None)?
+                    .wait(),
+                Instant::now(),
+            ));
+
+            // Requests is already added to write buffer, so wakeup
+            // the `flush_task` for each of them.
+            file.get_auxiliary().wakeup_flush_task();
+        }
+
+        let mut buffers = Vec::with_capacity(spans.len());
+        for (span, (future, sent_at)) in spans.iter().zip(futures) {
+            let write_end = &mut file.inner.write_end;
+            let (id, data) = write_end.cancel_if_task_failed(future).await?;
+            write_end.cache_id_mut(id);
+
+            file.inner.stats.record_request(sent_at);
+
+            let mut buffer = match data {
+                Data::Buffer(buffer) => buffer,
+                Data::Eof => BytesMut::new(),
+                _ => std::unreachable!("Expect Data::Buffer"),
+            };
+            file.inner.stats.record_read(buffer.len());
+
+            // Read the rest of the range on short reads.
+            let len = span.end - span.start;
+            let read = buffer.len() as u64;
+            if read != 0 && read < len {
+                let rest_len = (len - read).try_into().unwrap_or(usize::MAX);
+                match file
+                    .read_range(span.start + read, rest_len, BytesMut::new())
+                    .await
+                {
+                    Ok(rest) => buffer.unsplit(rest),
+                    Err(Error::RangeOutOfBounds { .. }) => (),
+                    Err(err) => return Err(err),
+                }
+            }
+
+            buffers.push(buffer.freeze());
+        }
+
+        Ok(ranges
+            .iter()
+            .zip(span_indices)
+            .map(|(range, span_index)| {
+                if range.is_empty() {
+                    return Bytes::new();
+                }
+
+                let span_start = spans[span_index].start;
+                let buffer = &buffers[span_index];
+
+                // The range is within the merged range, so both fit in
+                // `usize` once clamped to `buffer.len()`.
+                let start = min(range.start - span_start, buffer.len() as u64) as usize;
+                let end = min(range.end - span_start, buffer.len() as u64) as usize;
+
+                buffer.slice(start..end)
+            })
+            .collect())
+    }
+
     /// Write entire `buf`.
     ///
     /// # Cancel Safety
@@ -647,7 +1067,13 @@ impl File {
         self.offset
     }
 
-    async fn copy_to_impl(&mut self, dst: &mut Self, n: u64) -> Result<(), Error> {
+    async fn copy_range_impl(
+        &mut self,
+        offset: u64,
+        dst: &Self,
+        dst_offset: u64,
+        n: u64,
+    ) -> Result<(), Error> {
         if !self
             .inner
             .get_auxiliary()
@@ -659,21 +1085,29 @@ impl File {
 
         dst.check_for_writable()?;
 
-        let offset = self.offset;
+        let res = self
+            .send_readable_request(|write_end, handle, id| {
+                Ok(write_end
+                    .send_copy_data_request(
+                        id,
+                        handle,
+                        offset,
+                        n,
+                        Cow::Borrowed(&dst.inner.handle),
+                        dst_offset,
+                    )?
+                    .wait())
+            })
+            .await;
 
-        self.send_readable_request(|write_end, handle, id| {
-            Ok(write_end
-                .send_copy_data_request(
-                    id,
-                    handle,
-                    offset,
-                    n,
-                    Cow::Borrowed(&dst.inner.handle),
-                    dst.offset,
-                )?
-                .wait())
-        })
-        .await?;
+        dst.invalidate_read_cache();
+
+        res
+    }
+
+    async fn copy_to_impl(&mut self, dst: &mut Self, n: u64) -> Result<(), Error> {
+        self.copy_range_impl(self.offset, dst, dst.offset, n)
+            .await?;
 
         // Adjust offset
         Pin::new(self).start_seek(io::SeekFrom::Current(n.try_into().unwrap()))?;
@@ -682,6 +1116,38 @@ impl File {
         Ok(())
     }
 
+    /// Copy `n` bytes of data starting at `offset` of `self` to `dst`
+    /// starting at `dst_offset`, or until EOF of `self` if `n` is `None`.
+    ///
+    /// Unlike [`File::copy_to`], the offsets are given explicitly and
+    /// the offset of `self` and `dst` are unchanged, which allows e.g.
+    /// appending one remote file to another without downloading it.
+    ///
+    /// `self` and `dst` can refer to the same file as long as the
+    /// ranges do not overlap.
+    ///
+    /// # Precondition
+    ///
+    /// Requires extension `copy-data`.
+    /// For [openssh-portable], this is available from V_9_0_P1.
+    ///
+    /// You can check it with [`Sftp::support_copy`](crate::sftp::Sftp::support_copy).
+    ///
+    /// If the extension is not supported by the server, this function
+    /// would fail with [`Error::UnsupportedExtension`].
+    ///
+    /// [openssh-portable]: https://github.com/openssh/openssh-portable
+    pub async fn copy_range_to(
+        &mut self,
+        offset: u64,
+        dst: &Self,
+        dst_offset: u64,
+        n: Option<NonZeroU64>,
+    ) -> Result<(), Error> {
+        self.copy_range_impl(offset, dst, dst_offset, n.map_or(0, NonZeroU64::get))
+            .await
+    }
+
     /// Copy `n` bytes of data from `self` to `dst`.
     ///
     /// The server MUST copy the data exactly as if the data is copied
@@ -0,0 +1,101 @@
+use super::{File, OpenOptions};
+
+use crate::{
+    metadata::{MetaData, Permissions},
+    Error,
+};
+
+use std::{
+    future::{Future, IntoFuture},
+    path::Path,
+    pin::Pin,
+};
+
+/// Builder returned by [`Sftp::open`](crate::Sftp::open) and
+/// [`Sftp::create`](crate::Sftp::create), which can be awaited directly
+/// to open the file.
+///
+/// It takes the same options as [`OpenOptions`], with the defaults set by
+/// the function it is returned from.
+#[derive(Debug, Clone)]
+#[must_use = "OpenBuilder does nothing unless awaited"]
+pub struct OpenBuilder<P> {
+    options: OpenOptions,
+    path: P,
+}
+
+impl<P> OpenBuilder<P> {
+    pub(crate) fn new(options: OpenOptions, path: P) -> Self {
+        Self { options, path }
+    }
+
+    /// See [`OpenOptions::read`].
+    pub fn read(mut self, read: bool) -> Self {
+        self.options.read(read);
+        self
+    }
+
+    /// See [`OpenOptions::write`].
+    pub fn write(mut self, write: bool) -> Self {
+        self.options.write(write);
+        self
+    }
+
+    /// See [`OpenOptions::append`].
+    pub fn append(mut self, append: bool) -> Self {
+        self.options.append(append);
+        self
+    }
+
+    /// See [`OpenOptions::truncate`].
+    pub fn truncate(mut self, truncate: bool) -> Self {
+        self.options.truncate(truncate);
+        self
+    }
+
+    /// See [`OpenOptions::create`].
+    pub fn create(mut self, create: bool) -> Self {
+        self.options.create(create);
+        self
+    }
+
+    /// See [`OpenOptions::create_new`].
+    pub fn create_new(mut self, create_new: bool) -> Self {
+        self.options.create_new(create_new);
+        self
+    }
+
+    /// See [`OpenOptions::mode`].
+    pub fn mode(mut self, mode: Permissions) -> Self {
+        self.options.mode(mode);
+        self
+    }
+}
+
+impl<P: AsRef<Path>> OpenBuilder<P> {
+    /// Open the file and query its metadata in one round trip.
+    ///
+    /// See [`OpenOptions::open_with_metadata`].
+    ///
+    /// # Cancel Safety
+    ///
+    /// This function is cancel safe.
+    pub async fn with_metadata(self) -> Result<(File, MetaData), Error> {
+        self.options.open_with_metadata(self.path).await
+    }
+}
+
+impl<P: AsRef<Path>> IntoFuture for OpenBuilder<P> {
+    type Output = Result<File, Error>;
+    type IntoFuture = Pin<Box<dyn Future<Output = Self::Output> + Send + 'static>>;
+
+    /// # Cancel Safety
+    ///
+    /// The returned future is cancel safe.
+    fn into_future(self) -> Self::IntoFuture {
+        let Self { options, path } = self;
+        let path = path.as_ref().to_path_buf();
+
+        Box::pin(async move { options.open(path).await })
+    }
+}
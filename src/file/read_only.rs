@@ -0,0 +1,105 @@
+use super::File;
+
+use crate::{metadata::MetaData, Error};
+
+use std::{
+    io,
+    ops::Range,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::{Bytes, BytesMut};
+use tokio::io::AsyncSeek;
+
+/// A read-only view of [`File`].
+///
+/// Unlike [`File`], it does not provide any method that modifies
+/// the remote file, so it can be passed to components that should
+/// only be given read access.
+///
+/// It can be obtained from [`SftpReadOnly::open`](crate::SftpReadOnly::open)
+/// or converted from [`File`].
+#[derive(Debug, Clone)]
+pub struct ReadOnlyFile(File);
+
+impl From<File> for ReadOnlyFile {
+    fn from(file: File) -> Self {
+        Self(file)
+    }
+}
+
+impl ReadOnlyFile {
+    /// Close the [`ReadOnlyFile`], send the close request
+    /// if this is the last reference.
+    ///
+    /// # Cancel Safety
+    ///
+    /// This function is cancel safe.
+    pub async fn close(self) -> Result<(), Error> {
+        self.0.close().await
+    }
+
+    /// Queries metadata about the remote file.
+    pub async fn metadata(&mut self) -> Result<MetaData, Error> {
+        self.0.metadata().await
+    }
+
+    /// See [`File::read`].
+    ///
+    /// # Cancel Safety
+    ///
+    /// This function is cancel safe.
+    pub async fn read(&mut self, n: u32, buffer: BytesMut) -> Result<Option<BytesMut>, Error> {
+        self.0.read(n, buffer).await
+    }
+
+    /// See [`File::read_all`].
+    ///
+    /// # Cancel Safety
+    ///
+    /// This function is cancel safe.
+    pub async fn read_all(&mut self, n: usize, buffer: BytesMut) -> Result<BytesMut, Error> {
+        self.0.read_all(n, buffer).await
+    }
+
+    /// See [`File::read_range`].
+    ///
+    /// # Cancel Safety
+    ///
+    /// This function is cancel safe.
+    pub async fn read_range(
+        &self,
+        offset: u64,
+        len: usize,
+        buffer: BytesMut,
+    ) -> Result<BytesMut, Error> {
+        self.0.read_range(offset, len, buffer).await
+    }
+
+    /// See [`File::read_ranges`].
+    ///
+    /// # Cancel Safety
+    ///
+    /// This function is cancel safe.
+    pub async fn read_ranges(&self, ranges: &[Range<u64>]) -> Result<Vec<Bytes>, Error> {
+        self.0.read_ranges(ranges).await
+    }
+
+    /// Return the offset of the file.
+    pub fn offset(&self) -> u64 {
+        self.0.offset()
+    }
+}
+
+impl AsyncSeek for ReadOnlyFile {
+    /// Only adjust local offset, same as [`File`].
+    fn start_seek(mut self: Pin<&mut Self>, position: io::SeekFrom) -> io::Result<()> {
+        Pin::new(&mut self.0).start_seek(position)
+    }
+
+    /// This function is a no-op, it simply return the offset.
+    fn poll_complete(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Pin::new(&mut self.0).poll_complete(cx)
+    }
+}
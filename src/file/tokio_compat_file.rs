@@ -1,5 +1,4 @@
 use crate::{
-    cancel_error,
     file::{utility::take_io_slices, File},
     lowlevel::{AwaitableDataFuture, AwaitableStatusFuture, Handle},
     Buffer, Data, Error, Id, WriteEnd,
@@ -17,6 +16,7 @@ use std::{
     ops::{Deref, DerefMut},
     pin::Pin,
     task::{Context, Poll},
+    time::Instant,
 };
 
 use bytes::{Buf, Bytes, BytesMut};
@@ -29,13 +29,6 @@ use tokio_util::sync::WaitForCancellationFutureOwned;
 /// The default length of the buffer used in [`TokioCompatFile`].
 pub const DEFAULT_BUFLEN: NonZeroUsize = unsafe { NonZeroUsize::new_unchecked(4096) };
 
-fn sftp_to_io_error(sftp_err: Error) -> io::Error {
-    match sftp_err {
-        Error::IOError(io_error) => io_error,
-        sftp_err => io::Error::new(io::ErrorKind::Other, sftp_err),
-    }
-}
-
 fn send_request<Func, R>(file: &mut File, f: Func) -> Result<R, Error>
 where
     Func: FnOnce(&mut WriteEnd, Id, Cow<'_, Handle>, u64) -> Result<R, Error>,
@@ -56,6 +49,36 @@ where
     Ok(awaitable)
 }
 
+fn send_read_request(
+    file: &mut File,
+    read_futures: &mut VecDeque<ReadFutureElement>,
+    offset: u64,
+    read_len: u32,
+    buffer: BytesMut,
+) -> Result<(), Error> {
+    let future = send_request(file, |write_end, id, handle, _offset| {
+        write_end.send_read_request(id, handle, offset, read_len, Some(buffer))
+    })?
+    .wait();
+
+    read_futures.push_back(ReadFutureElement {
+        future,
+        read_len,
+        sent_at: Instant::now(),
+    });
+
+    Ok(())
+}
+
+fn record_write<T>(file: &File, element: &WriteFutureElement, res: &Result<T, Error>) {
+    let stats = &file.inner.stats;
+    stats.record_request(element.sent_at);
+    if res.is_ok() {
+        stats.record_written(element.write_len);
+    }
+    file.invalidate_read_cache();
+}
+
 /// File that implements [`AsyncRead`], [`AsyncBufRead`], [`AsyncSeek`] and
 /// [`AsyncWrite`], which is compatible with
 /// [`tokio::fs::File`](https://docs.rs/tokio/latest/tokio/fs/struct.File.html).
@@ -69,7 +92,14 @@ pub struct TokioCompatFile {
 
     write_len: usize,
 
-    read_future: Option<AwaitableDataFuture<Buffer>>,
+    /// Read requests sent for consecutive ranges of the file, the first
+    /// one starts at the current offset.
+    read_futures: VecDeque<ReadFutureElement>,
+    /// Number of read requests sent in addition to the one at the
+    /// current offset.
+    read_ahead: usize,
+    /// Offset right after the range of the last read request sent.
+    read_ahead_offset: u64,
     write_futures: VecDeque<WriteFutureElement>,
 
     /// cancellation_fut is not only cancel-safe, but also can be polled after
@@ -80,10 +110,18 @@ pub struct TokioCompatFile {
     cancellation_future: WaitForCancellationFutureOwned,
 }
 
+#[derive(Debug)]
+struct ReadFutureElement {
+    future: AwaitableDataFuture<Buffer>,
+    read_len: u32,
+    sent_at: Instant,
+}
+
 #[derive(Debug)]
 struct WriteFutureElement {
     future: AwaitableStatusFuture<Buffer>,
     write_len: usize,
+    sent_at: Instant,
 }
 
 impl TokioCompatFile {
@@ -107,7 +145,9 @@ impl TokioCompatFile {
 
             write_len: 0,
 
-            read_future: None,
+            read_futures: VecDeque::new(),
+            read_ahead: 0,
+            read_ahead_offset: 0,
             write_futures: VecDeque::new(),
         }
     }
@@ -145,6 +185,27 @@ impl TokioCompatFile {
         }
     }
 
+    /// Return the number of read requests kept in flight ahead of the
+    /// current offset, see [`TokioCompatFile::set_read_ahead`].
+    pub fn read_ahead(&self) -> usize {
+        self.read_ahead
+    }
+
+    /// Keep `requests` read requests in flight for the data right after
+    /// the one being read, so that reading the file sequentially does not
+    /// wait for a round trip per request.
+    ///
+    /// Each of them reads as much as the read that sent it, so at most
+    /// `requests * max_read_len` bytes are buffered. They are discarded
+    /// when the file is seeked, EOF is reached or the server returns
+    /// less data than requested.
+    ///
+    /// It is `0` by default.
+    pub fn set_read_ahead(&mut self, requests: usize) {
+        self.read_ahead = requests;
+        self.read_futures.truncate(requests + 1);
+    }
+
     /// This function is a low-level call.
     ///
     /// It needs to be paired with the `consume` method or
@@ -219,39 +280,47 @@ impl TokioCompatFile {
         let max_read_len = this.inner.max_read_len_impl();
         let amt = min(amt.get(), max_read_len);
 
-        let future = if let Some(future) = this.read_future {
-            // Get the active future.
-            //
-            // The future might read more/less than remaining,
-            // but the offset must be equal to this.offset,
-            // since AsyncSeek::start_seek would reset this.future
-            // if this.offset is changed.
-            future
-        } else {
+        if this.read_futures.is_empty() {
+            // The first future must read from this.offset, which is
+            // guaranteed since AsyncSeek::start_seek would reset
+            // this.read_futures if this.offset is changed.
             this.buffer.reserve(amt as usize);
             let cap = this.buffer.capacity();
             let buffer = this.buffer.split_off(cap - (amt as usize));
 
-            let future = send_request(this.inner, |write_end, id, handle, offset| {
-                write_end.send_read_request(id, handle, offset, amt, Some(buffer))
-            })?
-            .wait();
+            let offset = this.inner.offset;
+            send_read_request(this.inner, this.read_futures, offset, amt, buffer)?;
+            *this.read_ahead_offset = offset + u64::from(amt);
+        }
 
-            // Store it in this.read_future
-            *this.read_future = Some(future);
-            this.read_future
-                .as_mut()
-                .expect("FileFuture::Data is just assigned to self.future!")
-        };
+        while this.read_futures.len() <= *this.read_ahead {
+            let offset = *this.read_ahead_offset;
+            let buffer = BytesMut::with_capacity(amt as usize);
+
+            send_read_request(this.inner, this.read_futures, offset, amt, buffer)?;
+            *this.read_ahead_offset = offset + u64::from(amt);
+        }
 
         if this.cancellation_future.poll(cx).is_ready() {
-            return Poll::Ready(Err(cancel_error()));
+            return Poll::Ready(Err(this.inner.auxiliary().cancel_error()));
         }
 
         // Wait for the future
-        let res = ready!(Pin::new(future).poll(cx));
-        *this.read_future = None;
-        let (id, data) = res?;
+        let element = this
+            .read_futures
+            .front_mut()
+            .expect("read_futures cannot be empty after sending requests");
+        let res = ready!(Pin::new(&mut element.future).poll(cx));
+        let element = this.read_futures.pop_front().unwrap();
+        this.inner.inner.stats.record_request(element.sent_at);
+
+        let (id, data) = match res {
+            Ok(res) => res,
+            Err(err) => {
+                this.read_futures.clear();
+                return Poll::Ready(Err(err));
+            }
+        };
 
         this.inner.inner.cache_id_mut(id);
         match data {
@@ -263,9 +332,20 @@ impl TokioCompatFile {
                 // sftp v3 can at most read in max_read_len bytes.
                 debug_assert!(buffer.len() <= max_read_len as usize);
 
+                this.inner.inner.stats.record_read(buffer.len());
+
+                if buffer.len() < element.read_len as usize {
+                    // Requests sent after it are for the wrong ranges.
+                    this.read_futures.clear();
+                }
+
                 this.buffer.unsplit(buffer);
             }
-            Data::Eof => return Poll::Ready(Ok(())),
+            Data::Eof => {
+                // Requests sent after it are all beyond EOF.
+                this.read_futures.clear();
+                return Poll::Ready(Ok(()));
+            }
             _ => std::unreachable!("Expect Data::Buffer"),
         };
 
@@ -325,7 +405,7 @@ impl TokioCompatFile {
         }
 
         if this.cancellation_future.poll(cx).is_ready() {
-            return Err(sftp_to_io_error(cancel_error()));
+            return Err(io::Error::from(this.inner.auxiliary().cancel_error()));
         }
 
         Ok(())
@@ -342,6 +422,7 @@ impl TokioCompatFile {
         let res = if let Some(element) = this.write_futures.front_mut() {
             let res = ready!(Pin::new(&mut element.future).poll(cx));
             *this.write_len -= element.write_len;
+            record_write(this.inner, element, &res);
             res
         } else {
             // All futures consumed without error
@@ -356,7 +437,7 @@ impl TokioCompatFile {
         // propagate error and recycle id
         this.inner
             .inner
-            .cache_id_mut(res.map_err(sftp_to_io_error)?.0);
+            .cache_id_mut(res.map_err(io::Error::from)?.0);
 
         Poll::Ready(Ok(()))
     }
@@ -380,7 +461,9 @@ impl From<TokioCompatFile> for File {
 /// Reads, writes, and seeks can be performed independently.
 impl Clone for TokioCompatFile {
     fn clone(&self) -> Self {
-        Self::with_capacity(self.inner.clone(), self.buffer_len)
+        let mut file = Self::with_capacity(self.inner.clone(), self.buffer_len);
+        file.read_ahead = self.read_ahead;
+        file
     }
 }
 
@@ -408,7 +491,7 @@ impl AsyncSeek for TokioCompatFile {
 
         if new_offset != prev_offset {
             // Reset future since they are invalidated by change of offset.
-            *this.read_future = None;
+            this.read_futures.clear();
 
             // Reset buffer or consume buffer if necessary.
             if new_offset < prev_offset {
@@ -440,8 +523,7 @@ impl AsyncBufRead for TokioCompatFile {
             let buffer_len = this.buffer_len.get().try_into().unwrap_or(u32::MAX);
             let buffer_len = NonZeroU32::new(buffer_len).unwrap();
 
-            ready!(self.as_mut().poll_read_into_buffer(cx, buffer_len))
-                .map_err(sftp_to_io_error)?;
+            ready!(self.as_mut().poll_read_into_buffer(cx, buffer_len)).map_err(io::Error::from)?;
         }
 
         Poll::Ready(Ok(self.project().buffer))
@@ -475,7 +557,7 @@ impl AsyncRead for TokioCompatFile {
             let n = n.try_into().unwrap_or(u32::MAX);
             let n = NonZeroU32::new(n).unwrap();
 
-            ready!(self.as_mut().poll_read_into_buffer(cx, n)).map_err(sftp_to_io_error)?;
+            ready!(self.as_mut().poll_read_into_buffer(cx, n)).map_err(io::Error::from)?;
         }
 
         let n = min(remaining, self.buffer.len());
@@ -565,7 +647,7 @@ impl AsyncWrite for TokioCompatFile {
         let future = send_request(file, |write_end, id, handle, offset| {
             write_end.send_write_request_buffered(id, handle, offset, Cow::Borrowed(buf))
         })
-        .map_err(sftp_to_io_error)?
+        .map_err(io::Error::from)?
         .wait();
 
         // Since a new request is buffered, flushing is required.
@@ -574,6 +656,7 @@ impl AsyncWrite for TokioCompatFile {
         this.write_futures.push_back(WriteFutureElement {
             future,
             write_len: n as usize,
+            sent_at: Instant::now(),
         });
 
         *self.as_mut().project().write_len = new_write_len;
@@ -600,6 +683,7 @@ impl AsyncWrite for TokioCompatFile {
             let res = if let Some(element) = this.write_futures.front_mut() {
                 let res = ready!(Pin::new(&mut element.future).poll(cx));
                 *this.write_len -= element.write_len;
+                record_write(this.inner, element, &res);
                 res
             } else {
                 // All futures consumed without error
@@ -614,7 +698,7 @@ impl AsyncWrite for TokioCompatFile {
             // propagate error and recycle id
             this.inner
                 .inner
-                .cache_id_mut(res.map_err(sftp_to_io_error)?.0);
+                .cache_id_mut(res.map_err(io::Error::from)?.0);
         }
     }
 
@@ -680,7 +764,7 @@ impl AsyncWrite for TokioCompatFile {
         let future = send_request(file, |write_end, id, handle, offset| {
             write_end.send_write_request_buffered_vectored2(id, handle, offset, &buffers)
         })
-        .map_err(sftp_to_io_error)?
+        .map_err(io::Error::from)?
         .wait();
 
         // Since a new request is buffered, flushing is required.
@@ -689,6 +773,7 @@ impl AsyncWrite for TokioCompatFile {
         this.write_futures.push_back(WriteFutureElement {
             future,
             write_len: n as usize,
+            sent_at: Instant::now(),
         });
 
         *self.as_mut().project().write_len = new_write_len;
@@ -708,13 +793,13 @@ impl AsyncWrite for TokioCompatFile {
 impl TokioCompatFile {
     async fn do_drop(
         mut file: File,
-        read_future: Option<AwaitableDataFuture<Buffer>>,
+        read_futures: VecDeque<ReadFutureElement>,
         write_futures: VecDeque<WriteFutureElement>,
     ) {
-        if let Some(read_future) = read_future {
+        for read_element in read_futures {
             // read_future error is ignored since users are no longer interested
             // in this.
-            if let Ok((id, _)) = read_future.await {
+            if let Ok((id, _)) = read_element.future.await {
                 file.inner.cache_id_mut(id);
             }
         }
@@ -748,12 +833,12 @@ impl PinnedDrop for TokioCompatFile {
         let this = self.as_mut().project();
 
         let file = this.inner.clone();
-        let read_future = this.read_future.take();
+        let read_futures = mem::take(this.read_futures);
         let write_futures = mem::take(this.write_futures);
 
         let cancellation_fut = self.auxiliary().cancel_token.clone().cancelled_owned();
 
-        let do_drop_fut = Self::do_drop(file, read_future, write_futures);
+        let do_drop_fut = Self::do_drop(file, read_futures, write_futures);
 
         self.auxiliary().tokio_handle().spawn(async move {
             tokio::select! {
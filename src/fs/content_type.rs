@@ -0,0 +1,241 @@
+use std::path::Path;
+
+/// Number of bytes read from the start of a file to sniff its content type.
+pub(super) const SNIFF_LEN: u32 = 512;
+
+const OCTET_STREAM: &str = "application/octet-stream";
+const TEXT_PLAIN: &str = "text/plain; charset=utf-8";
+
+/// (offset, magic bytes)
+type Magic = (usize, &'static [u8]);
+
+/// (all of the magic bytes, mime type)
+const MAGIC: &[(&[Magic], &str)] = &[
+    (&[(0, b"\x89PNG\r\n\x1a\n")], "image/png"),
+    (&[(0, b"\xff\xd8\xff")], "image/jpeg"),
+    (&[(0, b"GIF87a")], "image/gif"),
+    (&[(0, b"GIF89a")], "image/gif"),
+    (&[(0, b"RIFF"), (8, b"WEBP")], "image/webp"),
+    // Reserved fields are zero and the size of the DIB header is < 256.
+    (
+        &[(0, b"BM"), (6, b"\0\0\0\0"), (15, b"\0\0\0")],
+        "image/bmp",
+    ),
+    (&[(0, b"\x00\x00\x01\x00")], "image/x-icon"),
+    (&[(0, b"%PDF-")], "application/pdf"),
+    (&[(0, b"%!PS")], "application/postscript"),
+    (&[(0, b"PK\x03\x04")], "application/zip"),
+    // Only deflate is used in practice.
+    (&[(0, b"\x1f\x8b\x08")], "application/gzip"),
+    // Followed by the magic of the first block.
+    (&[(0, b"BZh"), (4, b"1AY&SY")], "application/x-bzip2"),
+    (&[(0, b"\xfd7zXZ\x00")], "application/x-xz"),
+    (&[(0, b"\x28\xb5\x2f\xfd")], "application/zstd"),
+    (&[(0, b"7z\xbc\xaf\x27\x1c")], "application/x-7z-compressed"),
+    (&[(257, b"ustar")], "application/x-tar"),
+    (&[(0, b"\x00asm")], "application/wasm"),
+    // ISO base media files, the major brand tells what they contain.
+    (&[(4, b"ftyp"), (8, b"isom")], "video/mp4"),
+    (&[(4, b"ftyp"), (8, b"iso2")], "video/mp4"),
+    (&[(4, b"ftyp"), (8, b"mp41")], "video/mp4"),
+    (&[(4, b"ftyp"), (8, b"mp42")], "video/mp4"),
+    (&[(4, b"ftyp"), (8, b"avc1")], "video/mp4"),
+    (&[(4, b"ftyp"), (8, b"dash")], "video/mp4"),
+    (&[(4, b"ftyp"), (8, b"M4V ")], "video/mp4"),
+    (&[(4, b"ftyp"), (8, b"M4A ")], "audio/mp4"),
+    (&[(4, b"ftyp"), (8, b"qt  ")], "video/quicktime"),
+    (&[(4, b"ftyp"), (8, b"heic")], "image/heic"),
+    (&[(4, b"ftyp"), (8, b"heix")], "image/heic"),
+    (&[(4, b"ftyp"), (8, b"mif1")], "image/heif"),
+    (&[(4, b"ftyp"), (8, b"avif")], "image/avif"),
+    (&[(0, b"\x1a\x45\xdf\xa3")], "video/webm"),
+    (&[(0, b"OggS")], "audio/ogg"),
+    (&[(0, b"fLaC")], "audio/flac"),
+    // Followed by the major version (2 to 4) and the revision (0).
+    (&[(0, b"ID3\x02\x00")], "audio/mpeg"),
+    (&[(0, b"ID3\x03\x00")], "audio/mpeg"),
+    (&[(0, b"ID3\x04\x00")], "audio/mpeg"),
+    (&[(0, b"RIFF"), (8, b"WAVE")], "audio/wav"),
+    (&[(0, b"wOFF")], "font/woff"),
+    (&[(0, b"wOF2")], "font/woff2"),
+];
+
+/// (extension, mime type), extensions are compared case-insensitively.
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("html", "text/html; charset=utf-8"),
+    ("htm", "text/html; charset=utf-8"),
+    ("css", "text/css; charset=utf-8"),
+    ("js", "text/javascript; charset=utf-8"),
+    ("mjs", "text/javascript; charset=utf-8"),
+    ("json", "application/json"),
+    ("xml", "application/xml"),
+    ("svg", "image/svg+xml"),
+    ("txt", TEXT_PLAIN),
+    ("md", "text/markdown; charset=utf-8"),
+    ("csv", "text/csv; charset=utf-8"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("ico", "image/x-icon"),
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("tar", "application/x-tar"),
+    ("wasm", "application/wasm"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+];
+
+/// Return a best-effort mime type of a file given its first bytes `head`
+/// and its `path`.
+///
+/// Magic bytes are checked first, then the extension of `path`, and
+/// finally `head` is checked for whether it looks like utf-8 text.
+///
+/// An empty file without a known extension is treated as
+/// `application/octet-stream`.
+pub(super) fn sniff(head: &[u8], path: &Path) -> &'static str {
+    if let Some(mime) = sniff_magic(head) {
+        return mime;
+    }
+
+    if let Some(mime) = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(|ext| {
+            EXTENSIONS
+                .iter()
+                .find(|(known, _)| known.eq_ignore_ascii_case(ext))
+        })
+        .map(|(_, mime)| *mime)
+    {
+        return mime;
+    }
+
+    if !head.is_empty() && looks_like_text(head) {
+        TEXT_PLAIN
+    } else {
+        OCTET_STREAM
+    }
+}
+
+fn sniff_magic(head: &[u8]) -> Option<&'static str> {
+    let found = MAGIC.iter().find(|(magics, _)| {
+        magics.iter().all(|(offset, magic)| {
+            head.get(*offset..)
+                .map_or(false, |head| head.starts_with(magic))
+        })
+    });
+    if let Some((_, mime)) = found {
+        return Some(mime);
+    }
+
+    let trimmed = head
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .map_or(&[][..], |start| &head[start..]);
+
+    let starts_with_ignore_case = |prefix: &[u8]| {
+        trimmed
+            .get(..prefix.len())
+            .map_or(false, |s| s.eq_ignore_ascii_case(prefix))
+    };
+
+    if starts_with_ignore_case(b"<!doctype html") || starts_with_ignore_case(b"<html") {
+        Some("text/html; charset=utf-8")
+    } else if starts_with_ignore_case(b"<?xml") {
+        Some("application/xml")
+    } else {
+        None
+    }
+}
+
+fn looks_like_text(head: &[u8]) -> bool {
+    let is_binary = |b: &u8| matches!(b, 0x00..=0x08 | 0x0b | 0x0e..=0x1a | 0x1c..=0x1f);
+
+    if head.iter().any(is_binary) {
+        return false;
+    }
+
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        // `head` might end in the middle of a multi-byte character.
+        Err(err) => err.error_len().is_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_magic() {
+        assert_eq!(
+            sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR", Path::new("a.txt")),
+            "image/png"
+        );
+        assert_eq!(sniff(b"RIFF\0\0\0\0WEBPVP8 ", Path::new("a")), "image/webp");
+        assert_eq!(sniff(b"%PDF-1.7\n", Path::new("a")), "application/pdf");
+        assert_eq!(
+            sniff(b"\n  <!DOCTYPE html><html>", Path::new("a")),
+            "text/html; charset=utf-8"
+        );
+    }
+
+    #[test]
+    fn test_sniff_extension() {
+        assert_eq!(
+            sniff(b"body { color: red; }", Path::new("a/style.CSS")),
+            "text/css; charset=utf-8"
+        );
+        assert_eq!(sniff(b"{}", Path::new("a.json")), "application/json");
+    }
+
+    #[test]
+    fn test_sniff_fallback() {
+        assert_eq!(sniff(b"hello, world!\n", Path::new("a")), TEXT_PLAIN);
+        // Truncated in the middle of a multi-byte character.
+        assert_eq!(sniff(&"你好".as_bytes()[..4], Path::new("a")), TEXT_PLAIN);
+        assert_eq!(
+            sniff(b"\x7fELF\x02\x01\x01\0", Path::new("a")),
+            OCTET_STREAM
+        );
+        assert_eq!(sniff(b"", Path::new("a")), OCTET_STREAM);
+        assert_eq!(sniff(b"", Path::new("a.txt")), TEXT_PLAIN);
+    }
+
+    #[test]
+    fn test_sniff_no_false_positive() {
+        // Text that happens to start with short magic bytes.
+        assert_eq!(sniff(b"BMW cars\n", Path::new("a")), TEXT_PLAIN);
+        assert_eq!(sniff(b"ID3 tags\n", Path::new("a")), TEXT_PLAIN);
+        assert_eq!(sniff(b"BZh, hello\n", Path::new("a")), TEXT_PLAIN);
+        // WEBP and WAVE require the RIFF header.
+        assert_eq!(sniff(b"12345678WEBP\n", Path::new("a")), TEXT_PLAIN);
+        assert_eq!(sniff(b"12345678WAVE\n", Path::new("a")), TEXT_PLAIN);
+        // ISO base media files are told apart by their brand.
+        assert_eq!(
+            sniff(b"\0\0\0\x1cftypheic\0\0\0\0", Path::new("a")),
+            "image/heic"
+        );
+        assert_eq!(
+            sniff(b"\0\0\0\x1cftypavif\0\0\0\0", Path::new("a")),
+            "image/avif"
+        );
+        assert_eq!(
+            sniff(b"\0\0\0\x1cftypisom\0\0\0\0", Path::new("a")),
+            "video/mp4"
+        );
+        assert_eq!(
+            sniff(b"BM\x36\0\0\0\0\0\0\0\x36\0\0\0\x28\0\0\0", Path::new("a")),
+            "image/bmp"
+        );
+    }
+}
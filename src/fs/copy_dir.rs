@@ -0,0 +1,355 @@
+use super::{walk_error, Fs, SymlinkPolicy, Walker};
+
+use crate::{
+    metadata::{MetaData, MetaDataBuilder},
+    Error,
+};
+
+use std::{collections::HashMap, io, path::Path};
+
+/// Mapping of source uid/gid to destination uid/gid, used when preserving
+/// ownership between hosts whose numeric ids differ.
+///
+/// Ids can also be mapped by the name of the source user or group, which
+/// is looked up via [`Fs::users_groups_by_id`] and takes effect only for
+/// ids that are not mapped by number.
+///
+/// Ids that are not in the map are kept as-is.
+#[derive(Debug, Clone, Default)]
+pub struct IdMap {
+    uids: HashMap<u32, u32>,
+    gids: HashMap<u32, u32>,
+    users: HashMap<String, u32>,
+    groups: HashMap<String, u32>,
+}
+
+impl IdMap {
+    /// Create an empty [`IdMap`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map source uid `src` to destination uid `dst`.
+    pub fn map_uid(&mut self, src: u32, dst: u32) -> &mut Self {
+        self.uids.insert(src, dst);
+        self
+    }
+
+    /// Map source gid `src` to destination gid `dst`.
+    pub fn map_gid(&mut self, src: u32, dst: u32) -> &mut Self {
+        self.gids.insert(src, dst);
+        self
+    }
+
+    /// Map source user named `src` to destination uid `dst`.
+    ///
+    /// Requires extension `users-groups-by-id`, see
+    /// [`Fs::users_groups_by_id`].
+    pub fn map_user(&mut self, src: impl Into<String>, dst: u32) -> &mut Self {
+        self.users.insert(src.into(), dst);
+        self
+    }
+
+    /// Map source group named `src` to destination gid `dst`.
+    ///
+    /// Requires extension `users-groups-by-id`, see
+    /// [`Fs::users_groups_by_id`].
+    pub fn map_group(&mut self, src: impl Into<String>, dst: u32) -> &mut Self {
+        self.groups.insert(src.into(), dst);
+        self
+    }
+
+    /// Return destination uid of source uid `uid`, ignoring names.
+    pub fn uid(&self, uid: u32) -> u32 {
+        self.uids.get(&uid).copied().unwrap_or(uid)
+    }
+
+    /// Return destination gid of source gid `gid`, ignoring names.
+    pub fn gid(&self, gid: u32) -> u32 {
+        self.gids.get(&gid).copied().unwrap_or(gid)
+    }
+}
+
+/// Options for [`Fs::copy_dir`].
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CopyDirOptions<'a> {
+    preserve_permissions: bool,
+    preserve_times: bool,
+    preserve_ownership: bool,
+    id_map: Option<&'a IdMap>,
+    symlinks: SymlinkPolicy,
+}
+
+impl<'a> CopyDirOptions<'a> {
+    /// Create a new [`CopyDirOptions`].
+    pub const fn new() -> Self {
+        Self {
+            preserve_permissions: false,
+            preserve_times: false,
+            preserve_ownership: false,
+            id_map: None,
+            symlinks: SymlinkPolicy::Recreate,
+        }
+    }
+
+    /// Copy permissions of files and directories, default is `false`.
+    #[must_use]
+    pub const fn preserve_permissions(mut self, preserve_permissions: bool) -> Self {
+        self.preserve_permissions = preserve_permissions;
+        self
+    }
+
+    /// Copy access and modification times of files and directories,
+    /// default is `false`.
+    #[must_use]
+    pub const fn preserve_times(mut self, preserve_times: bool) -> Self {
+        self.preserve_times = preserve_times;
+        self
+    }
+
+    /// Copy owner and group of files and directories, default is `false`.
+    ///
+    /// Changing ownership usually requires the server to run as root.
+    #[must_use]
+    pub const fn preserve_ownership(mut self, preserve_ownership: bool) -> Self {
+        self.preserve_ownership = preserve_ownership;
+        self
+    }
+
+    /// Map uid and gid of the source through `id_map` when
+    /// [preserving ownership](CopyDirOptions::preserve_ownership).
+    ///
+    /// If `id_map` maps any names, then ids not mapped by number are
+    /// looked up via [`Fs::users_groups_by_id`], failing the copy if the
+    /// server does not support it.
+    #[must_use]
+    pub const fn id_map(mut self, id_map: &'a IdMap) -> Self {
+        self.id_map = Some(id_map);
+        self
+    }
+
+    /// How symlinks are handled, default is [`SymlinkPolicy::Recreate`].
+    #[must_use]
+    pub const fn symlinks(mut self, symlinks: SymlinkPolicy) -> Self {
+        self.symlinks = symlinks;
+        self
+    }
+
+    /// Return metadata of `src` to be set on its copy, if any.
+    ///
+    /// `id` is the destination uid and gid of `src` resolved by
+    /// [`IdResolver`].
+    fn preserved(&self, src: &MetaData, id: Option<(u32, u32)>) -> Option<MetaData> {
+        let mut builder = MetaDataBuilder::new();
+        let mut preserved = false;
+
+        if self.preserve_permissions {
+            if let Some(perm) = src.permissions() {
+                builder.permissions(perm);
+                preserved = true;
+            }
+        }
+
+        if self.preserve_times {
+            if let (Some(accessed), Some(modified)) = (src.accessed(), src.modified()) {
+                builder.time(accessed, modified);
+                preserved = true;
+            }
+        }
+
+        if let Some(id) = id {
+            builder.id(id);
+            preserved = true;
+        }
+
+        preserved.then(|| builder.create())
+    }
+}
+
+/// Resolves destination uid and gid of the source using
+/// [`CopyDirOptions::id_map`], caching the ids mapped by name so that
+/// each id is only looked up once.
+#[derive(Debug, Default)]
+struct IdResolver {
+    uids: HashMap<u32, u32>,
+    gids: HashMap<u32, u32>,
+}
+
+impl IdResolver {
+    /// Return destination uid and gid of `src` if ownership is preserved.
+    async fn resolve(
+        &mut self,
+        fs: &mut Fs,
+        options: &CopyDirOptions<'_>,
+        src: &MetaData,
+    ) -> Result<Option<(u32, u32)>, Error> {
+        let (uid, gid) = match (src.uid(), src.gid()) {
+            (Some(uid), Some(gid)) if options.preserve_ownership => (uid, gid),
+            _ => return Ok(None),
+        };
+        let id_map = match options.id_map {
+            Some(id_map) => id_map,
+            None => return Ok(Some((uid, gid))),
+        };
+
+        // Only look up names of ids that are neither mapped by number
+        // nor looked up before.
+        let uids: &[u32] = if id_map.users.is_empty()
+            || id_map.uids.contains_key(&uid)
+            || self.uids.contains_key(&uid)
+        {
+            &[]
+        } else {
+            &[uid]
+        };
+        let gids: &[u32] = if id_map.groups.is_empty()
+            || id_map.gids.contains_key(&gid)
+            || self.gids.contains_key(&gid)
+        {
+            &[]
+        } else {
+            &[gid]
+        };
+
+        if !uids.is_empty() || !gids.is_empty() {
+            let names = fs.users_groups_by_id(uids, gids).await?;
+
+            if let Some(name) = names.users.first() {
+                let dst = name.as_ref().and_then(|name| id_map.users.get(name));
+                self.uids.insert(uid, dst.copied().unwrap_or(uid));
+            }
+            if let Some(name) = names.groups.first() {
+                let dst = name.as_ref().and_then(|name| id_map.groups.get(name));
+                self.gids.insert(gid, dst.copied().unwrap_or(gid));
+            }
+        }
+
+        Ok(Some((
+            self.uids
+                .get(&uid)
+                .copied()
+                .unwrap_or_else(|| id_map.uid(uid)),
+            self.gids
+                .get(&gid)
+                .copied()
+                .unwrap_or_else(|| id_map.gid(gid)),
+        )))
+    }
+}
+
+async fn create_dir_if_missing(fs: &mut Fs, path: &Path) -> Result<(), Error> {
+    match fs.create_dir(path).await {
+        // Copying into an existing directory is fine.
+        Err(_) if fs.is_dir(path).await => Ok(()),
+        res => res,
+    }
+}
+
+impl Fs {
+    /// Recursively copy directory `src` to `dst` on the remote.
+    ///
+    /// Directories are created if missing, regular files are copied using
+    /// the `copy-data` extension if the server supports it and relayed
+    /// through the client otherwise, and symlinks are handled according
+    /// to [`CopyDirOptions::symlinks`].
+    ///
+    /// `dst` may be inside of `src`, in which case it is not copied into
+    /// itself, but it must not be `src` itself, including through a
+    /// symlink, otherwise [`io::ErrorKind::InvalidInput`] is returned.
+    ///
+    /// The copy stops at the first error, leaving whatever has already been
+    /// copied in place.
+    pub async fn copy_dir(
+        &mut self,
+        src: impl AsRef<Path>,
+        dst: impl AsRef<Path>,
+        options: CopyDirOptions<'_>,
+    ) -> Result<(), Error> {
+        async fn inner(
+            this: &mut Fs,
+            src: &Path,
+            dst: &Path,
+            options: CopyDirOptions<'_>,
+        ) -> Result<(), Error> {
+            let metadata = this.metadata(src).await?;
+            if !metadata.file_type().map_or(false, |t| t.is_dir()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "source of copy_dir is not a directory",
+                )
+                .into());
+            }
+
+            create_dir_if_missing(this, dst).await?;
+
+            let src_canonical = this.canonicalize(src).await?;
+            let dst_canonical = this.canonicalize(dst).await?;
+
+            // Copying every file onto itself would truncate it.
+            if src_canonical == dst_canonical {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "source and destination of copy_dir are the same directory",
+                )
+                .into());
+            }
+
+            // Path of `dst` within `src` if it is nested in it, which must
+            // not be copied, otherwise the copy would never end.
+            let nested = dst_canonical
+                .strip_prefix(&src_canonical)
+                .ok()
+                .map(|rel| src.join(rel));
+
+            let mut walker = Walker::new(this.clone(), src.to_path_buf())
+                .follow_root(true)
+                .symlinks(options.symlinks);
+            if let Some(nested) = nested {
+                walker = walker.filter(move |path, _metadata| path != nested);
+            }
+
+            // Metadata of copied directories, which is set after everything
+            // is copied since copying changes their modification time.
+            let mut dirs = Vec::new();
+            let mut id_resolver = IdResolver::default();
+
+            while let Some(res) = walker.next().await {
+                let entry = res.map_err(walk_error)?;
+                let dst = entry.dst_path(src, dst)?;
+
+                let file_type = entry.metadata.file_type();
+
+                if file_type.map_or(false, |t| t.is_dir()) {
+                    if entry.depth > 0 {
+                        create_dir_if_missing(this, &dst).await?;
+                    }
+
+                    let id = id_resolver.resolve(this, &options, &entry.metadata).await?;
+                    if let Some(metadata) = options.preserved(&entry.metadata, id) {
+                        dirs.push((dst, metadata));
+                    }
+                } else if file_type.map_or(false, |t| t.is_symlink()) {
+                    let target = this.read_link(&entry.path).await?;
+                    this.symlink(&target, &dst).await?;
+                } else {
+                    this.copy_file(&entry.path, &dst).await?;
+
+                    let id = id_resolver.resolve(this, &options, &entry.metadata).await?;
+                    if let Some(metadata) = options.preserved(&entry.metadata, id) {
+                        this.set_metadata(&dst, metadata).await?;
+                    }
+                }
+            }
+
+            // Directories are walked before their content, so the content
+            // is set before the directory containing it.
+            for (dst, metadata) in dirs.into_iter().rev() {
+                this.set_metadata(&dst, metadata).await?;
+            }
+
+            Ok(())
+        }
+
+        inner(self, src.as_ref(), dst.as_ref(), options).await
+    }
+}
@@ -1,8 +1,7 @@
 use crate::{
-    cancel_error,
     lowlevel::NameEntry,
     metadata::{FileType, MetaData},
-    Error,
+    Error, IoStats,
 };
 
 use super::Dir;
@@ -13,6 +12,7 @@ use std::{
     path::Path,
     pin::Pin,
     task::{ready, Context, Poll},
+    time::Instant,
     vec::IntoIter,
 };
 
@@ -63,6 +63,8 @@ pub struct ReadDir {
     //  - entries.is_none() => future.is_none()
     //  - If entries.is_some(), then future.is_none() ^ entries.unwrap().as_slice().is_empty()
     future: Option<ResponseFuture>,
+    /// When `future` is sent, used to record its latency.
+    sent_at: Instant,
     entries: Option<IntoIter<NameEntry>>,
 
     /// cancellation_fut is not only cancel-safe, but also can be polled after
@@ -79,6 +81,7 @@ impl ReadDir {
             cancellation_fut: dir.0.get_auxiliary().cancel_token.clone().cancelled_owned(),
             dir,
             future: None,
+            sent_at: Instant::now(),
             entries: Some(Vec::new().into_iter()),
         }
     }
@@ -100,6 +103,11 @@ impl ReadDir {
 
         Ok(future)
     }
+
+    /// Return the I/O statistics of the underlying directory handle.
+    pub fn stats(&self) -> IoStats {
+        self.dir.stats()
+    }
 }
 
 impl Stream for ReadDir {
@@ -122,16 +130,18 @@ impl Stream for ReadDir {
                 Some(future) => future,
                 None => {
                     *future = Some(Self::new_request(dir)?);
+                    *this.sent_at = Instant::now();
                     future.as_mut().unwrap()
                 }
             };
 
             let res = {
+                let auxiliary = this.dir.0.get_auxiliary();
                 let fut = async move {
                     tokio::select! {
                         biased;
 
-                        _ = cancellation_fut => Err(cancel_error()),
+                        _ = cancellation_fut => Err(auxiliary.cancel_error()),
                         res = fut => res,
                     }
                 };
@@ -141,6 +151,7 @@ impl Stream for ReadDir {
                 ready!(fut.poll(cx))
             };
             *future = None; // future is ready, reset it to None
+            this.dir.0.stats.record_request(*this.sent_at);
             let (id, ret) = res?;
 
             this.dir.0.cache_id_mut(id);
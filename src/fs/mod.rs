@@ -1,22 +1,47 @@
 use crate::{
-    file::OpenOptions,
+    file::{File, OpenOptions, TokioCompatFile},
     lowlevel::{self, Extensions},
     metadata::{MetaData, MetaDataBuilder, Permissions},
-    Auxiliary, Buffer, Error, Id, OwnedHandle, WriteEnd, WriteEndWithCachedId,
+    Auxiliary, Buffer, Error, Id, IoStats, OwnedHandle, WriteEnd, WriteEndWithCachedId,
 };
 
+pub use lowlevel::{CheckFile, SpaceAvailable, StatVfs, UsersGroups};
+
 use std::{
     borrow::Cow,
     cmp::min,
     convert::TryInto,
+    io,
     path::{Path, PathBuf},
 };
 
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 
 mod dir;
 pub use dir::{DirEntry, ReadDir};
 
+mod read_only;
+pub use read_only::ReadOnlyFs;
+
+mod content_type;
+
+mod rename;
+pub use rename::OverwritePolicy;
+
+mod recursive;
+pub use recursive::RecursiveReport;
+
+mod copy_dir;
+pub use copy_dir::{CopyDirOptions, IdMap};
+
+mod walk_dir;
+pub(crate) use walk_dir::{walk_error, BoxFuture, Children, Tree, TreeEntry, Walker};
+pub use walk_dir::{WalkDir, WalkDirEntry};
+
+mod symlink;
+pub(crate) use symlink::FollowedLinks;
+pub use symlink::SymlinkPolicy;
+
 type AwaitableStatus = lowlevel::AwaitableStatus<Buffer>;
 type AwaitableAttrs = lowlevel::AwaitableAttrs<Buffer>;
 type SendLinkingRequest =
@@ -82,6 +107,13 @@ impl Fs {
         inner(self, path.as_ref()).await
     }
 
+    /// Recursively walk the directory tree rooted at `path`.
+    ///
+    /// See [`WalkDir`] for details.
+    pub fn walk_dir(&self, path: impl AsRef<Path>) -> WalkDir {
+        WalkDir::new(self.clone(), path.as_ref().to_path_buf())
+    }
+
     /// Create a directory builder.
     pub fn dir_builder(&mut self) -> DirBuilder<'_> {
         DirBuilder {
@@ -99,12 +131,67 @@ impl Fs {
         inner(self, path.as_ref()).await
     }
 
+    /// Return `true` if `path` is a directory, following symlinks.
+    pub(crate) async fn is_dir(&mut self, path: &Path) -> bool {
+        matches!(
+            self.metadata(path).await.map(|metadata| metadata.file_type()),
+            Ok(Some(file_type)) if file_type.is_dir()
+        )
+    }
+
+    /// Recursively create a directory and all of its parent components
+    /// if they are missing.
+    ///
+    /// It is not an error if `path` already exists as a directory.
+    pub async fn create_dir_all(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        async fn inner(this: &mut Fs, path: &Path) -> Result<(), Error> {
+            // Walk up until a directory is created or found to exist,
+            // then create the missing ones from top to bottom.
+            let mut missing = Vec::new();
+
+            for dir in path.ancestors() {
+                if dir.as_os_str().is_empty() {
+                    break;
+                }
+
+                match this.create_dir(dir).await {
+                    Ok(()) => break,
+                    Err(err) => match this.metadata(dir).await {
+                        Ok(metadata) if metadata.file_type().map_or(false, |t| t.is_dir()) => break,
+                        Err(Error::SftpError(lowlevel::SftpErrorKind::NoSuchFile, _)) => {
+                            missing.push(dir)
+                        }
+                        _ => return Err(err),
+                    },
+                }
+            }
+
+            for dir in missing.into_iter().rev() {
+                if let Err(err) = this.create_dir(dir).await {
+                    // It might have been created concurrently.
+                    if !this.is_dir(dir).await {
+                        return Err(err);
+                    }
+                }
+            }
+
+            Ok(())
+        }
+
+        inner(self, path.as_ref()).await
+    }
+
     async fn remove_impl(&mut self, path: &Path, f: SendRmRequest) -> Result<(), Error> {
         let path = self.concat_path_if_needed(path);
 
-        self.write_end
-            .send_request(|write_end, id| Ok(f(write_end, id, path)?.wait()))
-            .await
+        let res = self
+            .write_end
+            .send_request(|write_end, id| Ok(f(write_end, id, Cow::Borrowed(&path))?.wait()))
+            .await;
+
+        self.get_auxiliary().invalidate_read_cache(&path);
+
+        res
     }
 
     /// Removes an existing, empty directory.
@@ -162,9 +249,18 @@ impl Fs {
         let src = self.concat_path_if_needed(src);
         let dst = self.concat_path_if_needed(dst);
 
-        self.write_end
-            .send_request(|write_end, id| Ok(f(write_end, id, src, dst)?.wait()))
-            .await
+        let res = self
+            .write_end
+            .send_request(|write_end, id| {
+                Ok(f(write_end, id, Cow::Borrowed(&src), Cow::Borrowed(&dst))?.wait())
+            })
+            .await;
+
+        let auxiliary = self.get_auxiliary();
+        auxiliary.invalidate_read_cache(&src);
+        auxiliary.invalidate_read_cache(&dst);
+
+        res
     }
 
     /// Creates a new hard link on the remote filesystem.
@@ -234,6 +330,135 @@ impl Fs {
         inner(self, from.as_ref(), to.as_ref()).await
     }
 
+    /// Moves `src` to `dst`, creating the missing parents of `dst`.
+    ///
+    /// It first tries [`Fs::rename`], which is atomic if the server supports
+    /// `posix-rename`.
+    ///
+    /// If that fails with [`lowlevel::SftpErrorKind::Failure`], which is
+    /// what openssh reports for moving across filesystems, `src` is a
+    /// regular file and `dst` does not exist, then `src` is copied to `dst`
+    /// and removed afterwards.
+    /// The copy is done on the server if it supports the `copy-data`
+    /// extension and the length of `dst` is verified before `src` is
+    /// removed. If the copy fails, `dst` is removed and `src` is kept.
+    ///
+    /// Without `posix-rename`, openssh also reports
+    /// [`lowlevel::SftpErrorKind::Failure`] when `dst` already exists, in
+    /// which case the error from [`Fs::rename`] is returned and `dst` is
+    /// left untouched.
+    ///
+    /// Directories are never copied, the error from [`Fs::rename`] is
+    /// returned for them instead.
+    pub async fn move_to(
+        &mut self,
+        src: impl AsRef<Path>,
+        dst: impl AsRef<Path>,
+    ) -> Result<(), Error> {
+        async fn inner(this: &mut Fs, src: &Path, dst: &Path) -> Result<(), Error> {
+            if let Some(parent) = dst.parent() {
+                if !parent.as_os_str().is_empty() {
+                    this.create_dir_all(parent).await?;
+                }
+            }
+
+            let err = match this.rename(src, dst).await {
+                Ok(()) => return Ok(()),
+                Err(err @ Error::SftpError(lowlevel::SftpErrorKind::Failure, _)) => err,
+                Err(err) => return Err(err),
+            };
+
+            match this.symlink_metadata(src).await?.file_type() {
+                Some(file_type) if file_type.is_file() => (),
+                _ => return Err(err),
+            }
+
+            match this.symlink_metadata(dst).await {
+                Err(Error::SftpError(lowlevel::SftpErrorKind::NoSuchFile, _)) => (),
+                Ok(_) => return Err(err),
+                Err(err) => return Err(err),
+            }
+
+            this.copy_file(src, dst).await?;
+            this.remove_file(src).await
+        }
+
+        inner(self, src.as_ref(), dst.as_ref()).await
+    }
+
+    /// Copy the content of regular file `src` to `dst` and verify its length,
+    /// `dst` is removed if the copy fails.
+    async fn copy_file(&mut self, src: &Path, dst: &Path) -> Result<(), Error> {
+        let src_path = self.concat_path_if_needed(src).into_owned();
+        let dst_path = self.concat_path_if_needed(dst).into_owned();
+
+        let mut src_file = OpenOptions::open_inner(
+            lowlevel::OpenOptions::new().read(true),
+            false,
+            false,
+            false,
+            &src_path,
+            self.write_end.clone(),
+        )
+        .await?;
+        let src_len = src_file.metadata().await?.len();
+
+        let mut dst_file = OpenOptions::open_inner(
+            lowlevel::OpenOptions::new().write(true),
+            true,
+            true,
+            false,
+            &dst_path,
+            self.write_end.clone(),
+        )
+        .await?;
+
+        let copy_data = self
+            .get_auxiliary()
+            .extensions()
+            .contains(Extensions::COPY_DATA);
+
+        let res = async move {
+            if copy_data {
+                src_file.copy_all_to(&mut dst_file).await?;
+            } else {
+                let src = TokioCompatFile::new(src_file.clone());
+                let dst = TokioCompatFile::new(dst_file.clone());
+                tokio::pin!(src, dst);
+
+                // `copy` flushes `dst` once EOF of `src` is reached, so all
+                // writes are acknowledged by the server after it returns.
+                tokio::io::copy(&mut src, &mut dst).await?;
+            }
+
+            let dst_len = dst_file.metadata().await?.len();
+
+            src_file.close().await?;
+            dst_file.close().await?;
+
+            if src_len.is_some() && src_len != dst_len {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "length of the copied file does not match the source",
+                )
+                .into());
+            }
+
+            Ok(())
+        }
+        .await;
+
+        if res.is_err() {
+            // Do not leave a partial copy behind, the error of the copy
+            // is more useful than the one of the removal.
+            //
+            // Pass `dst` since `remove_file` joins it with cwd by itself.
+            self.remove_file(dst).await.ok();
+        }
+
+        res
+    }
+
     /// Reads a symbolic link, returning the file that the link points to.
     pub async fn read_link(&mut self, path: impl AsRef<Path>) -> Result<PathBuf, Error> {
         async fn inner(this: &mut Fs, path: &Path) -> Result<PathBuf, Error> {
@@ -251,13 +476,18 @@ impl Fs {
     async fn set_metadata_impl(&mut self, path: &Path, metadata: MetaData) -> Result<(), Error> {
         let path = self.concat_path_if_needed(path);
 
-        self.write_end
+        let res = self
+            .write_end
             .send_request(|write_end, id| {
                 Ok(write_end
-                    .send_setstat_request(id, path, metadata.into_inner())?
+                    .send_setstat_request(id, Cow::Borrowed(&path), metadata.into_inner())?
                     .wait())
             })
-            .await
+            .await;
+
+        self.get_auxiliary().invalidate_read_cache(&path);
+
+        res
     }
 
     /// Change the metadata of a file or a directory.
@@ -269,6 +499,47 @@ impl Fs {
         self.set_metadata_impl(path.as_ref(), metadata).await
     }
 
+    /// Change the metadata of a file, a directory or a symlink, without
+    /// following symlink.
+    ///
+    /// # Precondition
+    ///
+    /// Require extension `lsetstat`
+    ///
+    /// You can check it with [`Sftp::support_lsetstat`](crate::sftp::Sftp::support_lsetstat).
+    pub async fn set_symlink_metadata(
+        &mut self,
+        path: impl AsRef<Path>,
+        metadata: MetaData,
+    ) -> Result<(), Error> {
+        async fn inner(this: &mut Fs, path: &Path, metadata: MetaData) -> Result<(), Error> {
+            if !this
+                .get_auxiliary()
+                .extensions()
+                .contains(Extensions::LSETSTAT)
+            {
+                return Err(Error::UnsupportedExtension(&"lsetstat"));
+            }
+
+            let path = this.concat_path_if_needed(path);
+
+            let res = this
+                .write_end
+                .send_request(|write_end, id| {
+                    Ok(write_end
+                        .send_lsetstat_request(id, Cow::Borrowed(&path), metadata.into_inner())?
+                        .wait())
+                })
+                .await;
+
+            this.get_auxiliary().invalidate_read_cache(&path);
+
+            res
+        }
+
+        inner(self, path.as_ref(), metadata).await
+    }
+
     /// Changes the permissions found on a file or a directory.
     pub async fn set_permissions(
         &mut self,
@@ -309,62 +580,367 @@ impl Fs {
             .await
     }
 
+    /// Returns statistics of the filesystem `path` resides on, e.g.
+    /// to check [`StatVfs::available_space`] before a large upload.
+    ///
+    /// # Precondition
+    ///
+    /// Require extension `statvfs`
+    ///
+    /// You can check it with [`Sftp::support_statvfs`](crate::sftp::Sftp::support_statvfs).
+    pub async fn statvfs(&mut self, path: impl AsRef<Path>) -> Result<StatVfs, Error> {
+        async fn inner(this: &mut Fs, path: &Path) -> Result<StatVfs, Error> {
+            if !this
+                .get_auxiliary()
+                .extensions()
+                .contains(Extensions::STATVFS)
+            {
+                return Err(Error::UnsupportedExtension(&"statvfs"));
+            }
+
+            let path = this.concat_path_if_needed(path);
+
+            this.write_end
+                .send_request(|write_end, id| Ok(write_end.send_statvfs_request(id, path)?.wait()))
+                .await
+        }
+
+        inner(self, path.as_ref()).await
+    }
+
+    /// Same as [`File::check_file`](crate::file::File::check_file), except
+    /// that the file is specified by `path`.
+    pub async fn check_file(
+        &mut self,
+        path: impl AsRef<Path>,
+        algorithms: &str,
+        offset: u64,
+        len: u64,
+        block_size: u32,
+    ) -> Result<CheckFile, Error> {
+        let path = self.concat_path_if_needed(path.as_ref());
+
+        self.write_end
+            .send_request(|write_end, id| {
+                Ok(write_end
+                    .send_check_file_name_request(id, path, algorithms, offset, len, block_size)?
+                    .wait())
+            })
+            .await
+    }
+
+    /// Returns the home directory of user `username`, or of the user
+    /// logged in if `username` is empty.
+    ///
+    /// # Precondition
+    ///
+    /// Require extension `home-directory`, which is not advertised by
+    /// the server. Servers that do not support it return
+    /// [`lowlevel::SftpErrorKind::OpUnsupported`].
+    pub async fn home_dir(&mut self, username: &str) -> Result<PathBuf, Error> {
+        self.write_end
+            .send_request(|write_end, id| {
+                Ok(write_end.send_home_directory_request(id, username)?.wait())
+            })
+            .await
+            .map(Into::into)
+    }
+
+    /// Returns names of the users with ids `uids` and the groups with ids
+    /// `gids`, e.g. to display the owner of [`MetaData::uid`] and
+    /// [`MetaData::gid`].
+    ///
+    /// # Precondition
+    ///
+    /// Require extension `users-groups-by-id`, which is not advertised by
+    /// the server. Servers that do not support it return
+    /// [`lowlevel::SftpErrorKind::OpUnsupported`].
+    pub async fn users_groups_by_id(
+        &mut self,
+        uids: &[u32],
+        gids: &[u32],
+    ) -> Result<UsersGroups, Error> {
+        let users_groups = self
+            .write_end
+            .send_request(|write_end, id| {
+                Ok(write_end
+                    .send_users_groups_by_id_request(id, uids, gids)?
+                    .wait())
+            })
+            .await?;
+
+        if users_groups.users.len() != uids.len() || users_groups.groups.len() != gids.len() {
+            return Err(Error::InvalidResponse(
+                &"Number of names in users-groups-by-id reply does not match the request",
+            ));
+        }
+
+        Ok(users_groups)
+    }
+
+    /// Returns space available on the device `path` resides on.
+    ///
+    /// # Precondition
+    ///
+    /// Require extension `space-available`, which is not supported by
+    /// openssh-portable. Servers that do not support it return
+    /// [`lowlevel::SftpErrorKind::OpUnsupported`], use [`Fs::statvfs`]
+    /// instead.
+    pub async fn space_available(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<SpaceAvailable, Error> {
+        let path = self.concat_path_if_needed(path.as_ref());
+
+        self.write_end
+            .send_request(|write_end, id| {
+                Ok(write_end.send_space_available_request(id, path)?.wait())
+            })
+            .await
+    }
+
+    /// Returns `Ok(true)` if `path` points at an existing entity.
+    ///
+    /// Unlike [`std::path::Path::try_exists`], symlinks are not followed,
+    /// so a broken symlink is considered to exist.
+    ///
+    /// `Ok(false)` is only returned if the server reports
+    /// [`lowlevel::SftpErrorKind::NoSuchFile`], any other error
+    /// (e.g. permission denied or connection failure) is returned as is.
+    pub async fn try_exists(&mut self, path: impl AsRef<Path>) -> Result<bool, Error> {
+        match self.symlink_metadata(path).await {
+            Ok(_) => Ok(true),
+            Err(Error::SftpError(lowlevel::SftpErrorKind::NoSuchFile, _)) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns `true` if `path` points at an existing entity.
+    ///
+    /// This function returns `false` on any error, use [`Fs::try_exists`]
+    /// to tell "not found" apart from other errors.
+    pub async fn exists(&mut self, path: impl AsRef<Path>) -> bool {
+        matches!(self.try_exists(path).await, Ok(true))
+    }
+
     /// Reads the entire contents of a file into a bytes.
+    ///
+    /// If [`SftpOptions::read_cache`](crate::SftpOptions::read_cache) is enabled,
+    /// then small files are served from the cache without any round trip.
     pub async fn read(&mut self, path: impl AsRef<Path>) -> Result<BytesMut, Error> {
         async fn inner(this: &mut Fs, path: &Path) -> Result<BytesMut, Error> {
             let path = this.concat_path_if_needed(path);
 
+            let generation = match this.get_auxiliary().read_cache() {
+                Some(read_cache) => match read_cache.get(&path) {
+                    Some(content) => return Ok(BytesMut::from(&*content)),
+                    None => Some(read_cache.generation()),
+                },
+                None => None,
+            };
+
+            let buffer = this.read_uncached(&path).await?;
+
+            if let (Some(read_cache), Some(generation)) =
+                (this.get_auxiliary().read_cache(), generation)
+            {
+                read_cache.insert(&path, Bytes::copy_from_slice(&buffer), generation);
+            }
+
+            Ok(buffer)
+        }
+
+        inner(self, path.as_ref()).await
+    }
+
+    /// Reads the entire contents of a file that is expected to be small,
+    /// returning [`Error::FileTooLarge`] if it is longer than `max_len`.
+    ///
+    /// Compared to [`Fs::read`], this function saves two round trips
+    /// by not querying the metadata of the file and by not waiting for
+    /// the handle to be closed, which is done in the background.
+    ///
+    /// Since the handle returned by the server is needed to read the file,
+    /// the open request cannot be pipelined with the reads.
+    ///
+    /// The read cache set by
+    /// [`SftpOptions::read_cache`](crate::SftpOptions::read_cache) is
+    /// not used.
+    pub async fn read_small(
+        &mut self,
+        path: impl AsRef<Path>,
+        max_len: usize,
+    ) -> Result<BytesMut, Error> {
+        async fn inner(this: &mut Fs, path: &Path, max_len: usize) -> Result<BytesMut, Error> {
+            let path = this.concat_path_if_needed(path);
+
             let mut file = OpenOptions::open_inner(
                 lowlevel::OpenOptions::new().read(true),
                 false,
                 false,
                 false,
-                path.as_ref(),
+                &path,
                 this.write_end.clone(),
             )
             .await?;
-            let max_read_len = file.max_read_len_impl();
 
-            let cap_to_reserve: usize = if let Some(len) = file.metadata().await?.len() {
-                // To detect EOF, we need to a little bit more then the length
-                // of the file.
-                len.saturating_add(300)
-                    .try_into()
-                    .unwrap_or(max_read_len as usize)
+            // Read one more byte to tell whether the file is too large.
+            let limit = max_len.saturating_add(1);
+            let mut buffer = BytesMut::with_capacity(min(limit, file.max_read_len_impl() as usize));
+
+            while buffer.len() < limit {
+                let cnt = buffer.len();
+                let n = (limit - cnt).try_into().unwrap_or(u32::MAX);
+
+                match file.read(n, buffer.split_off(cnt)).await? {
+                    Some(bytes) => buffer.unsplit(bytes),
+                    None => break,
+                }
+            }
+
+            // `file` is dropped here and closed in the background.
+
+            if buffer.len() > max_len {
+                Err(Error::FileTooLarge {
+                    max_len: max_len as u64,
+                })
             } else {
-                max_read_len as usize
-            };
+                Ok(buffer)
+            }
+        }
 
-            let mut buffer = BytesMut::with_capacity(cap_to_reserve);
+        inner(self, path.as_ref(), max_len).await
+    }
 
-            loop {
-                let cnt = buffer.len();
+    /// Read `len` bytes of the file at `path` starting at `offset`.
+    ///
+    /// See [`File::read_range`](crate::file::File::read_range) for how
+    /// ranges extending beyond EOF are handled.
+    pub async fn read_range(
+        &mut self,
+        path: impl AsRef<Path>,
+        offset: u64,
+        len: usize,
+    ) -> Result<BytesMut, Error> {
+        async fn inner(
+            this: &mut Fs,
+            path: &Path,
+            offset: u64,
+            len: usize,
+        ) -> Result<BytesMut, Error> {
+            let path = this.concat_path_if_needed(path);
+
+            let file = OpenOptions::open_inner(
+                lowlevel::OpenOptions::new().read(true),
+                false,
+                false,
+                false,
+                &path,
+                this.write_end.clone(),
+            )
+            .await?;
+
+            let buffer = file.read_range(offset, len, BytesMut::new()).await?;
+
+            file.close().await?;
+
+            Ok(buffer)
+        }
+
+        inner(self, path.as_ref(), offset, len).await
+    }
+
+    /// Returns a best-effort mime type of the file at `path`, e.g. for
+    /// setting the `Content-Type` header when serving it over http.
+    ///
+    /// Only the first 512 bytes of the file are read, and they are matched
+    /// against well-known magic bytes, falling back to the extension of
+    /// `path` and then to `text/plain; charset=utf-8` or
+    /// `application/octet-stream` depending on whether the content looks
+    /// like utf-8 text. Empty files are `application/octet-stream` unless
+    /// their extension is known.
+    pub async fn content_type(&mut self, path: impl AsRef<Path>) -> Result<&'static str, Error> {
+        async fn inner(this: &mut Fs, path: &Path) -> Result<&'static str, Error> {
+            let path = this.concat_path_if_needed(path);
 
-                let n: u32 = if cnt <= cap_to_reserve {
-                    // To detect EOF, we need to a little bit more then the
-                    // length of the file.
-                    (cap_to_reserve - cnt)
-                        .saturating_add(300)
-                        .try_into()
-                        .map(|n| min(n, max_read_len))
-                        .unwrap_or(max_read_len)
-                } else {
-                    max_read_len
-                };
-                buffer.reserve(n.try_into().unwrap_or(usize::MAX));
-
-                if let Some(bytes) = file.read(n, buffer.split_off(cnt)).await? {
-                    buffer.unsplit(bytes);
-                } else {
-                    // Eof
-                    break Ok(buffer);
+            let mut file = OpenOptions::open_inner(
+                lowlevel::OpenOptions::new().read(true),
+                false,
+                false,
+                false,
+                &path,
+                this.write_end.clone(),
+            )
+            .await?;
+
+            let mut head = BytesMut::with_capacity(content_type::SNIFF_LEN as usize);
+
+            while head.len() < content_type::SNIFF_LEN as usize {
+                let n = content_type::SNIFF_LEN - head.len() as u32;
+
+                match file.read(n, head.split_off(head.len())).await? {
+                    Some(buffer) => head.unsplit(buffer),
+                    None => break,
                 }
             }
+
+            file.close().await?;
+
+            Ok(content_type::sniff(&head, &path))
         }
 
         inner(self, path.as_ref()).await
     }
 
+    async fn read_uncached(&mut self, path: &Path) -> Result<BytesMut, Error> {
+        let mut file = OpenOptions::open_inner(
+            lowlevel::OpenOptions::new().read(true),
+            false,
+            false,
+            false,
+            path,
+            self.write_end.clone(),
+        )
+        .await?;
+        let max_read_len = file.max_read_len_impl();
+
+        let cap_to_reserve: usize = if let Some(len) = file.metadata().await?.len() {
+            // To detect EOF, we need to a little bit more then the length
+            // of the file.
+            len.saturating_add(300)
+                .try_into()
+                .unwrap_or(max_read_len as usize)
+        } else {
+            max_read_len as usize
+        };
+
+        let mut buffer = BytesMut::with_capacity(cap_to_reserve);
+
+        loop {
+            let cnt = buffer.len();
+
+            let n: u32 = if cnt <= cap_to_reserve {
+                // To detect EOF, we need to a little bit more then the
+                // length of the file.
+                (cap_to_reserve - cnt)
+                    .saturating_add(300)
+                    .try_into()
+                    .map(|n| min(n, max_read_len))
+                    .unwrap_or(max_read_len)
+            } else {
+                max_read_len
+            };
+            buffer.reserve(n.try_into().unwrap_or(usize::MAX));
+
+            if let Some(bytes) = file.read(n, buffer.split_off(cnt)).await? {
+                buffer.unsplit(bytes);
+            } else {
+                // Eof
+                break Ok(buffer);
+            }
+        }
+    }
+
     /// Open/Create a file for writing and write the entire `contents` into it.
     pub async fn write(
         &mut self,
@@ -389,6 +965,23 @@ impl Fs {
 
         inner(self, path.as_ref(), content.as_ref()).await
     }
+
+    /// Open `path` for reading, or for writing if `write` is `true`,
+    /// in which case it is created if it does not exist and truncated
+    /// if it does.
+    pub(crate) async fn open_file(&mut self, path: &Path, write: bool) -> Result<File, Error> {
+        let path = self.concat_path_if_needed(path);
+
+        OpenOptions::open_inner(
+            lowlevel::OpenOptions::new().read(!write).write(write),
+            write,
+            write,
+            false,
+            &path,
+            self.write_end.clone(),
+        )
+        .await
+    }
 }
 
 /// Remote Directory
@@ -406,6 +999,12 @@ impl Dir {
     pub async fn close(self) -> Result<(), Error> {
         self.0.close().await
     }
+
+    /// Return the I/O statistics of the underlying directory handle,
+    /// which is shared with the clones of this [`Dir`].
+    pub fn stats(&self) -> IoStats {
+        self.0.stats.get()
+    }
 }
 
 /// Builder for new directory to create.
@@ -0,0 +1,115 @@
+use super::{Dir, Fs};
+
+use crate::{metadata::MetaData, Error};
+
+use std::path::{Path, PathBuf};
+
+use bytes::BytesMut;
+
+/// A read-only view of [`Fs`].
+///
+/// Unlike [`Fs`], it does not provide any method that modifies
+/// the remote filesystem, so it can be passed to components that
+/// should only be given read access.
+///
+/// It can be obtained from [`SftpReadOnly::fs`](crate::SftpReadOnly::fs)
+/// or converted from [`Fs`].
+#[derive(Debug, Clone)]
+pub struct ReadOnlyFs(Fs);
+
+impl From<Fs> for ReadOnlyFs {
+    fn from(fs: Fs) -> Self {
+        Self(fs)
+    }
+}
+
+impl ReadOnlyFs {
+    /// Return current working dir.
+    pub fn cwd(&self) -> &Path {
+        self.0.cwd()
+    }
+
+    /// Set current working dir.
+    ///
+    /// See [`Fs::set_cwd`].
+    pub fn set_cwd(&mut self, cwd: impl Into<PathBuf>) {
+        self.0.set_cwd(cwd)
+    }
+
+    /// Open a remote dir.
+    pub async fn open_dir(&mut self, path: impl AsRef<Path>) -> Result<Dir, Error> {
+        self.0.open_dir(path).await
+    }
+
+    /// See [`Fs::canonicalize`].
+    pub async fn canonicalize(&mut self, path: impl AsRef<Path>) -> Result<PathBuf, Error> {
+        self.0.canonicalize(path).await
+    }
+
+    /// Reads a symbolic link, returning the file that the link points to.
+    pub async fn read_link(&mut self, path: impl AsRef<Path>) -> Result<PathBuf, Error> {
+        self.0.read_link(path).await
+    }
+
+    /// Given a path, queries the file system to get information about a file,
+    /// directory, etc.
+    pub async fn metadata(&mut self, path: impl AsRef<Path>) -> Result<MetaData, Error> {
+        self.0.metadata(path).await
+    }
+
+    /// Queries the file system metadata for a path.
+    pub async fn symlink_metadata(&mut self, path: impl AsRef<Path>) -> Result<MetaData, Error> {
+        self.0.symlink_metadata(path).await
+    }
+
+    /// Returns `Ok(true)` if `path` points at an existing entity.
+    ///
+    /// See [`Fs::try_exists`].
+    pub async fn try_exists(&mut self, path: impl AsRef<Path>) -> Result<bool, Error> {
+        self.0.try_exists(path).await
+    }
+
+    /// Returns `true` if `path` points at an existing entity.
+    ///
+    /// See [`Fs::exists`].
+    pub async fn exists(&mut self, path: impl AsRef<Path>) -> bool {
+        self.0.exists(path).await
+    }
+
+    /// Reads the entire contents of a file that is expected to be small.
+    ///
+    /// See [`Fs::read_small`].
+    pub async fn read_small(
+        &mut self,
+        path: impl AsRef<Path>,
+        max_len: usize,
+    ) -> Result<BytesMut, Error> {
+        self.0.read_small(path, max_len).await
+    }
+
+    /// Read `len` bytes of the file at `path` starting at `offset`.
+    ///
+    /// See [`Fs::read_range`].
+    pub async fn read_range(
+        &mut self,
+        path: impl AsRef<Path>,
+        offset: u64,
+        len: usize,
+    ) -> Result<BytesMut, Error> {
+        self.0.read_range(path, offset, len).await
+    }
+
+    /// Returns a best-effort mime type of the file at `path`.
+    ///
+    /// See [`Fs::content_type`].
+    pub async fn content_type(&mut self, path: impl AsRef<Path>) -> Result<&'static str, Error> {
+        self.0.content_type(path).await
+    }
+
+    /// Reads the entire contents of a file into a bytes.
+    ///
+    /// See [`Fs::read`].
+    pub async fn read(&mut self, path: impl AsRef<Path>) -> Result<BytesMut, Error> {
+        self.0.read(path).await
+    }
+}
@@ -0,0 +1,299 @@
+use super::{DirEntry, Fs, Tree, Walker};
+
+use crate::{
+    lowlevel::{AwaitableStatusFuture, FileAttrs},
+    metadata::{MetaDataBuilder, Permissions},
+    Buffer, Error,
+};
+
+use std::{
+    collections::VecDeque,
+    future::poll_fn,
+    io,
+    num::NonZeroUsize,
+    path::{Component, Path, PathBuf},
+};
+
+use futures_core::Stream;
+
+/// Report of a recursive operation such as [`Fs::set_permissions_recursive`].
+#[derive(Debug, Default)]
+pub struct RecursiveReport {
+    succeeded: u64,
+    failures: Vec<(PathBuf, Error)>,
+}
+
+impl RecursiveReport {
+    /// Number of paths the operation succeeded on.
+    pub fn succeeded(&self) -> u64 {
+        self.succeeded
+    }
+
+    /// Paths the operation failed on, along with the error returned
+    /// by the server.
+    pub fn failures(&self) -> &[(PathBuf, Error)] {
+        &self.failures
+    }
+
+    /// Return failures of the operation.
+    pub fn into_failures(self) -> Vec<(PathBuf, Error)> {
+        self.failures
+    }
+
+    /// Return `true` if the operation succeeded on every path.
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    fn record(&mut self, path: PathBuf, res: Result<(), Error>) -> Result<(), Error> {
+        match res {
+            Ok(()) => self.succeeded += 1,
+            // Errors reported by the server only affect this path.
+            Err(err @ Error::SftpError(..)) => self.failures.push((path, err)),
+            Err(err) => return Err(err),
+        }
+        Ok(())
+    }
+}
+
+/// Request sent by [`PendingRequests::send`].
+#[derive(Debug, Copy, Clone)]
+enum Request {
+    Setstat(FileAttrs),
+    Remove,
+    Rmdir,
+}
+
+/// Requests that have been sent but not yet responded.
+struct PendingRequests {
+    futures: VecDeque<(PathBuf, AwaitableStatusFuture<Buffer>)>,
+    max_pending: usize,
+}
+
+impl PendingRequests {
+    fn new(max_pending: NonZeroUsize) -> Self {
+        Self {
+            futures: VecDeque::new(),
+            max_pending: max_pending.get(),
+        }
+    }
+
+    async fn wait_one(&mut self, fs: &mut Fs, report: &mut RecursiveReport) -> Result<(), Error> {
+        if let Some((path, future)) = self.futures.pop_front() {
+            let res = fs.write_end.cancel_if_task_failed(future).await.map(|_| ());
+            fs.get_auxiliary()
+                .invalidate_read_cache(&fs.concat_path_if_needed(&path));
+            report.record(path, res)?;
+        }
+        Ok(())
+    }
+
+    async fn wait_all(&mut self, fs: &mut Fs, report: &mut RecursiveReport) -> Result<(), Error> {
+        while !self.futures.is_empty() {
+            self.wait_one(fs, report).await?;
+        }
+        Ok(())
+    }
+
+    async fn send(
+        &mut self,
+        fs: &mut Fs,
+        report: &mut RecursiveReport,
+        path: PathBuf,
+        request: Request,
+    ) -> Result<(), Error> {
+        if self.futures.len() >= self.max_pending {
+            self.wait_one(fs, report).await?;
+        }
+
+        let full_path = fs.concat_path_if_needed(&path);
+
+        let id = fs.write_end.create_response_id();
+        let write_end = &mut fs.write_end;
+        let future = match request {
+            Request::Setstat(attrs) => write_end.send_setstat_request(id, full_path, attrs)?,
+            Request::Remove => write_end.send_remove_request(id, full_path)?,
+            Request::Rmdir => write_end.send_rmdir_request(id, full_path)?,
+        }
+        .wait();
+
+        // Requests is already added to write buffer, so wakeup
+        // the `flush_task` if necessary.
+        fs.get_auxiliary().wakeup_flush_task();
+
+        self.futures.push_back((path, future));
+
+        Ok(())
+    }
+}
+
+/// Return `true` if `name` is exactly one normal component, so that
+/// joining it to a directory cannot escape that directory.
+fn is_file_name(name: &Path) -> bool {
+    let mut components = name.components();
+
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(component)), None) => component == name.as_os_str(),
+        _ => false,
+    }
+}
+
+impl Fs {
+    /// Return entries of directory `path` except for `.` and `..`.
+    ///
+    /// Since the names are joined with `path` to walk the tree, any name
+    /// that is not a single path component, e.g. `../x` or `/x` sent by
+    /// a malicious server, is rejected with [`Error::InvalidResponse`].
+    pub(crate) async fn read_dir_entries(&mut self, path: &Path) -> Result<Vec<DirEntry>, Error> {
+        let read_dir = self.open_dir(path).await?.read_dir();
+        tokio::pin!(read_dir);
+
+        let mut entries = Vec::new();
+        while let Some(entry) = poll_fn(|cx| read_dir.as_mut().poll_next(cx)).await {
+            let entry = entry?;
+
+            let filename = entry.filename();
+            if filename == Path::new(".") || filename == Path::new("..") {
+                continue;
+            }
+            if !is_file_name(filename) {
+                return Err(Error::InvalidResponse(
+                    &"Directory entry name is not a single path component",
+                ));
+            }
+
+            entries.push(entry);
+        }
+
+        Ok(entries)
+    }
+
+    async fn set_attrs_recursive(
+        &mut self,
+        path: &Path,
+        attrs: FileAttrs,
+        max_pending: NonZeroUsize,
+    ) -> Result<RecursiveReport, Error> {
+        let mut report = RecursiveReport::default();
+        let mut pending = PendingRequests::new(max_pending);
+        let setstat = Request::Setstat(attrs);
+
+        // Directories are changed after their content, so that
+        // removing permissions of a directory does not prevent
+        // its content from being changed.
+        let mut walker = Walker::new(self.clone(), path.to_path_buf()).contents_first(true);
+
+        while let Some(res) = walker.next().await {
+            match res {
+                // Setstat would follow symlinks, so they are skipped.
+                Ok(entry) if <Fs as Tree>::is_symlink(&entry.metadata) => (),
+                Ok(entry) => pending.send(self, &mut report, entry.path, setstat).await?,
+                Err((path, err)) => report.record(path, Err(err))?,
+            }
+        }
+
+        pending.wait_all(self, &mut report).await?;
+
+        Ok(report)
+    }
+
+    /// Recursively change the permissions of `path` and everything in it.
+    ///
+    /// Up to `max_pending` setstat requests are sent without waiting for
+    /// their responses. Directories are changed after their content and
+    /// symlinks are skipped.
+    ///
+    /// Errors reported by the server for individual paths (e.g. permission
+    /// denied) do not stop the operation and are collected in the returned
+    /// [`RecursiveReport`], while other errors are returned immediately.
+    pub async fn set_permissions_recursive(
+        &mut self,
+        path: impl AsRef<Path>,
+        perm: Permissions,
+        max_pending: NonZeroUsize,
+    ) -> Result<RecursiveReport, Error> {
+        let attrs = MetaDataBuilder::new()
+            .permissions(perm)
+            .create()
+            .into_inner();
+
+        self.set_attrs_recursive(path.as_ref(), attrs, max_pending)
+            .await
+    }
+
+    /// Recursively change the owner and group of `path` and everything
+    /// in it.
+    ///
+    /// See [`Fs::set_permissions_recursive`] for how it is done.
+    pub async fn chown_recursive(
+        &mut self,
+        path: impl AsRef<Path>,
+        (uid, gid): (u32, u32),
+        max_pending: NonZeroUsize,
+    ) -> Result<RecursiveReport, Error> {
+        let attrs = MetaDataBuilder::new().id((uid, gid)).create().into_inner();
+
+        self.set_attrs_recursive(path.as_ref(), attrs, max_pending)
+            .await
+    }
+
+    /// Removes a directory and everything in it, like
+    /// [`std::fs::remove_dir_all`].
+    ///
+    /// Up to `max_pending` remove/rmdir requests are sent without waiting
+    /// for their responses, which relies on the server processing requests
+    /// in order (which openssh-portable does) to remove the content of a
+    /// directory before the directory itself. Symlinks are removed, not
+    /// followed.
+    ///
+    /// If `path` itself is a symlink, only the symlink is removed.
+    ///
+    /// Removal continues after errors reported by the server for individual
+    /// paths, the first of them is returned once it is done.
+    pub async fn remove_dir_all(
+        &mut self,
+        path: impl AsRef<Path>,
+        max_pending: NonZeroUsize,
+    ) -> Result<(), Error> {
+        let path = path.as_ref();
+
+        match self.symlink_metadata(path).await?.file_type() {
+            Some(file_type) if file_type.is_symlink() => return self.remove_file(path).await,
+            Some(file_type) if !file_type.is_dir() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "path of remove_dir_all is not a directory",
+                )
+                .into())
+            }
+            _ => (),
+        }
+
+        let mut report = RecursiveReport::default();
+        let mut pending = PendingRequests::new(max_pending);
+
+        // The content of directories is removed before themselves.
+        let mut walker = Walker::new(self.clone(), path.to_path_buf()).contents_first(true);
+
+        while let Some(res) = walker.next().await {
+            match res {
+                Ok(entry) => {
+                    let request = if <Fs as Tree>::is_dir(&entry.metadata) {
+                        Request::Rmdir
+                    } else {
+                        Request::Remove
+                    };
+                    pending.send(self, &mut report, entry.path, request).await?;
+                }
+                Err((path, err)) => report.record(path, Err(err))?,
+            }
+        }
+
+        pending.wait_all(self, &mut report).await?;
+
+        match report.into_failures().into_iter().next() {
+            Some((_path, err)) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use super::Fs;
+
+use crate::{
+    lowlevel::{self, Extensions},
+    Error, WriteEnd,
+};
+
+use std::{
+    ffi::OsString,
+    io,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// What [`Fs::rename_with`] does if the destination already exists.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OverwritePolicy<'a> {
+    /// Fail with [`io::ErrorKind::AlreadyExists`].
+    Fail,
+
+    /// Replace the destination.
+    Overwrite,
+
+    /// Rename the destination to its path with the suffix appended,
+    /// replacing any existing backup, then rename the source.
+    Backup(&'a str),
+}
+
+impl Fs {
+    async fn posix_rename_or_replace(&mut self, from: &Path, to: &Path) -> Result<(), Error> {
+        if self
+            .get_auxiliary()
+            .extensions()
+            .contains(Extensions::POSIX_RENAME)
+        {
+            return self
+                .linking_impl(from, to, WriteEnd::send_posix_rename_request)
+                .await;
+        }
+
+        // Standard rename fails if `to` already exists on openssh, so `to`
+        // is moved out of the way first and only removed once `from` is
+        // renamed, so that it can be restored if renaming `from` fails.
+        let is_dir = match self.symlink_metadata(to).await {
+            Ok(metadata) => metadata.file_type().map_or(false, |t| t.is_dir()),
+            Err(Error::SftpError(lowlevel::SftpErrorKind::NoSuchFile, _)) => {
+                return self
+                    .linking_impl(from, to, WriteEnd::send_rename_request)
+                    .await;
+            }
+            Err(err) => return Err(err),
+        };
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos());
+        let mut tmp = OsString::from(to);
+        tmp.push(format!(".{nanos:x}.tmp"));
+        let tmp = Path::new(&tmp);
+
+        self.linking_impl(to, tmp, WriteEnd::send_rename_request)
+            .await?;
+
+        if let Err(err) = self
+            .linking_impl(from, to, WriteEnd::send_rename_request)
+            .await
+        {
+            self.linking_impl(tmp, to, WriteEnd::send_rename_request)
+                .await?;
+            return Err(err);
+        }
+
+        if is_dir {
+            self.remove_dir(tmp).await
+        } else {
+            self.remove_file(tmp).await
+        }
+    }
+
+    /// Renames a file or directory to a new name, with an explicit
+    /// [`OverwritePolicy`] for when `to` already exists.
+    ///
+    /// Existence of `to` is checked by [`Fs::try_exists`] before renaming,
+    /// so another client could still create it in between:
+    ///
+    /// - With [`OverwritePolicy::Fail`], the standard sftp rename is used,
+    ///   which refuses to replace an existing file on openssh.
+    /// - With [`OverwritePolicy::Overwrite`], `posix-rename` is used if the
+    ///   server supports it, which atomically replaces `to`. Otherwise, `to`
+    ///   is first renamed to a temporary path next to it, which is removed
+    ///   once `from` is renamed or renamed back if that fails. Removing it
+    ///   only works for files and empty directories.
+    /// - With [`OverwritePolicy::Backup`], `to` is moved to the backup path
+    ///   as if by [`OverwritePolicy::Overwrite`], then `from` is renamed as
+    ///   if by [`OverwritePolicy::Fail`]. If that fails, the backup is
+    ///   renamed back to `to`, though the backup it replaced is lost.
+    ///   An empty suffix is rejected with [`io::ErrorKind::InvalidInput`].
+    pub async fn rename_with(
+        &mut self,
+        from: impl AsRef<Path>,
+        to: impl AsRef<Path>,
+        policy: OverwritePolicy<'_>,
+    ) -> Result<(), Error> {
+        async fn inner(
+            this: &mut Fs,
+            from: &Path,
+            to: &Path,
+            policy: OverwritePolicy<'_>,
+        ) -> Result<(), Error> {
+            match policy {
+                OverwritePolicy::Fail => {
+                    if this.try_exists(to).await? {
+                        return Err(io::Error::new(
+                            io::ErrorKind::AlreadyExists,
+                            "destination of rename already exists",
+                        )
+                        .into());
+                    }
+                }
+                OverwritePolicy::Overwrite => {
+                    return this.posix_rename_or_replace(from, to).await;
+                }
+                OverwritePolicy::Backup(suffix) => {
+                    // The backup would be `to` itself.
+                    if suffix.is_empty() {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "suffix of OverwritePolicy::Backup is empty",
+                        )
+                        .into());
+                    }
+
+                    if this.try_exists(to).await? {
+                        let mut backup = OsString::from(to);
+                        backup.push(suffix);
+                        let backup = Path::new(&backup);
+
+                        this.posix_rename_or_replace(to, backup).await?;
+
+                        if let Err(err) = this
+                            .linking_impl(from, to, WriteEnd::send_rename_request)
+                            .await
+                        {
+                            this.linking_impl(backup, to, WriteEnd::send_rename_request)
+                                .await?;
+                            return Err(err);
+                        }
+
+                        return Ok(());
+                    }
+                }
+            }
+
+            this.linking_impl(from, to, WriteEnd::send_rename_request)
+                .await
+        }
+
+        inner(self, from.as_ref(), to.as_ref(), policy).await
+    }
+}
@@ -0,0 +1,63 @@
+use std::path::{Path, PathBuf};
+
+/// How symlinks are handled when copying or transferring a directory tree,
+/// e.g. by [`Fs::copy_dir`](super::Fs::copy_dir) and
+/// [`Sftp::upload_dir`](crate::Sftp::upload_dir).
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Create a symlink pointing at the same target on the destination.
+    #[default]
+    Recreate,
+
+    /// Copy whatever the symlink points at as if it was not a symlink.
+    ///
+    /// Symlinks to directories that would lead back to a directory being
+    /// copied are skipped, so following them does not loop forever.
+    Follow,
+
+    /// Ignore symlinks.
+    Skip,
+}
+
+/// Canonical paths of directories where a symlink to a directory was
+/// followed, used to detect cycles for [`SymlinkPolicy::Follow`].
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FollowedLinks(Vec<PathBuf>);
+
+impl FollowedLinks {
+    /// Follow symlink in directory `dir` pointing at directory `target`,
+    /// both of which must be canonical.
+    ///
+    /// Return `None` if `target` contains a directory on the current path,
+    /// in which case following it would never end.
+    pub(crate) fn follow(&self, dir: PathBuf, target: &Path) -> Option<Self> {
+        if dir.starts_with(target) || self.0.iter().any(|p| p.starts_with(target)) {
+            return None;
+        }
+
+        let mut links = self.clone();
+        links.0.push(dir);
+        Some(links)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_followed_links_cycle() {
+        let links = FollowedLinks::default();
+
+        // /r/a/up -> /r
+        assert!(links.follow("/r/a".into(), Path::new("/r")).is_none());
+        // /r/a/self -> /r/a
+        assert!(links.follow("/r/a".into(), Path::new("/r/a")).is_none());
+
+        // /r/a/x -> /r/b, then /r/b/y -> /r/a
+        let links = links.follow("/r/a".into(), Path::new("/r/b")).unwrap();
+        assert!(links.follow("/r/b".into(), Path::new("/r/a")).is_none());
+        // /r/b/z -> /r/c
+        assert!(links.follow("/r/b".into(), Path::new("/r/c")).is_some());
+    }
+}
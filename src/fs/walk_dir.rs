@@ -0,0 +1,511 @@
+use super::{FollowedLinks, Fs, SymlinkPolicy};
+
+use crate::{metadata::MetaData, Error};
+
+use std::{
+    ffi::OsString,
+    fmt,
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use futures_core::Stream;
+
+pub(crate) type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Name and metadata of the entries of a directory.
+pub(crate) type Children<M> = Vec<(OsString, M)>;
+
+type WalkFilter<M> = Box<dyn FnMut(&Path, &M) -> bool + Send + Sync>;
+
+/// Directory tree that can be walked by [`Walker`], implemented for the
+/// remote [`Fs`] and for the local filesystem in [`crate::transfer`].
+pub(crate) trait Tree: Send {
+    type Metadata: Send + Sync;
+
+    fn symlink_metadata<'a>(
+        &'a mut self,
+        path: &'a Path,
+    ) -> BoxFuture<'a, Result<Self::Metadata, Error>>;
+
+    fn metadata<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<Self::Metadata, Error>>;
+
+    fn canonicalize<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<PathBuf, Error>>;
+
+    /// Return the name and metadata of every entry in directory `path`,
+    /// except for `.` and `..`, symlinks are not followed.
+    fn read_dir<'a>(
+        &'a mut self,
+        path: &'a Path,
+    ) -> BoxFuture<'a, Result<Children<Self::Metadata>, Error>>;
+
+    fn is_dir(metadata: &Self::Metadata) -> bool;
+
+    fn is_symlink(metadata: &Self::Metadata) -> bool;
+}
+
+impl Tree for Fs {
+    type Metadata = MetaData;
+
+    fn symlink_metadata<'a>(
+        &'a mut self,
+        path: &'a Path,
+    ) -> BoxFuture<'a, Result<MetaData, Error>> {
+        Box::pin(Fs::symlink_metadata(self, path))
+    }
+
+    fn metadata<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<MetaData, Error>> {
+        Box::pin(Fs::metadata(self, path))
+    }
+
+    fn canonicalize<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<PathBuf, Error>> {
+        Box::pin(Fs::canonicalize(self, path))
+    }
+
+    fn read_dir<'a>(
+        &'a mut self,
+        path: &'a Path,
+    ) -> BoxFuture<'a, Result<Children<MetaData>, Error>> {
+        Box::pin(async move {
+            Ok(self
+                .read_dir_entries(path)
+                .await?
+                .into_iter()
+                .map(|entry| (entry.filename().as_os_str().to_owned(), entry.metadata()))
+                .collect())
+        })
+    }
+
+    fn is_dir(metadata: &MetaData) -> bool {
+        metadata.file_type().map_or(false, |t| t.is_dir())
+    }
+
+    fn is_symlink(metadata: &MetaData) -> bool {
+        metadata.file_type().map_or(false, |t| t.is_symlink())
+    }
+}
+
+/// Entry yielded by [`Walker`].
+#[derive(Debug)]
+pub(crate) struct TreeEntry<M> {
+    pub(crate) path: PathBuf,
+    pub(crate) depth: usize,
+    pub(crate) metadata: M,
+}
+
+impl<M> TreeEntry<M> {
+    /// Return the path of the entry with `root`, the root of the walk,
+    /// replaced by `dst`.
+    ///
+    /// Fails if the entry is not inside of `root`.
+    pub(crate) fn dst_path(&self, root: &Path, dst: &Path) -> Result<PathBuf, Error> {
+        let path = self.path.strip_prefix(root).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "entry of the walk is not inside of its root",
+            )
+        })?;
+
+        Ok(if path.as_os_str().is_empty() {
+            dst.to_path_buf()
+        } else {
+            dst.join(path)
+        })
+    }
+}
+
+/// Convert an error returned by [`Walker::next`] into
+/// [`Error::WalkDirError`].
+pub(crate) fn walk_error((path, error): (PathBuf, Error)) -> Error {
+    Error::WalkDirError {
+        path,
+        error: Box::new(error),
+    }
+}
+
+#[derive(Debug)]
+enum Pending<M> {
+    /// Yield the entry and then visit its content if it is a directory.
+    Visit(TreeEntry<M>, FollowedLinks),
+    /// Content of the directory is already visited, just yield it.
+    Yield(TreeEntry<M>),
+}
+
+/// Depth-first walker of a [`Tree`], shared by [`WalkDir`] and the
+/// recursive operations of [`Fs`] and [`crate::transfer`].
+pub(crate) struct Walker<T: Tree> {
+    tree: T,
+    root: Option<PathBuf>,
+    stack: Vec<Pending<T::Metadata>>,
+    max_depth: usize,
+    contents_first: bool,
+    follow_root: bool,
+    symlinks: SymlinkPolicy,
+    filter: Option<WalkFilter<T::Metadata>>,
+}
+
+impl<T: Tree> fmt::Debug for Walker<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Walker")
+            .field("root", &self.root)
+            .field("max_depth", &self.max_depth)
+            .field("contents_first", &self.contents_first)
+            .field("follow_root", &self.follow_root)
+            .field("symlinks", &self.symlinks)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Tree> Walker<T> {
+    pub(crate) fn new(tree: T, root: PathBuf) -> Self {
+        Self {
+            tree,
+            root: Some(root),
+            stack: Vec::new(),
+            max_depth: usize::MAX,
+            contents_first: false,
+            follow_root: false,
+            symlinks: SymlinkPolicy::Recreate,
+            filter: None,
+        }
+    }
+
+    /// See [`WalkDir::max_depth`].
+    #[must_use]
+    pub(crate) fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// See [`WalkDir::contents_first`].
+    #[must_use]
+    pub(crate) fn contents_first(mut self, contents_first: bool) -> Self {
+        self.contents_first = contents_first;
+        self
+    }
+
+    /// Follow the root if it is a symlink, regardless of
+    /// [`Walker::symlinks`], default is `false`.
+    #[must_use]
+    pub(crate) fn follow_root(mut self, follow_root: bool) -> Self {
+        self.follow_root = follow_root;
+        self
+    }
+
+    /// See [`WalkDir::symlinks`].
+    #[must_use]
+    pub(crate) fn symlinks(mut self, symlinks: SymlinkPolicy) -> Self {
+        self.symlinks = symlinks;
+        self
+    }
+
+    /// See [`WalkDir::filter`].
+    #[must_use]
+    pub(crate) fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: FnMut(&Path, &T::Metadata) -> bool + Send + Sync + 'static,
+    {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Return metadata of the target of symlink `path` and the links
+    /// followed to reach it, or `None` if following it would loop.
+    async fn follow(
+        &mut self,
+        path: &Path,
+        links: FollowedLinks,
+    ) -> Result<Option<(T::Metadata, FollowedLinks)>, Error> {
+        let metadata = self.tree.metadata(path).await?;
+        if !T::is_dir(&metadata) {
+            return Ok(Some((metadata, links)));
+        }
+
+        let dir = self
+            .tree
+            .canonicalize(path.parent().unwrap_or(path))
+            .await?;
+        let target = self.tree.canonicalize(path).await?;
+
+        Ok(links
+            .follow(dir, &target)
+            .map(|followed| (metadata, followed)))
+    }
+
+    /// Return the next entry, or `None` if the whole tree is visited.
+    ///
+    /// Errors are returned along with the path they occurred on.
+    pub(crate) async fn next(
+        &mut self,
+    ) -> Option<Result<TreeEntry<T::Metadata>, (PathBuf, Error)>> {
+        if let Some(root) = self.root.take() {
+            let res = if self.follow_root {
+                self.tree.metadata(&root).await
+            } else {
+                self.tree.symlink_metadata(&root).await
+            };
+
+            match res {
+                Ok(metadata) => self.stack.push(Pending::Visit(
+                    TreeEntry {
+                        path: root,
+                        depth: 0,
+                        metadata,
+                    },
+                    FollowedLinks::default(),
+                )),
+                Err(err) => return Some(Err((root, err))),
+            }
+        }
+
+        loop {
+            let (mut entry, mut links) = match self.stack.pop()? {
+                Pending::Yield(entry) => return Some(Ok(entry)),
+                Pending::Visit(entry, links) => (entry, links),
+            };
+
+            // The root is always yielded.
+            if entry.depth > 0 {
+                if T::is_symlink(&entry.metadata) {
+                    match self.symlinks {
+                        SymlinkPolicy::Recreate => (),
+                        SymlinkPolicy::Follow => match self.follow(&entry.path, links).await {
+                            Ok(Some((metadata, followed))) => {
+                                entry.metadata = metadata;
+                                links = followed;
+                            }
+                            Ok(None) => continue,
+                            Err(err) => return Some(Err((entry.path, err))),
+                        },
+                        SymlinkPolicy::Skip => continue,
+                    }
+                }
+
+                if let Some(filter) = &mut self.filter {
+                    if !filter(&entry.path, &entry.metadata) {
+                        continue;
+                    }
+                }
+            }
+
+            if !T::is_dir(&entry.metadata) || entry.depth >= self.max_depth {
+                return Some(Ok(entry));
+            }
+
+            let children = match self.tree.read_dir(&entry.path).await {
+                Ok(children) => children,
+                Err(err) => {
+                    let path = entry.path.clone();
+                    // Still yield the directory itself after the error.
+                    self.stack.push(Pending::Yield(entry));
+                    return Some(Err((path, err)));
+                }
+            };
+
+            let depth = entry.depth + 1;
+            let dir = entry.path.clone();
+
+            let entry = if self.contents_first {
+                self.stack.push(Pending::Yield(entry));
+                None
+            } else {
+                Some(entry)
+            };
+
+            // Push in reverse so that entries are visited in the
+            // order they are listed.
+            self.stack
+                .extend(children.into_iter().rev().map(|(name, metadata)| {
+                    Pending::Visit(
+                        TreeEntry {
+                            path: dir.join(name),
+                            depth,
+                            metadata,
+                        },
+                        links.clone(),
+                    )
+                }));
+
+            if let Some(entry) = entry {
+                return Some(Ok(entry));
+            }
+        }
+    }
+}
+
+/// Entry yielded by [`WalkDir`].
+#[derive(Debug, Clone)]
+pub struct WalkDirEntry {
+    path: PathBuf,
+    depth: usize,
+    metadata: MetaData,
+}
+
+impl WalkDirEntry {
+    /// Path of the entry, which is the root passed to [`Fs::walk_dir`]
+    /// joined with the names of the directories leading to the entry.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Return the path of the entry.
+    pub fn into_path(self) -> PathBuf {
+        self.path
+    }
+
+    /// Depth of the entry, the root is at depth 0 and its
+    /// direct children are at depth 1.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Metadata of the entry returned by the server, symlinks are
+    /// not followed unless [`SymlinkPolicy::Follow`] is used.
+    pub fn metadata(&self) -> MetaData {
+        self.metadata
+    }
+}
+
+impl From<TreeEntry<MetaData>> for WalkDirEntry {
+    fn from(entry: TreeEntry<MetaData>) -> Self {
+        Self {
+            path: entry.path,
+            depth: entry.depth,
+            metadata: entry.metadata,
+        }
+    }
+}
+
+type NextFuture = BoxFuture<
+    'static,
+    (
+        Box<Walker<Fs>>,
+        Option<Result<TreeEntry<MetaData>, (PathBuf, Error)>>,
+    ),
+>;
+
+/// Recursive [`Stream`] of a remote directory tree, returned by
+/// [`Fs::walk_dir`].
+///
+/// The tree is traversed depth-first, directories are yielded before
+/// their content unless [`WalkDir::contents_first`] is set. Symlinks are
+/// handled according to [`WalkDir::symlinks`] and symlinks that would
+/// lead back to a directory being walked are never followed.
+///
+/// Errors are yielded as [`Error::WalkDirError`] holding the path they
+/// occurred on. Errors when reading a directory do not stop the
+/// traversal, so that the rest of the tree is still visited.
+///
+/// It must be configured before it is polled.
+pub struct WalkDir {
+    walker: Option<Box<Walker<Fs>>>,
+    future: Option<NextFuture>,
+}
+
+impl fmt::Debug for WalkDir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WalkDir")
+            .field("walker", &self.walker)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WalkDir {
+    pub(super) fn new(fs: Fs, root: PathBuf) -> Self {
+        Self {
+            walker: Some(Box::new(Walker::new(fs, root))),
+            future: None,
+        }
+    }
+
+    fn map_walker(mut self, f: impl FnOnce(Walker<Fs>) -> Walker<Fs>) -> Self {
+        let walker = self
+            .walker
+            .take()
+            .expect("WalkDir shall be configured before it is polled");
+        self.walker = Some(Box::new(f(*walker)));
+        self
+    }
+
+    /// Do not descend into directories deeper than `max_depth`, e.g.
+    /// `0` only yields the root and `1` yields the root and its direct
+    /// children.
+    ///
+    /// There is no limit by default.
+    #[must_use]
+    pub fn max_depth(self, max_depth: usize) -> Self {
+        self.map_walker(|walker| walker.max_depth(max_depth))
+    }
+
+    /// Yield directories after their content instead of before.
+    ///
+    /// It is `false` by default.
+    #[must_use]
+    pub fn contents_first(self, contents_first: bool) -> Self {
+        self.map_walker(|walker| walker.contents_first(contents_first))
+    }
+
+    /// How symlinks below the root are handled:
+    ///
+    /// - [`SymlinkPolicy::Recreate`] yields them without following them.
+    /// - [`SymlinkPolicy::Follow`] yields what they point at instead, and
+    ///   walks into it if it is a directory.
+    /// - [`SymlinkPolicy::Skip`] does not yield them.
+    ///
+    /// The root is never followed. It is [`SymlinkPolicy::Recreate`]
+    /// by default.
+    #[must_use]
+    pub fn symlinks(self, symlinks: SymlinkPolicy) -> Self {
+        self.map_walker(|walker| walker.symlinks(symlinks))
+    }
+
+    /// Only yield entries below the root for which `filter` returns `true`,
+    /// excluded directories are not walked into.
+    ///
+    /// `filter` is called with the path and metadata of the entry, after
+    /// symlinks are handled according to [`WalkDir::symlinks`].
+    #[must_use]
+    pub fn filter<F>(self, filter: F) -> Self
+    where
+        F: FnMut(&Path, &MetaData) -> bool + Send + Sync + 'static,
+    {
+        self.map_walker(|walker| walker.filter(filter))
+    }
+}
+
+impl Stream for WalkDir {
+    type Item = Result<WalkDirEntry, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        let future = match &mut this.future {
+            Some(future) => future,
+            None => {
+                let mut walker = match this.walker.take() {
+                    Some(walker) => walker,
+                    None => return Poll::Ready(None),
+                };
+
+                this.future.insert(Box::pin(async move {
+                    let res = walker.next().await;
+                    (walker, res)
+                }))
+            }
+        };
+
+        let (walker, res) = ready!(future.as_mut().poll(cx));
+        this.future = None;
+
+        // Drop the walker once it is exhausted, so that polling it
+        // again returns `None` without sending any request.
+        if res.is_some() {
+            this.walker = Some(walker);
+        }
+
+        Poll::Ready(res.map(|res| res.map(WalkDirEntry::from).map_err(walk_error)))
+    }
+}
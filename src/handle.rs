@@ -7,16 +7,94 @@ use std::{
     borrow::Cow,
     future::Future,
     ops::{Deref, DerefMut},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
 use derive_destructure2::destructure;
 
+/// I/O statistics of a remote file or directory handle, returned by
+/// [`crate::file::File::stats`] and [`crate::fs::Dir::stats`].
+///
+/// The statistics are shared by all clones of the same handle.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct IoStats {
+    bytes_read: u64,
+    bytes_written: u64,
+    requests: u64,
+    total_latency: Duration,
+}
+
+impl IoStats {
+    /// Number of bytes read via the handle.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Number of bytes written via the handle.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Number of requests on the handle that have completed,
+    /// including failed ones.
+    pub fn requests(&self) -> u64 {
+        self.requests
+    }
+
+    /// Average time between sending a request and receiving its response,
+    /// `None` if no request has completed yet.
+    pub fn average_latency(&self) -> Option<Duration> {
+        let requests: u32 = self.requests.try_into().unwrap_or(u32::MAX);
+        self.total_latency.checked_div(requests)
+    }
+}
+
+#[derive(Debug, Default)]
+pub(super) struct HandleStats {
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    requests: AtomicU64,
+    total_latency_nanos: AtomicU64,
+}
+
+impl HandleStats {
+    /// Record a request sent at `sent_at` that has just completed.
+    pub(super) fn record_request(&self, sent_at: Instant) {
+        let latency = sent_at.elapsed().as_nanos().try_into().unwrap_or(u64::MAX);
+
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.total_latency_nanos
+            .fetch_add(latency, Ordering::Relaxed);
+    }
+
+    pub(super) fn record_read(&self, n: usize) {
+        self.bytes_read.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    pub(super) fn record_written(&self, n: usize) {
+        self.bytes_written.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    pub(super) fn get(&self) -> IoStats {
+        IoStats {
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            requests: self.requests.load(Ordering::Relaxed),
+            total_latency: Duration::from_nanos(self.total_latency_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
 /// Remote Directory
 #[derive(Debug, Clone, destructure)]
 pub(super) struct OwnedHandle {
     pub(super) write_end: WriteEndWithCachedId,
     pub(super) handle: Arc<HandleOwned>,
+    pub(super) stats: Arc<HandleStats>,
 }
 
 impl Drop for OwnedHandle {
@@ -63,6 +141,7 @@ impl OwnedHandle {
         Self {
             write_end,
             handle: Arc::new(handle),
+            stats: Arc::default(),
         }
     }
 
@@ -73,10 +152,16 @@ impl OwnedHandle {
         R: Send,
     {
         let handle = &self.handle;
+        let sent_at = Instant::now();
 
-        self.write_end
+        let res = self
+            .write_end
             .send_request(|write_end, id| f(write_end, Cow::Borrowed(handle), id))
-            .await
+            .await;
+
+        self.stats.record_request(sent_at);
+
+        res
     }
 
     /// Close the [`OwnedHandle`], send the close request
@@ -90,7 +175,7 @@ impl OwnedHandle {
             // This is the last reference to the arc
 
             // Release resources without running `Drop::drop`
-            let (mut write_end, handle) = self.destructure();
+            let (mut write_end, handle, _stats) = self.destructure();
 
             write_end
                 .send_request(|write_end, id| {
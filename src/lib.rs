@@ -64,7 +64,7 @@ mod sftp;
 use sftp::SftpHandle;
 #[cfg(feature = "openssh")]
 pub use sftp::{CheckOpensshConnection, OpensshSession};
-pub use sftp::{Sftp, SftpAuxiliaryData};
+pub use sftp::{Sftp, SftpAuxiliaryData, SftpReadOnly};
 
 #[cfg(feature = "openssh")]
 pub use openssh;
@@ -77,13 +77,25 @@ use queue::MpscQueue;
 
 mod tasks;
 
+mod write_stall;
+
+mod events;
+pub use events::{CloseReason, ConnectionEvent};
+
+mod scope;
+pub use scope::SftpScope;
+
 mod auxiliary;
 use auxiliary::Auxiliary;
 
 mod cache;
 use cache::WriteEndWithCachedId;
 
+mod lru_cache;
+use lru_cache::LruCache;
+
 mod handle;
+pub use handle::IoStats;
 use handle::OwnedHandle;
 
 /// Module contains types for manipulating files.
@@ -95,6 +107,12 @@ pub mod fs;
 /// Module contains types for manipulating metadata of files or directories.
 pub mod metadata;
 
+/// Module contains types for transferring files between local and remote.
+pub mod transfer;
+
+/// Module contains a filesystem trait implemented for both remote and local.
+pub mod vfs;
+
 type Buffer = BytesMut;
 
 type WriteEnd = lowlevel::WriteEnd<Buffer, MpscQueue, Auxiliary>;
@@ -0,0 +1,371 @@
+use std::{
+    collections::BTreeMap,
+    ops::Bound,
+    path::{Component, Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use once_cell::sync::OnceCell;
+
+#[derive(Debug)]
+struct Entry {
+    content: Bytes,
+    /// Key into `Inner::order`, bumped every time the entry is accessed.
+    tick: u64,
+    /// When the content is inserted, used to expire the entry.
+    inserted_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// `BTreeMap` is used so that all entries under a directory are
+    /// adjacent and can be invalidated together.
+    entries: BTreeMap<Box<Path>, Entry>,
+    /// Entries ordered from the least recently used to the most
+    /// recently used.
+    order: BTreeMap<u64, Box<Path>>,
+    next_tick: u64,
+    total_len: usize,
+    /// Bumped on every invalidation, so that content read before the
+    /// invalidation is not inserted afterwards.
+    generation: u64,
+}
+
+impl Inner {
+    fn bump_tick(&mut self) -> u64 {
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        tick
+    }
+
+    fn remove(&mut self, path: &Path) {
+        if let Some(entry) = self.entries.remove(path) {
+            self.order.remove(&entry.tick);
+            self.total_len -= entry.content.len();
+        }
+    }
+
+    /// Remove `path` and every entry under it.
+    fn remove_all(&mut self, path: &Path) {
+        let paths: Vec<Box<Path>> = self
+            .entries
+            .range::<Path, _>((Bound::Included(path), Bound::Unbounded))
+            .map(|(key, _entry)| key)
+            .take_while(|key| key.starts_with(path))
+            .cloned()
+            .collect();
+
+        for path in paths {
+            self.remove(&path);
+        }
+    }
+}
+
+/// Opaque token returned by [`LruCache::generation`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(super) struct Generation(u64);
+
+/// In-memory LRU cache for the content of small files,
+/// used by [`crate::fs::Fs::read`].
+///
+/// Entries are keyed by the lexically normalized absolute path, relative
+/// paths are resolved against the default directory of the remote
+/// `sftp-server`. Paths that cannot be normalized without a round trip,
+/// i.e. those containing `..` or starting with `~`, are never cached and
+/// invalidating them drops the whole cache.
+///
+/// Entries expire `ttl` after they are inserted, so that changes not made
+/// through this client are eventually observed.
+#[derive(Debug)]
+pub(super) struct LruCache {
+    inner: Mutex<Inner>,
+    default_dir: OnceCell<Box<Path>>,
+    max_entry_len: usize,
+    max_total_len: usize,
+    ttl: Duration,
+}
+
+impl LruCache {
+    pub(super) fn new(max_entry_len: usize, max_total_len: usize, ttl: Duration) -> Self {
+        Self {
+            inner: Mutex::new(Inner::default()),
+            default_dir: OnceCell::new(),
+            max_entry_len,
+            max_total_len,
+            ttl,
+        }
+    }
+
+    /// Set the default directory of the remote `sftp-server`, used to
+    /// resolve relative paths.
+    pub(super) fn set_default_dir(&self, default_dir: Box<Path>) {
+        self.default_dir
+            .set(default_dir)
+            .expect("default_dir shall be uninitialized");
+    }
+
+    fn key(&self, path: &Path) -> Option<PathBuf> {
+        let mut components = path.components().peekable();
+        let mut key = PathBuf::new();
+
+        match components.peek() {
+            Some(Component::RootDir) => (),
+            // `~` and `~user` are expanded by the server.
+            Some(Component::Normal(name)) if name.to_string_lossy().starts_with('~') => {
+                return None
+            }
+            _ => key.push(self.default_dir.get()?),
+        }
+
+        for component in components {
+            match component {
+                Component::Prefix(_) | Component::ParentDir => return None,
+                Component::CurDir => (),
+                component => key.push(component),
+            }
+        }
+
+        Some(key)
+    }
+
+    /// Return the generation to pass to [`LruCache::insert`], it must be
+    /// obtained before reading the content to be inserted.
+    pub(super) fn generation(&self) -> Generation {
+        Generation(self.inner.lock().unwrap().generation)
+    }
+
+    /// Return the cached content of `path` and mark it as most
+    /// recently used, or `None` if it is not cached or has expired.
+    pub(super) fn get(&self, path: &Path) -> Option<Bytes> {
+        let path = self.key(path)?;
+
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.entries.get(&*path)?.inserted_at.elapsed() >= self.ttl {
+            inner.remove(&path);
+            return None;
+        }
+
+        let tick = inner.bump_tick();
+
+        let Inner { entries, order, .. } = &mut *inner;
+
+        let entry = entries.get_mut(&*path)?;
+        let path = order
+            .remove(&entry.tick)
+            .expect("every entry must have a corresponding order");
+        order.insert(tick, path);
+        entry.tick = tick;
+
+        Some(entry.content.clone())
+    }
+
+    /// Insert `content` of `path`, evicting least recently used entries
+    /// if the total length would exceed the limit.
+    ///
+    /// `content` that is larger than the maximum entry length, or that
+    /// is read before any invalidation since `generation`, is ignored.
+    pub(super) fn insert(&self, path: &Path, content: Bytes, generation: Generation) {
+        let len = content.len();
+        if len > self.max_entry_len || len > self.max_total_len {
+            return;
+        }
+
+        let path = match self.key(path) {
+            Some(path) => path,
+            None => return,
+        };
+
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.generation != generation.0 {
+            return;
+        }
+
+        inner.remove(&path);
+
+        while inner.total_len + len > self.max_total_len {
+            // Use `keys().next()` instead of `pop_first` to keep msrv 1.64
+            let lru_tick = *inner
+                .order
+                .keys()
+                .next()
+                .expect("total_len > 0 implies there must be at least one entry");
+            let lru_path = inner.order.remove(&lru_tick).unwrap();
+            let entry = inner
+                .entries
+                .remove(&lru_path)
+                .expect("every order must have a corresponding entry");
+            inner.total_len -= entry.content.len();
+        }
+
+        let tick = inner.bump_tick();
+        let path: Box<Path> = path.into();
+
+        inner.order.insert(tick, path.clone());
+        inner.entries.insert(
+            path,
+            Entry {
+                content,
+                tick,
+                inserted_at: Instant::now(),
+            },
+        );
+        inner.total_len += len;
+    }
+
+    /// Remove the cached content of `path` and of everything under it,
+    /// in case it is a directory.
+    ///
+    /// It must be called after the modification has completed.
+    pub(super) fn invalidate(&self, path: &Path) {
+        let key = self.key(path);
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.generation += 1;
+
+        match key {
+            Some(path) => inner.remove_all(&path),
+            None => {
+                let generation = inner.generation;
+                *inner = Inner {
+                    generation,
+                    ..Inner::default()
+                };
+            }
+        }
+    }
+
+    /// Remove all cached content.
+    pub(super) fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        let generation = inner.generation + 1;
+
+        *inner = Inner {
+            generation,
+            ..Inner::default()
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bytes, Duration, LruCache, Path};
+
+    fn new_cache(max_entry_len: usize, max_total_len: usize) -> LruCache {
+        new_cache_with_ttl(max_entry_len, max_total_len, Duration::from_secs(3600))
+    }
+
+    fn new_cache_with_ttl(max_entry_len: usize, max_total_len: usize, ttl: Duration) -> LruCache {
+        let cache = LruCache::new(max_entry_len, max_total_len, ttl);
+        cache.set_default_dir(Path::new("/home/user").into());
+        cache
+    }
+
+    #[test]
+    fn test_lru_cache_eviction() {
+        let cache = new_cache(4, 8);
+        let generation = cache.generation();
+
+        cache.insert(Path::new("a"), Bytes::from_static(b"aaaa"), generation);
+        cache.insert(Path::new("b"), Bytes::from_static(b"bbbb"), generation);
+
+        // Access "a" so that "b" becomes the least recently used.
+        assert_eq!(cache.get(Path::new("a")).unwrap(), &b"aaaa"[..]);
+
+        cache.insert(Path::new("c"), Bytes::from_static(b"cc"), generation);
+
+        assert!(cache.get(Path::new("b")).is_none());
+        assert!(cache.get(Path::new("a")).is_some());
+        assert!(cache.get(Path::new("c")).is_some());
+
+        // Entries larger than the max entry length are never cached.
+        cache.insert(Path::new("d"), Bytes::from_static(b"ddddd"), generation);
+        assert!(cache.get(Path::new("d")).is_none());
+
+        cache.invalidate(Path::new("a"));
+        assert!(cache.get(Path::new("a")).is_none());
+
+        cache.clear();
+        assert!(cache.get(Path::new("c")).is_none());
+    }
+
+    #[test]
+    fn test_lru_cache_key() {
+        let cache = new_cache(4, 8);
+
+        cache.insert(
+            Path::new("./dir//a"),
+            Bytes::from_static(b"a"),
+            cache.generation(),
+        );
+
+        assert!(cache.get(Path::new("dir/a")).is_some());
+        assert!(cache.get(Path::new("/home/user/dir/./a")).is_some());
+
+        // Paths that cannot be normalized are never cached.
+        cache.insert(
+            Path::new("dir/../b"),
+            Bytes::from_static(b"b"),
+            cache.generation(),
+        );
+        assert!(cache.get(Path::new("dir/../b")).is_none());
+        cache.insert(
+            Path::new("~/c"),
+            Bytes::from_static(b"c"),
+            cache.generation(),
+        );
+        assert!(cache.get(Path::new("~/c")).is_none());
+
+        // and invalidating them drops everything.
+        cache.invalidate(Path::new("dir/../a"));
+        assert!(cache.get(Path::new("dir/a")).is_none());
+    }
+
+    #[test]
+    fn test_lru_cache_invalidation() {
+        let cache = new_cache(4, 8);
+        let generation = cache.generation();
+
+        cache.insert(Path::new("dir/a"), Bytes::from_static(b"a"), generation);
+        cache.insert(Path::new("dir/b/c"), Bytes::from_static(b"c"), generation);
+        cache.insert(Path::new("dir2"), Bytes::from_static(b"d"), generation);
+
+        // Invalidating a directory invalidates everything under it.
+        cache.invalidate(Path::new("/home/user/dir"));
+        assert!(cache.get(Path::new("dir/a")).is_none());
+        assert!(cache.get(Path::new("dir/b/c")).is_none());
+        assert!(cache.get(Path::new("dir2")).is_some());
+
+        // Content read before an invalidation is not inserted.
+        cache.insert(Path::new("dir/a"), Bytes::from_static(b"a"), generation);
+        assert!(cache.get(Path::new("dir/a")).is_none());
+    }
+
+    #[test]
+    fn test_lru_cache_ttl() {
+        let cache = new_cache_with_ttl(4, 8, Duration::from_millis(50));
+
+        cache.insert(Path::new("a"), Bytes::from_static(b"a"), cache.generation());
+        assert!(cache.get(Path::new("a")).is_some());
+
+        std::thread::sleep(Duration::from_millis(100));
+
+        // Expired entries are removed instead of being returned.
+        assert!(cache.get(Path::new("a")).is_none());
+        cache.insert(
+            Path::new("b"),
+            Bytes::from_static(b"bbbb"),
+            cache.generation(),
+        );
+        cache.insert(
+            Path::new("c"),
+            Bytes::from_static(b"cccc"),
+            cache.generation(),
+        );
+        assert!(cache.get(Path::new("b")).is_some());
+        assert!(cache.get(Path::new("c")).is_some());
+    }
+}
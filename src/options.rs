@@ -1,11 +1,8 @@
 use std::{
-    num::{NonZeroU16, NonZeroUsize},
+    num::{NonZeroU16, NonZeroU32, NonZeroUsize},
     time::Duration,
 };
 
-#[cfg(feature = "__ci-tests")]
-use std::num::NonZeroU32;
-
 /// Options when creating [`super::Sftp`].
 #[derive(Debug, Copy, Clone, Default)]
 pub struct SftpOptions {
@@ -14,11 +11,15 @@ pub struct SftpOptions {
     flush_interval: Option<Duration>,
     max_pending_requests: Option<NonZeroU16>,
     tokio_compat_file_write_limit: Option<NonZeroUsize>,
-
-    #[cfg(feature = "__ci-tests")]
+    read_cache: Option<(NonZeroUsize, NonZeroUsize, Duration)>,
+    write_stall_deadline: Option<Duration>,
     max_read_len: Option<NonZeroU32>,
-    #[cfg(feature = "__ci-tests")]
     max_write_len: Option<NonZeroU32>,
+    max_outstanding_requests: Option<NonZeroUsize>,
+    request_timeout: Option<Duration>,
+    keepalive_interval: Option<Duration>,
+    strict_validation: bool,
+    max_response_len: Option<NonZeroU32>,
 }
 
 impl SftpOptions {
@@ -30,11 +31,15 @@ impl SftpOptions {
             flush_interval: None,
             max_pending_requests: None,
             tokio_compat_file_write_limit: None,
-
-            #[cfg(feature = "__ci-tests")]
+            read_cache: None,
+            write_stall_deadline: None,
             max_read_len: None,
-            #[cfg(feature = "__ci-tests")]
             max_write_len: None,
+            max_outstanding_requests: None,
+            request_timeout: None,
+            keepalive_interval: None,
+            strict_validation: false,
+            max_response_len: None,
         }
     }
 
@@ -138,14 +143,72 @@ impl SftpOptions {
             .map(NonZeroUsize::get)
             .unwrap_or(640 * 1024)
     }
-}
 
-#[cfg(feature = "__ci-tests")]
-impl SftpOptions {
-    /// Set `max_read_len`.
+    /// Enable the in-memory LRU cache for [`crate::fs::Fs::read`].
+    ///
+    /// * `max_entry_len` - files larger than this are never cached.
+    /// * `max_total_len` - the total length of all cached files, the least
+    ///   recently used ones are evicted once it is exceeded.
+    /// * `ttl` - how long a file is cached after it is read.
+    ///
+    /// The cache is shared by all [`crate::fs::Fs`] created from the same
+    /// [`super::Sftp`] and entries are invalidated when the file, or the
+    /// directory containing it, is modified through this [`super::Sftp`],
+    /// including writes to an already opened [`crate::file::File`].
+    /// However changes made by other clients or processes on the remote,
+    /// or made through a symlink, are not observed until the entry
+    /// expires after `ttl`.
+    ///
+    /// Enabling it costs one extra round trip in [`super::Sftp::new`] to
+    /// resolve the default directory of the remote `sftp-server`.
+    ///
+    /// Use [`super::Sftp::clear_read_cache`] to drop all cached entries.
     ///
-    /// It can be used to reduce `max_read_len`, but cannot be used
-    /// to increase `max_read_len`.
+    /// It is disabled by default.
+    #[must_use]
+    pub const fn read_cache(
+        mut self,
+        max_entry_len: NonZeroUsize,
+        max_total_len: NonZeroUsize,
+        ttl: Duration,
+    ) -> Self {
+        self.read_cache = Some((max_entry_len, max_total_len, ttl));
+        self
+    }
+
+    pub(super) fn get_read_cache(&self) -> Option<(usize, usize, Duration)> {
+        self.read_cache.map(|(max_entry_len, max_total_len, ttl)| {
+            (max_entry_len.get(), max_total_len.get(), ttl)
+        })
+    }
+
+    /// Set the deadline for writing requests to the server to make progress.
+    ///
+    /// If the underlying writer cannot accept any data within
+    /// `deadline`, e.g. because the ssh process hangs and its pipe buffer
+    /// is full, then the connection fails with
+    /// [`crate::Error::WriteStalled`] instead of waiting forever.
+    ///
+    /// It is disabled by default.
+    #[must_use]
+    pub const fn write_stall_deadline(mut self, deadline: Duration) -> Self {
+        self.write_stall_deadline = Some(deadline);
+        self
+    }
+
+    pub(super) fn get_write_stall_deadline(&self) -> Option<Duration> {
+        self.write_stall_deadline
+    }
+
+    /// Set `max_read_len`, the maximum amount of bytes read in one request.
+    ///
+    /// By default, it is the read length advertised by the server via
+    /// the limits extension, or the default of openssh-portable if the
+    /// server does not support it.
+    ///
+    /// It can be used to reduce `max_read_len`, e.g. for servers or
+    /// middleboxes that misbehave at the advertised maximum, but cannot
+    /// be used to increase `max_read_len`.
     #[must_use]
     pub const fn max_read_len(mut self, max_read_len: NonZeroU32) -> Self {
         self.max_read_len = Some(max_read_len);
@@ -156,7 +219,11 @@ impl SftpOptions {
         self.max_read_len.map(NonZeroU32::get)
     }
 
-    /// Set `max_write_len`.
+    /// Set `max_write_len`, the maximum amount of bytes written in one request.
+    ///
+    /// By default, it is the write length advertised by the server via
+    /// the limits extension, or the default of openssh-portable if the
+    /// server does not support it.
     ///
     /// It can be used to reduce `max_write_len`, but cannot be used
     /// to increase `max_write_len`.
@@ -169,15 +236,117 @@ impl SftpOptions {
     pub(super) fn get_max_write_len(&self) -> Option<u32> {
         self.max_write_len.map(NonZeroU32::get)
     }
-}
 
-#[cfg(not(feature = "__ci-tests"))]
-impl SftpOptions {
-    pub(super) const fn get_max_read_len(&self) -> Option<u32> {
-        None
+    /// Set `max_outstanding_requests`, the maximum number of requests
+    /// awaiting their responses at the same time.
+    ///
+    /// Once the limit is reached, new requests wait until one of the
+    /// outstanding requests is completed, and the waiting requests are
+    /// served in FIFO order.
+    ///
+    /// Requests sent in the background, e.g. by
+    /// [`crate::file::TokioCompatFile`] or when a handle is dropped, are not
+    /// counted.
+    ///
+    /// It is unlimited by default.
+    #[must_use]
+    pub const fn max_outstanding_requests(
+        mut self,
+        max_outstanding_requests: NonZeroUsize,
+    ) -> Self {
+        self.max_outstanding_requests = Some(max_outstanding_requests);
+        self
+    }
+
+    pub(super) fn get_max_outstanding_requests(&self) -> Option<usize> {
+        self.max_outstanding_requests.map(NonZeroUsize::get)
+    }
+
+    /// Set `request_timeout`, the maximum time to wait for the response
+    /// of a request.
+    ///
+    /// If the response does not arrive in time, then the request fails with
+    /// [`crate::Error::TimedOut`] and its response is discarded once it
+    /// arrives.
+    ///
+    /// It applies to requests sent by methods of [`crate::fs::Fs`],
+    /// [`crate::file::File`] and [`crate::fs::Dir`], but not to
+    /// [`crate::fs::ReadDir`] and [`crate::file::TokioCompatFile`].
+    ///
+    /// It can be overridden for one call by running it with
+    /// [`crate::SftpScope::run_with_timeout`], in which case only the
+    /// timeout passed to it applies.
+    ///
+    /// It can be changed after connecting via
+    /// [`super::Sftp::set_request_timeout`].
+    ///
+    /// It is disabled by default.
+    #[must_use]
+    pub const fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = Some(request_timeout);
+        self
+    }
+
+    pub(super) fn get_request_timeout(&self) -> Option<Duration> {
+        self.request_timeout
+    }
+
+    /// Set `keepalive_interval`.
+    ///
+    /// If no request is sent within `keepalive_interval`, then a cheap
+    /// request (realpath of `.`) is sent to keep the connection alive,
+    /// so that NAT or firewalls do not drop idle connections.
+    ///
+    /// If it is not answered by the time the next keepalive is due, then
+    /// [`crate::ConnectionEvent::KeepaliveMissed`] is emitted.
+    ///
+    /// It is disabled by default.
+    #[must_use]
+    pub const fn keepalive_interval(mut self, keepalive_interval: Duration) -> Self {
+        self.keepalive_interval = Some(keepalive_interval);
+        self
+    }
+
+    pub(super) fn get_keepalive_interval(&self) -> Option<Duration> {
+        self.keepalive_interval
+    }
+
+    /// Enable or disable strict validation of responses.
+    ///
+    /// With strict validation, responses with inconsistent lengths,
+    /// trailing bytes or ids that do not match any outstanding request
+    /// are rejected as [`crate::Error::MalformedPacket`], which fails
+    /// the connection.
+    ///
+    /// It is disabled by default.
+    #[must_use]
+    pub const fn strict_validation(mut self, strict_validation: bool) -> Self {
+        self.strict_validation = strict_validation;
+        self
+    }
+
+    pub(super) fn get_strict_validation(&self) -> bool {
+        self.strict_validation
+    }
+
+    /// Set `max_response_len`, the maximum length of a response packet
+    /// accepted from the server.
+    ///
+    /// Longer responses are rejected as [`crate::Error::MalformedPacket`]
+    /// before any memory is allocated for them, which fails the connection.
+    ///
+    /// It should be larger than [`super::Sftp::max_read_len`] and leave
+    /// room for directory listings, otherwise legitimate responses would
+    /// be rejected.
+    ///
+    /// It is unlimited by default.
+    #[must_use]
+    pub const fn max_response_len(mut self, max_response_len: NonZeroU32) -> Self {
+        self.max_response_len = Some(max_response_len);
+        self
     }
 
-    pub(super) const fn get_max_write_len(&self) -> Option<u32> {
-        None
+    pub(super) fn get_max_response_len(&self) -> Option<u32> {
+        self.max_response_len.map(NonZeroU32::get)
     }
 }
@@ -0,0 +1,128 @@
+use crate::{cancel_error, Error};
+
+use std::{future::Future, time::Duration};
+
+use tokio_util::sync::CancellationToken;
+
+tokio::task_local! {
+    /// Set while polling futures run by [`SftpScope::run_with_timeout`].
+    static REQUEST_TIMEOUT_OVERRIDDEN: ();
+}
+
+/// Return `true` if [`crate::SftpOptions::request_timeout`] is overridden
+/// by [`SftpScope::run_with_timeout`] in the current task.
+pub(super) fn is_request_timeout_overridden() -> bool {
+    REQUEST_TIMEOUT_OVERRIDDEN.try_with(|_| ()).is_ok()
+}
+
+/// A scope to run operations in, so that all of them can be cancelled
+/// at once, e.g. when the task using them is cancelled.
+///
+/// Futures wrapped by [`SftpScope::run`] are dropped once the scope is
+/// cancelled or dropped, which discards responses to their outstanding
+/// requests and closes the files and directories they opened in the
+/// background, as dropping [`File`](crate::file::File) and
+/// [`Dir`](crate::fs::Dir) does.
+///
+/// The scope is also cancelled if the background tasks of
+/// [`Sftp`](crate::Sftp) failed.
+///
+/// It is created by [`Sftp::scope`](crate::Sftp::scope).
+#[derive(Debug)]
+pub struct SftpScope {
+    token: CancellationToken,
+    /// Cancelled if the background tasks failed.
+    background: CancellationToken,
+}
+
+impl SftpScope {
+    pub(super) fn new(background: CancellationToken) -> Self {
+        Self {
+            token: background.child_token(),
+            background,
+        }
+    }
+
+    /// Run `future` in the scope.
+    ///
+    /// The returned future does not borrow the scope, so it can be spawned
+    /// as long as `future` can.
+    ///
+    /// It returns [`Error::Cancelled`] if the scope is cancelled or dropped
+    /// before `future` completes, or [`Error::BackgroundTaskFailure`] if
+    /// the background tasks failed.
+    pub fn run<F, T>(&self, future: F) -> impl Future<Output = Result<T, Error>>
+    where
+        F: Future<Output = Result<T, Error>>,
+    {
+        let token = self.token.clone();
+        let background = self.background.clone();
+
+        async move {
+            tokio::select! {
+                biased;
+
+                _ = token.cancelled() => Err(if background.is_cancelled() {
+                    cancel_error()
+                } else {
+                    Error::Cancelled
+                }),
+                res = future => res,
+            }
+        }
+    }
+
+    /// Run `future` in the scope with a timeout.
+    ///
+    /// It behaves like [`SftpScope::run`], except that it returns
+    /// [`Error::TimedOut`] if `future` does not complete within `timeout`.
+    ///
+    /// `future` is dropped on timeout, so responses to its outstanding
+    /// requests are discarded once they arrive and their request ids are
+    /// reclaimed, as if it is cancelled.
+    ///
+    /// [`crate::SftpOptions::request_timeout`] does not apply to requests
+    /// sent by `future`.
+    pub fn run_with_timeout<F, T>(
+        &self,
+        timeout: Duration,
+        future: F,
+    ) -> impl Future<Output = Result<T, Error>>
+    where
+        F: Future<Output = Result<T, Error>>,
+    {
+        let future = REQUEST_TIMEOUT_OVERRIDDEN.scope((), self.run(future));
+
+        async move {
+            tokio::time::timeout(timeout, future)
+                .await
+                .unwrap_or(Err(Error::TimedOut { timeout }))
+        }
+    }
+
+    /// Cancel all futures running in the scope, including futures run
+    /// in its children and ones run afterwards.
+    pub fn cancel(&self) {
+        self.token.cancel()
+    }
+
+    /// Return `true` if the scope is cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Create a child scope, which is cancelled when `self` is cancelled
+    /// but can also be cancelled on its own.
+    pub fn child(&self) -> SftpScope {
+        SftpScope {
+            token: self.token.child_token(),
+            background: self.background.clone(),
+        }
+    }
+}
+
+impl Drop for SftpScope {
+    fn drop(&mut self) {
+        self.token.cancel();
+    }
+}
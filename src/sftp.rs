@@ -1,28 +1,44 @@
 use crate::{
     auxiliary,
-    file::{File, OpenOptions},
+    file::{HandlePool, OpenBuilder, OpenOptions},
     fs::Fs,
     lowlevel, tasks,
     utils::{ErrorExt, ResultExt},
-    Error, MpscQueue, SftpOptions, SharedData, WriteEnd, WriteEndWithCachedId,
+    ConnectionEvent, Error, MpscQueue, SftpOptions, SftpScope, SharedData, WriteEnd,
+    WriteEndWithCachedId,
 };
 
 use auxiliary::Auxiliary;
-use lowlevel::{connect, Extensions};
+use lowlevel::{connect, Extensions, ServerHello};
 use tasks::{create_flush_task, create_read_task};
 
 use std::{
-    any::Any, convert::TryInto, fmt, future::Future, ops::Deref, path::Path, pin::Pin, sync::Arc,
+    any::Any,
+    borrow::Cow,
+    collections::BTreeMap,
+    convert::TryInto,
+    fmt,
+    future::Future,
+    num::{NonZeroU16, NonZeroU64, NonZeroUsize},
+    ops::Deref,
+    path::Path,
+    pin::Pin,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
 };
 
 use derive_destructure2::destructure;
 use tokio::{
     io::{AsyncRead, AsyncWrite},
     runtime::Handle,
-    sync::oneshot::Receiver,
+    sync::{oneshot::Receiver, watch},
     task::JoinHandle,
 };
 use tokio_io_utility::assert_send;
+use tokio_util::sync::CancellationToken;
+
+mod read_only;
+pub use read_only::SftpReadOnly;
 
 #[cfg(feature = "openssh")]
 mod openssh_session;
@@ -119,6 +135,30 @@ impl Sftp {
         Self::new_with_auxiliary(stdin, stdout, options, SftpAuxiliaryData::None).await
     }
 
+    /// Create [`Sftp`] from `stdin` and `stdout` implementing the traits
+    /// of `futures-io` instead of tokio.
+    ///
+    /// They are wrapped in the compat layer of `tokio-util`, this only
+    /// saves writing the wrapper by hand.
+    ///
+    /// A tokio runtime is still required, since the internal tasks are
+    /// spawned on it and rely on its timers, see [`Sftp::new_with_handle`].
+    /// Running on other runtimes, e.g. smol or async-std, is not supported.
+    #[cfg(feature = "futures-io")]
+    pub async fn new_futures_io<W, R>(
+        stdin: W,
+        stdout: R,
+        options: SftpOptions,
+    ) -> Result<Self, Error>
+    where
+        W: futures_io::AsyncWrite + Send + 'static,
+        R: futures_io::AsyncRead + Send + 'static,
+    {
+        use tokio_util::compat::{FuturesAsyncReadCompatExt, FuturesAsyncWriteCompatExt};
+
+        Self::new(stdin.compat_write(), stdout.compat(), options).await
+    }
+
     /// Create [`Sftp`] with some auxiliary data.
     ///
     /// The auxiliary data will be dropped after all sftp requests has been
@@ -138,22 +178,86 @@ impl Sftp {
         stdout: R,
         options: SftpOptions,
         auxiliary: SftpAuxiliaryData,
+    ) -> Result<Self, Error> {
+        Self::new_with_handle(stdin, stdout, options, auxiliary, Handle::current()).await
+    }
+
+    /// Create [`Sftp`] with some auxiliary data, spawning its internal
+    /// tasks on the tokio runtime of `tokio_handle` instead of the one
+    /// [`Sftp`] is created in.
+    ///
+    /// The internal tasks include the flush task, the read task and the
+    /// tasks that close handles in the background.
+    ///
+    /// NOTE that these tasks are `Send` and must be spawned on a tokio
+    /// runtime, so `tokio::task::LocalSet` and other executors are not
+    /// supported.
+    pub async fn new_with_handle<W: AsyncWrite + Send + 'static, R: AsyncRead + Send + 'static>(
+        stdin: W,
+        stdout: R,
+        options: SftpOptions,
+        auxiliary: SftpAuxiliaryData,
+        tokio_handle: Handle,
+    ) -> Result<Self, Error> {
+        Self::new_inner(stdin, stdout, options, auxiliary, tokio_handle, None).await
+    }
+
+    /// Same as [`Sftp::new_with_handle`], but the connection is shut down
+    /// once `cancellation_token` is cancelled.
+    ///
+    /// Once `cancellation_token` is cancelled, the internal tasks of
+    /// [`Sftp`] are aborted, all pending requests fail with
+    /// [`Error::Cancelled`] and new requests are rejected with
+    /// the same error.
+    ///
+    /// [`Sftp::close`] then returns the error of the aborted tasks.
+    pub async fn new_with_cancellation_token<
+        W: AsyncWrite + Send + 'static,
+        R: AsyncRead + Send + 'static,
+    >(
+        stdin: W,
+        stdout: R,
+        options: SftpOptions,
+        auxiliary: SftpAuxiliaryData,
+        tokio_handle: Handle,
+        cancellation_token: CancellationToken,
+    ) -> Result<Self, Error> {
+        Self::new_inner(
+            stdin,
+            stdout,
+            options,
+            auxiliary,
+            tokio_handle,
+            Some(cancellation_token),
+        )
+        .await
+    }
+
+    async fn new_inner<W: AsyncWrite + Send + 'static, R: AsyncRead + Send + 'static>(
+        stdin: W,
+        stdout: R,
+        options: SftpOptions,
+        auxiliary: SftpAuxiliaryData,
+        tokio_handle: Handle,
+        cancellation_token: Option<CancellationToken>,
     ) -> Result<Self, Error> {
         assert_send(async move {
             let write_end_buffer_size = options.get_write_end_buffer_size();
 
             let write_end = assert_send(Self::connect(
                 write_end_buffer_size.get(),
-                options.get_max_pending_requests(),
                 auxiliary,
-                options.get_tokio_compat_file_write_limit(),
+                &options,
+                tokio_handle,
+                cancellation_token,
             ))?;
 
             let flush_task = create_flush_task(
                 stdin,
                 SharedData::clone(&write_end),
                 write_end_buffer_size,
-                options.get_flush_interval(),
+                options.get_write_stall_deadline(),
+                options.get_keepalive_interval(),
             );
 
             let (rx, read_task) = create_read_task(
@@ -169,18 +273,14 @@ impl Sftp {
 
     fn connect(
         write_end_buffer_size: usize,
-        max_pending_requests: u16,
         auxiliary: SftpAuxiliaryData,
-        tokio_compat_file_write_limit: usize,
+        options: &SftpOptions,
+        tokio_handle: Handle,
+        cancellation_token: Option<CancellationToken>,
     ) -> Result<WriteEnd, Error> {
         connect(
             MpscQueue::with_capacity(write_end_buffer_size),
-            Auxiliary::new(
-                max_pending_requests,
-                auxiliary,
-                tokio_compat_file_write_limit,
-                Handle::current(),
-            ),
+            Auxiliary::new(options, auxiliary, tokio_handle, cancellation_token),
         )
     }
 
@@ -188,7 +288,7 @@ impl Sftp {
         flush_task: JoinHandle<Result<(), Error>>,
         read_task: JoinHandle<Result<(), Error>>,
         write_end: WriteEnd,
-        rx: Receiver<Extensions>,
+        rx: Receiver<ServerHello>,
         options: &SftpOptions,
     ) -> Result<Self, Error> {
         // Create sftp here.
@@ -203,8 +303,8 @@ impl Sftp {
 
         let write_end = WriteEndWithCachedId::new(write_end);
 
-        let extensions = if let Ok(extensions) = rx.await {
-            extensions
+        let server_hello = if let Ok(server_hello) = rx.await {
+            server_hello
         } else {
             drop(write_end);
 
@@ -213,7 +313,7 @@ impl Sftp {
             std::unreachable!("Error must have occurred in either read_task or flush_task")
         };
 
-        match Self::set_limits(write_end, options, extensions).await {
+        match Self::set_limits(write_end, options, server_hello).await {
             Err(Error::BackgroundTaskFailure(_)) => {
                 // Wait on flush_task and read_task to get a more detailed error message.
                 sftp.close().await?;
@@ -228,8 +328,10 @@ impl Sftp {
     async fn set_limits(
         mut write_end: WriteEndWithCachedId,
         options: &SftpOptions,
-        extensions: Extensions,
+        server_hello: ServerHello,
     ) -> Result<(), Error> {
+        let extensions = server_hello.extensions;
+
         let default_download_buflen = lowlevel::OPENSSH_PORTABLE_DEFAULT_DOWNLOAD_BUFLEN as u64;
         let default_upload_buflen = lowlevel::OPENSSH_PORTABLE_DEFAULT_UPLOAD_BUFLEN as u64;
 
@@ -237,34 +339,37 @@ impl Sftp {
         // is at least 9 bytes long.
         let default_max_packet_len = u32::MAX - 9;
 
-        let (read_len, write_len, packet_len) = if extensions.contains(Extensions::LIMITS) {
-            let mut limits = write_end
-                .send_request(|write_end, id| Ok(write_end.send_limits_request(id)?.wait()))
-                .await?;
-
-            if limits.read_len == 0 {
-                limits.read_len = default_download_buflen;
-            }
-
-            if limits.write_len == 0 {
-                limits.write_len = default_upload_buflen;
-            }
-
-            (
-                limits.read_len,
-                limits.write_len,
-                limits
-                    .packet_len
-                    .try_into()
-                    .unwrap_or(default_max_packet_len),
-            )
-        } else {
-            (
-                default_download_buflen,
-                default_upload_buflen,
-                default_max_packet_len,
-            )
-        };
+        let (read_len, write_len, packet_len, open_handles) =
+            if extensions.contains(Extensions::LIMITS) {
+                let mut limits = write_end
+                    .send_request(|write_end, id| Ok(write_end.send_limits_request(id)?.wait()))
+                    .await?;
+
+                if limits.read_len == 0 {
+                    limits.read_len = default_download_buflen;
+                }
+
+                if limits.write_len == 0 {
+                    limits.write_len = default_upload_buflen;
+                }
+
+                (
+                    limits.read_len,
+                    limits.write_len,
+                    limits
+                        .packet_len
+                        .try_into()
+                        .unwrap_or(default_max_packet_len),
+                    limits.open_handles,
+                )
+            } else {
+                (
+                    default_download_buflen,
+                    default_upload_buflen,
+                    default_max_packet_len,
+                    0,
+                )
+            };
 
         // Each read/write request also has a header and contains a handle,
         // which is 4-byte long for openssh but can be at most 256 bytes long
@@ -285,14 +390,42 @@ impl Sftp {
         let limits = auxiliary::Limits {
             read_len,
             write_len,
+            packet_len,
+            open_handles,
         };
 
         write_end
             .get_auxiliary()
             .conn_info
-            .set(auxiliary::ConnInfo { limits, extensions })
+            .set(auxiliary::ConnInfo {
+                limits,
+                extensions,
+                extension_pairs: server_hello.extension_pairs.into_iter().collect(),
+            })
             .expect("auxiliary.conn_info shall be uninitialized");
 
+        if write_end.get_auxiliary().read_cache().is_some() {
+            // Needed to resolve relative paths into keys of the read cache.
+            let default_dir = write_end
+                .send_request(|write_end, id| {
+                    Ok(write_end
+                        .send_realpath_request(id, Cow::Borrowed(Path::new(".")))?
+                        .wait())
+                })
+                .await?;
+
+            write_end
+                .get_auxiliary()
+                .read_cache()
+                .unwrap()
+                .set_default_dir(Path::new(&*default_dir).into());
+        }
+
+        write_end
+            .get_auxiliary()
+            .events
+            .send(ConnectionEvent::Connected);
+
         Ok(())
     }
 
@@ -364,26 +497,22 @@ impl Sftp {
     ///
     /// This function will create a file if it does not exist, and will truncate
     /// it if it does.
-    pub async fn create(&self, path: impl AsRef<Path>) -> Result<File, Error> {
-        async fn inner(this: &Sftp, path: &Path) -> Result<File, Error> {
-            this.options()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(path)
-                .await
-        }
-
-        inner(self, path.as_ref()).await
+    ///
+    /// The returned [`OpenBuilder`] can be awaited directly, or be further
+    /// configured before awaiting it.
+    pub fn create<P: AsRef<Path>>(&self, path: P) -> OpenBuilder<P> {
+        OpenBuilder::new(self.options(), path)
+            .write(true)
+            .create(true)
+            .truncate(true)
     }
 
     /// Attempts to open a file in read-only mode.
-    pub async fn open(&self, path: impl AsRef<Path>) -> Result<File, Error> {
-        async fn inner(this: &Sftp, path: &Path) -> Result<File, Error> {
-            this.options().read(true).open(path).await
-        }
-
-        inner(self, path.as_ref()).await
+    ///
+    /// The returned [`OpenBuilder`] can be awaited directly, or be further
+    /// configured before awaiting it, e.g. `sftp.open(path).write(true).await`.
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> OpenBuilder<P> {
+        OpenBuilder::new(self.options(), path).read(true)
     }
 
     /// [`Fs`] defaults to the current working dir set by remote `sftp-server`,
@@ -392,6 +521,198 @@ impl Sftp {
         Fs::new(self.handle.clone().write_end(), "".into())
     }
 
+    /// Create a [`HandlePool`] that keeps at most `capacity` recently
+    /// opened read-only handles open for reuse.
+    ///
+    /// If the server reports the maximum number of open handles it allows,
+    /// `capacity` is clamped to it.
+    pub fn handle_pool(&self, capacity: NonZeroUsize) -> HandlePool {
+        let capacity = match self.handle.get_auxiliary().limits().open_handles {
+            0 => capacity.get(),
+            open_handles => capacity
+                .get()
+                .min(open_handles.try_into().unwrap_or(usize::MAX)),
+        };
+
+        HandlePool::new(self.handle.clone(), capacity)
+    }
+
+    /// Return a [`watch::Receiver`] of the latest [`ConnectionEvent`],
+    /// so that the state of the connection can be observed without polling.
+    ///
+    /// Once [`ConnectionEvent::Closed`] is emitted, the receiver keeps
+    /// returning it.
+    pub fn connection_events(&self) -> watch::Receiver<ConnectionEvent> {
+        self.handle.get_auxiliary().events.subscribe()
+    }
+
+    /// Create a new [`SftpScope`] to run operations in.
+    pub fn scope(&self) -> SftpScope {
+        SftpScope::new(self.handle.get_auxiliary().cancel_token.clone())
+    }
+
+    /// Change [`SftpOptions::flush_interval`] of the running connection.
+    ///
+    /// It takes effect after the flush task wakes up next time.
+    pub fn set_flush_interval(&self, flush_interval: Duration) {
+        self.handle
+            .get_auxiliary()
+            .set_flush_interval(flush_interval)
+    }
+
+    /// Change [`SftpOptions::request_timeout`] of the running connection,
+    /// `None` disables it.
+    ///
+    /// It applies to requests sent after it is changed, the ones
+    /// already waiting for their responses keep the old timeout.
+    pub fn set_request_timeout(&self, request_timeout: Option<Duration>) {
+        self.handle
+            .get_auxiliary()
+            .set_request_timeout(request_timeout)
+    }
+
+    /// Change [`SftpOptions::max_pending_requests`] of the running connection.
+    ///
+    /// If there are already more pending requests than `max_pending_requests`,
+    /// then they are flushed immediately.
+    pub fn set_max_pending_requests(&self, max_pending_requests: NonZeroU16) {
+        self.handle
+            .get_auxiliary()
+            .set_max_pending_requests(max_pending_requests.get())
+    }
+
+    /// Change [`SftpOptions::tokio_compat_file_write_limit`] of the running
+    /// connection.
+    ///
+    /// It applies to all [`crate::file::TokioCompatFile`]s, including the
+    /// ones already created.
+    pub fn set_tokio_compat_file_write_limit(&self, limit: NonZeroUsize) {
+        self.handle
+            .get_auxiliary()
+            .set_tokio_compat_file_write_limit(limit.get())
+    }
+
+    /// Trigger flush task manually, sending out all requests in the write
+    /// buffer immediately without waiting for [`SftpOptions::flush_interval`].
+    pub fn manual_flush(&self) {
+        self.handle.get_auxiliary().trigger_flushing()
+    }
+
+    /// Flush the write buffer and wait until there is no outstanding
+    /// request, i.e. responses to all requests sent are received, including
+    /// the ones sent in the background, e.g. by
+    /// [`crate::file::TokioCompatFile`] or when a handle is dropped.
+    ///
+    /// It works regardless of the order in which the server responds,
+    /// however if requests keep being sent concurrently, it also waits
+    /// for them and might not return until they stop.
+    ///
+    /// # Cancel Safety
+    ///
+    /// This function is cancel safe.
+    pub async fn drain(&self) -> Result<(), Error> {
+        let auxiliary = self.handle.get_auxiliary();
+
+        self.manual_flush();
+
+        loop {
+            // Create the future before checking `responses_received`,
+            // so that no notification is missed.
+            let notified = auxiliary.responses_received_notify.notified();
+
+            // Load `requests_sent` first since it is always increased
+            // before `responses_received`.
+            let requests_sent = auxiliary.requests_sent.load(Ordering::Relaxed);
+            if auxiliary.responses_received.load(Ordering::Relaxed) >= requests_sent {
+                break Ok(());
+            }
+
+            tokio::select! {
+                biased;
+
+                _ = auxiliary.cancel_token.cancelled() => break Err(auxiliary.cancel_error()),
+                _ = notified => (),
+            }
+        }
+    }
+
+    /// Return `true` if the read task and the flush task have not failed
+    /// and the connection is not cancelled via the token passed to
+    /// [`Sftp::new_with_cancellation_token`].
+    ///
+    /// It does not contact the server, use [`Sftp::ping`] to check that
+    /// the server still responds.
+    pub fn is_alive(&self) -> bool {
+        !self.handle.get_auxiliary().cancel_token.is_cancelled()
+    }
+
+    /// Send a cheap request (realpath of `.`) and wait for its response,
+    /// to check that the server still responds.
+    pub async fn ping(&self) -> Result<(), Error> {
+        self.handle
+            .clone()
+            .write_end()
+            .send_request(|write_end, id| {
+                Ok(write_end
+                    .send_realpath_request(id, Cow::Borrowed(Path::new(".")))?
+                    .wait())
+            })
+            .await
+            .map(drop)
+    }
+
+    /// Drop all entries cached by the read cache enabled using
+    /// [`SftpOptions::read_cache`].
+    ///
+    /// It is a no-op if the read cache is not enabled.
+    pub fn clear_read_cache(&self) {
+        if let Some(read_cache) = self.handle.get_auxiliary().read_cache() {
+            read_cache.clear();
+        }
+    }
+
+    /// The maximum amount of bytes that can be written in one request.
+    /// Writing more than that, then your write will be split into multiple requests
+    ///
+    /// It is negotiated via the limits extension and can be lowered
+    /// using [`SftpOptions::max_write_len`].
+    pub fn max_write_len(&self) -> u32 {
+        self.handle.get_auxiliary().limits().write_len
+    }
+
+    /// The maximum amount of bytes that can be read in one request.
+    /// Reading more than that, then your read will be split into multiple requests
+    ///
+    /// It is negotiated via the limits extension and can be lowered
+    /// using [`SftpOptions::max_read_len`].
+    pub fn max_read_len(&self) -> u32 {
+        self.handle.get_auxiliary().limits().read_len
+    }
+
+    /// The maximum length of a packet the server accepts.
+    ///
+    /// It is negotiated via the limits extension.
+    pub fn max_packet_len(&self) -> u32 {
+        self.handle.get_auxiliary().limits().packet_len
+    }
+
+    /// The maximum number of handles the server allows to be opened at
+    /// the same time, `None` if the server does not report it.
+    ///
+    /// It is negotiated via the limits extension.
+    pub fn max_open_handles(&self) -> Option<NonZeroU64> {
+        NonZeroU64::new(self.handle.get_auxiliary().limits().open_handles)
+    }
+
+    /// Return all extensions the server advertises in its version packet,
+    /// mapping the name of each extension to its data (usually its version),
+    /// including the ones this crate does not support.
+    ///
+    /// Use the `support_*` methods to check for extensions used by this crate.
+    pub fn server_extensions(&self) -> &BTreeMap<String, String> {
+        self.handle.get_auxiliary().extension_pairs()
+    }
+
     /// Check if the remote server supports the expand path extension.
     ///
     /// If it returns true, then [`Fs::canonicalize`] with expand path is supported.
@@ -404,7 +725,7 @@ impl Sftp {
 
     /// Check if the remote server supports the fsync extension.
     ///
-    /// If it returns true, then [`File::sync_all`] is supported.
+    /// If it returns true, then [`File::sync_all`](crate::file::File::sync_all) is supported.
     pub fn support_fsync(&self) -> bool {
         self.handle
             .get_auxiliary()
@@ -432,37 +753,35 @@ impl Sftp {
             .contains(Extensions::POSIX_RENAME)
     }
 
-    /// Check if the remote server supports the copy data extension.
+    /// Check if the remote server supports the lsetstat extension.
     ///
-    /// If it returns true, then [`File::copy_to`] and [`File::copy_all_to`] are supported.
-    pub fn support_copy(&self) -> bool {
+    /// If it returns true, then [`Fs::set_symlink_metadata`] is supported.
+    pub fn support_lsetstat(&self) -> bool {
         self.handle
             .get_auxiliary()
             .extensions()
-            .contains(Extensions::COPY_DATA)
+            .contains(Extensions::LSETSTAT)
     }
-}
 
-#[cfg(feature = "__ci-tests")]
-impl Sftp {
-    /// The maximum amount of bytes that can be written in one request.
-    /// Writing more than that, then your write will be split into multiple requests
+    /// Check if the remote server supports the statvfs and fstatvfs extensions.
     ///
-    /// If [`Sftp::max_buffered_write`] is less than [`max_atomic_write_len`],
-    /// then the direct write is enabled and [`Sftp::max_write_len`] must be
-    /// less than [`max_atomic_write_len`].
-    pub fn max_write_len(&self) -> u32 {
-        self.handle.get_auxiliary().limits().write_len
-    }
-
-    /// The maximum amount of bytes that can be read in one request.
-    /// Reading more than that, then your read will be split into multiple requests
-    pub fn max_read_len(&self) -> u32 {
-        self.handle.get_auxiliary().limits().read_len
+    /// If it returns true, then [`Fs::statvfs`] and
+    /// [`File::statvfs`](crate::file::File::statvfs) are supported.
+    pub fn support_statvfs(&self) -> bool {
+        self.handle
+            .get_auxiliary()
+            .extensions()
+            .contains(Extensions::STATVFS | Extensions::FSTATVFS)
     }
 
-    /// Trigger flush task manually.
-    pub fn manual_flush(&self) {
-        self.handle.get_auxiliary().trigger_flushing()
+    /// Check if the remote server supports the copy data extension.
+    ///
+    /// If it returns true, then [`File::copy_to`](crate::file::File::copy_to) and
+    /// [`File::copy_all_to`](crate::file::File::copy_all_to) are supported.
+    pub fn support_copy(&self) -> bool {
+        self.handle
+            .get_auxiliary()
+            .extensions()
+            .contains(Extensions::COPY_DATA)
     }
 }
@@ -1,5 +1,7 @@
 use std::{fmt::Debug, future::Future, ops::Deref, pin::Pin, sync::Arc};
 
+#[cfg(feature = "tracing")]
+use openssh::ChildStderr;
 use openssh::{ChildStdin, ChildStdout, Error as OpensshError, Session, Stdio};
 use tokio::{sync::oneshot, task::JoinHandle};
 
@@ -41,6 +43,28 @@ impl Drop for OpensshSession {
     }
 }
 
+/// Forward diagnostics printed by the remote sftp subsystem to its stderr
+/// as tracing events.
+#[cfg(feature = "tracing")]
+async fn forward_stderr(stderr: Option<ChildStderr>) {
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    if let Some(stderr) = stderr {
+        let mut lines = BufReader::new(stderr).lines();
+
+        loop {
+            match lines.next_line().await {
+                Ok(Some(line)) => tracing::info!("sftp subsystem stderr: {line}"),
+                Ok(None) => break,
+                Err(err) => {
+                    tracing::error!("Failed to read stderr of sftp subsystem: {err}");
+                    break;
+                }
+            }
+        }
+    }
+}
+
 #[cfg_attr(
     feature = "tracing",
     tracing::instrument(name = "session_task", skip(tx, check_openssh_connection))
@@ -53,10 +77,16 @@ async fn create_session_task(
     #[cfg(feature = "tracing")]
     tracing::info!("Connecting to sftp subsystem, session = {session:?}");
 
+    // Only capture stderr if it can be forwarded somewhere.
+    #[cfg(feature = "tracing")]
+    let stderr = Stdio::piped();
+    #[cfg(not(feature = "tracing"))]
+    let stderr = Stdio::null();
+
     let res = Session::to_subsystem(session.clone(), "sftp")
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
-        .stderr(Stdio::null())
+        .stderr(stderr)
         .spawn()
         .await;
 
@@ -80,6 +110,9 @@ async fn create_session_task(
     let stdout = child.stdout().take().unwrap();
     tx.send(Ok((stdin, stdout))).unwrap(); // Ok
 
+    #[cfg(feature = "tracing")]
+    let stderr = child.stderr().take();
+
     let original_error = {
         let check_conn_future = async {
             if let Some(checker) = check_openssh_connection {
@@ -94,7 +127,12 @@ async fn create_session_task(
         };
 
         let wait_on_child_future = async {
-            match child.wait().await {
+            #[cfg(feature = "tracing")]
+            let (_, res) = tokio::join!(forward_stderr(stderr), child.wait());
+            #[cfg(not(feature = "tracing"))]
+            let res = child.wait().await;
+
+            match res {
                 Ok(exit_status) => {
                     if !exit_status.success() {
                         Some(Error::SftpServerFailure(exit_status))
@@ -141,6 +179,9 @@ impl Sftp {
     /// would also await on [`openssh::RemoteChild::wait`] and
     /// [`openssh::Session::close`] and propagate their error in
     /// [`Sftp::close`].
+    ///
+    /// If feature `tracing` is enabled, then everything the remote sftp
+    /// subsystem writes to its stderr is forwarded as tracing events.
     pub async fn from_session(session: Session, options: SftpOptions) -> Result<Self, Error> {
         Self::from_session_with_check_connection_inner(session, options, None).await
     }
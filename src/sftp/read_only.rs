@@ -0,0 +1,54 @@
+use super::{Sftp, SftpHandle};
+
+use crate::{
+    file::{OpenOptions, ReadOnlyFile},
+    fs::{Fs, ReadOnlyFs},
+    Error,
+};
+
+use std::path::Path;
+
+/// A read-only view of [`Sftp`].
+///
+/// It only exposes non-mutating operations, so a component given
+/// [`SftpReadOnly`] provably cannot write, remove or rename anything
+/// on the remote filesystem.
+///
+/// Cloning it is cheap and it can outlive the [`Sftp`] it is created from,
+/// though [`Sftp::close`] would wait until all of its clones are dropped.
+#[derive(Debug, Clone)]
+pub struct SftpReadOnly {
+    handle: SftpHandle,
+}
+
+impl SftpReadOnly {
+    pub(super) fn new(handle: SftpHandle) -> Self {
+        Self { handle }
+    }
+
+    /// Attempts to open a file in read-only mode.
+    pub async fn open(&self, path: impl AsRef<Path>) -> Result<ReadOnlyFile, Error> {
+        async fn inner(this: &SftpReadOnly, path: &Path) -> Result<ReadOnlyFile, Error> {
+            OpenOptions::new(this.handle.clone())
+                .read(true)
+                .open(path)
+                .await
+                .map(ReadOnlyFile::from)
+        }
+
+        inner(self, path.as_ref()).await
+    }
+
+    /// [`ReadOnlyFs`] defaults to the current working dir set by remote
+    /// `sftp-server`, which usually is the home directory.
+    pub fn fs(&self) -> ReadOnlyFs {
+        Fs::new(self.handle.clone().write_end(), "".into()).into()
+    }
+}
+
+impl Sftp {
+    /// Return a [`SftpReadOnly`] that only exposes non-mutating operations.
+    pub fn read_only(&self) -> SftpReadOnly {
+        SftpReadOnly::new(self.handle.clone())
+    }
+}
@@ -1,7 +1,15 @@
-use super::{lowlevel::Extensions, Error, ReadEnd, SharedData};
+use super::{
+    events::EventSender,
+    lowlevel::ServerHello,
+    write_stall::{self, StallDetector},
+    CloseReason, ConnectionEvent, Error, ReadEnd, SharedData, WriteEnd,
+};
 
 use std::{
+    borrow::Cow,
+    future::Future,
     num::NonZeroUsize,
+    path::Path,
     pin::Pin,
     sync::atomic::{AtomicUsize, Ordering},
     time::Duration,
@@ -13,10 +21,11 @@ use tokio::{
     io::{AsyncRead, AsyncWrite},
     pin,
     sync::oneshot,
-    task::{spawn, JoinHandle},
+    task::JoinHandle,
     time,
 };
 use tokio_io_utility::{write_all_bytes, ReusableIoSlices};
+use tokio_util::sync::CancellationToken;
 
 async fn flush(
     shared_data: &SharedData,
@@ -35,11 +44,46 @@ async fn flush(
     // `Queue` implementation for `MpscQueue` already removes
     // all empty `Bytes`s so that precond of write_all_bytes
     // is satisfied.
-    write_all_bytes(writer, buffer, reusable_io_slices).await?;
+    write_all_bytes(writer, buffer, reusable_io_slices)
+        .await
+        .map_err(write_stall::map_io_error)?;
 
     Ok(())
 }
 
+/// Send a realpath request of "." to keep the connection alive,
+/// its response is discarded by the read task.
+fn send_keepalive(shared_data: &SharedData) -> Result<(), Error> {
+    let auxiliary = shared_data.get_auxiliary();
+
+    // Once shutdown is ordered, no new request can be sent.
+    if !auxiliary.try_inc_active_user_count() {
+        return Ok(());
+    }
+
+    let mut write_end = WriteEnd::new(shared_data.clone());
+    let id = write_end.create_response_id();
+
+    let res = write_end
+        .send_realpath_request(id, Cow::Borrowed(Path::new(".")))
+        .map(drop);
+    if res.is_ok() {
+        auxiliary.wakeup_flush_task();
+    }
+
+    auxiliary.dec_active_user_count();
+
+    res
+}
+
+fn send_closed(events: &EventSender, res: &Result<(), Error>) {
+    let reason = match res {
+        Ok(()) => CloseReason::Requested,
+        Err(err) => CloseReason::Failed(err.to_string()),
+    };
+    events.send(ConnectionEvent::Closed(reason));
+}
+
 /// Return the size after substraction.
 ///
 /// # Panic
@@ -49,11 +93,42 @@ fn atomic_sub_assign(atomic: &AtomicUsize, val: usize) -> usize {
     atomic.fetch_sub(val, Ordering::Relaxed) - val
 }
 
+/// Abort `future` with [`Error::Cancelled`] once `shutdown_token` is
+/// cancelled.
+///
+/// Dropping the task cancels `Auxiliary::cancel_token`, which fails all
+/// pending requests.
+async fn abort_on_shutdown(
+    shutdown_token: Option<CancellationToken>,
+    future: impl Future<Output = Result<(), Error>>,
+) -> Result<(), Error> {
+    match shutdown_token {
+        Some(shutdown_token) => tokio::select! {
+            biased;
+
+            _ = shutdown_token.cancelled() => Err(Error::Cancelled),
+            res = future => res,
+        },
+        None => future.await,
+    }
+}
+
+fn new_interval(flush_interval: Duration) -> Option<time::Interval> {
+    if !flush_interval.is_zero() {
+        let mut interval = time::interval(flush_interval);
+        interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+        Some(interval)
+    } else {
+        None
+    }
+}
+
 pub(super) fn create_flush_task<W: AsyncWrite + Send + 'static>(
     writer: W,
     shared_data: SharedData,
     write_end_buffer_size: NonZeroUsize,
-    flush_interval: Duration,
+    write_stall_deadline: Option<Duration>,
+    keepalive_interval: Option<Duration>,
 ) -> JoinHandle<Result<(), Error>> {
     #[cfg_attr(
         feature = "tracing",
@@ -63,25 +138,25 @@ pub(super) fn create_flush_task<W: AsyncWrite + Send + 'static>(
         mut writer: Pin<&mut (dyn AsyncWrite + Send)>,
         shared_data: SharedData,
         write_end_buffer_size: NonZeroUsize,
-        flush_interval: Duration,
+        keepalive_interval: Option<Duration>,
     ) -> Result<(), Error> {
-        let mut interval = if !flush_interval.is_zero() {
-            let mut interval = time::interval(flush_interval);
-            interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
-            Some(interval)
-        } else {
-            None
-        };
-
         let auxiliary = shared_data.get_auxiliary();
+
+        let mut flush_interval = auxiliary.flush_interval();
+        let mut interval = new_interval(flush_interval);
+
         let flush_end_notify = &auxiliary.flush_end_notify;
         let read_end_notify = &auxiliary.read_end_notify;
         let pending_requests = &auxiliary.pending_requests;
         let shutdown_stage = &auxiliary.shutdown_stage;
-        let max_pending_requests = auxiliary.max_pending_requests();
+        let responses_received = &auxiliary.responses_received;
 
         let cancel_guard = auxiliary.cancel_token.clone().drop_guard();
 
+        // `responses_received` when the last keepalive is sent.
+        let mut keepalive_sent = None;
+        let mut keepalive_missed = false;
+
         let mut backup_queue_buffer = Vec::with_capacity(write_end_buffer_size.get());
         let mut reusable_io_slices = ReusableIoSlices::new(write_end_buffer_size);
 
@@ -112,7 +187,7 @@ pub(super) fn create_flush_task<W: AsyncWrite + Send + 'static>(
 
                 cnt = atomic_sub_assign(pending_requests, cnt);
 
-                if cnt < max_pending_requests {
+                if cnt < auxiliary.max_pending_requests() {
                     break;
                 }
             }
@@ -136,7 +211,41 @@ pub(super) fn create_flush_task<W: AsyncWrite + Send + 'static>(
                 break Ok(());
             }
 
-            flush_end_notify.notified().await;
+            match keepalive_interval {
+                Some(keepalive_interval) => {
+                    // No request is sent within keepalive_interval.
+                    if time::timeout(keepalive_interval, flush_end_notify.notified())
+                        .await
+                        .is_err()
+                    {
+                        let received = responses_received.load(Ordering::Relaxed);
+
+                        // Nothing is received since the last keepalive.
+                        if keepalive_sent == Some(received) && !keepalive_missed {
+                            keepalive_missed = true;
+                            auxiliary.events.send(ConnectionEvent::KeepaliveMissed);
+                        }
+
+                        keepalive_sent = Some(received);
+                        send_keepalive(&shared_data)?;
+                    }
+
+                    if keepalive_missed
+                        && keepalive_sent != Some(responses_received.load(Ordering::Relaxed))
+                    {
+                        keepalive_missed = false;
+                        auxiliary.events.send(ConnectionEvent::Connected);
+                    }
+                }
+                None => flush_end_notify.notified().await,
+            }
+
+            // Pick up flush_interval changed via `Sftp::set_flush_interval`.
+            let new_flush_interval = auxiliary.flush_interval();
+            if new_flush_interval != flush_interval {
+                flush_interval = new_flush_interval;
+                interval = new_interval(flush_interval);
+            }
 
             if let Some(interval) = interval.as_mut() {
                 tokio::select! {
@@ -155,10 +264,27 @@ pub(super) fn create_flush_task<W: AsyncWrite + Send + 'static>(
         }
     }
 
-    spawn(async move {
+    let tokio_handle = shared_data.get_auxiliary().tokio_handle().clone();
+
+    tokio_handle.spawn(async move {
+        let writer = StallDetector::new(writer, write_stall_deadline);
         pin!(writer);
 
-        inner(writer, shared_data, write_end_buffer_size, flush_interval).await
+        let events = shared_data.get_auxiliary().events.clone();
+        let shutdown_token = shared_data.get_auxiliary().shutdown_token.clone();
+
+        let res = abort_on_shutdown(
+            shutdown_token,
+            inner(
+                writer,
+                shared_data,
+                write_end_buffer_size,
+                keepalive_interval,
+            ),
+        )
+        .await;
+        send_closed(&events, &res);
+        res
     })
 }
 
@@ -166,7 +292,10 @@ pub(super) fn create_read_task<R: AsyncRead + Send + 'static>(
     stdout: R,
     read_end_buffer_size: NonZeroUsize,
     shared_data: SharedData,
-) -> (oneshot::Receiver<Extensions>, JoinHandle<Result<(), Error>>) {
+) -> (
+    oneshot::Receiver<ServerHello>,
+    JoinHandle<Result<(), Error>>,
+) {
     #[cfg_attr(
         feature = "tracing",
         tracing::instrument(name = "read_task", skip(stdout, tx, shared_data), err)
@@ -175,11 +304,16 @@ pub(super) fn create_read_task<R: AsyncRead + Send + 'static>(
         stdout: Pin<&mut (dyn AsyncRead + Send)>,
         read_end_buffer_size: NonZeroUsize,
         shared_data: SharedData,
-        tx: oneshot::Sender<Extensions>,
+        tx: oneshot::Sender<ServerHello>,
     ) -> Result<(), Error> {
-        let read_end = ReadEnd::new(stdout, read_end_buffer_size, shared_data.clone());
+        let mut read_end = ReadEnd::new(stdout, read_end_buffer_size, shared_data.clone());
 
         let auxiliary = shared_data.get_auxiliary();
+        read_end.set_strict_validation(auxiliary.strict_validation);
+        if let Some(max_response_len) = auxiliary.max_response_len {
+            read_end.set_max_packet_len(max_response_len);
+        }
+
         let read_end_notify = &auxiliary.read_end_notify;
         let requests_to_read = &auxiliary.requests_to_read;
         let shutdown_stage = &auxiliary.shutdown_stage;
@@ -204,9 +338,12 @@ pub(super) fn create_read_task<R: AsyncRead + Send + 'static>(
         tracing::debug!("Receiving version and extensions, shared_data = {shared_data:p}");
 
         // Receive version and extensions
-        let extensions = read_end.as_mut().receive_server_hello_pinned().await?;
+        let server_hello = read_end
+            .as_mut()
+            .receive_server_hello_with_pairs_pinned()
+            .await?;
 
-        tx.send(extensions).unwrap();
+        tx.send(server_hello).unwrap();
 
         loop {
             read_end_notify.notified().await;
@@ -225,6 +362,11 @@ pub(super) fn create_read_task<R: AsyncRead + Send + 'static>(
                     read_end.as_mut().read_in_one_packet_pinned().await?;
                 }
 
+                auxiliary
+                    .responses_received
+                    .fetch_add(cnt as u64, Ordering::Relaxed);
+                auxiliary.responses_received_notify.notify_waiters();
+
                 cnt = atomic_sub_assign(requests_to_read, cnt);
             }
 
@@ -240,10 +382,21 @@ pub(super) fn create_read_task<R: AsyncRead + Send + 'static>(
 
     let (tx, rx) = oneshot::channel();
 
-    let handle = spawn(async move {
+    let tokio_handle = shared_data.get_auxiliary().tokio_handle().clone();
+
+    let handle = tokio_handle.spawn(async move {
         pin!(stdout);
 
-        inner(stdout, read_end_buffer_size, shared_data, tx).await
+        let events = shared_data.get_auxiliary().events.clone();
+        let shutdown_token = shared_data.get_auxiliary().shutdown_token.clone();
+
+        let res = abort_on_shutdown(
+            shutdown_token,
+            inner(stdout, read_end_buffer_size, shared_data, tx),
+        )
+        .await;
+        send_closed(&events, &res);
+        res
     });
 
     (rx, handle)
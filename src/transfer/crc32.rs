@@ -0,0 +1,61 @@
+//! CRC-32 (IEEE 802.3), as used by gzip, zip and `cksum -a crc32b`.
+
+const TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            j += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+};
+
+#[derive(Debug, Copy, Clone)]
+pub(super) struct Crc32(u32);
+
+impl Crc32 {
+    pub(super) const fn new() -> Self {
+        Self(!0)
+    }
+
+    pub(super) fn update(&mut self, bytes: &[u8]) {
+        let mut crc = self.0;
+        for byte in bytes {
+            crc = TABLE[((crc as u8) ^ byte) as usize] ^ (crc >> 8);
+        }
+        self.0 = crc;
+    }
+
+    pub(super) const fn finish(self) -> u32 {
+        !self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(Crc32::new().finish(), 0);
+
+        let mut crc = Crc32::new();
+        crc.update(b"1234");
+        crc.update(b"56789");
+        assert_eq!(crc.finish(), 0xCBF4_3926);
+    }
+}
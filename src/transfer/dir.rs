@@ -0,0 +1,463 @@
+use super::{Filter, TransferOptions, TransferReport};
+
+use crate::{
+    fs::{walk_error, BoxFuture, Children, Fs, SymlinkPolicy, Tree, TreeEntry, Walker},
+    Error, Sftp,
+};
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    future::{poll_fn, Future},
+    io,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    pin::Pin,
+    task::Poll,
+};
+
+use tokio::fs;
+
+/// Options for [`Sftp::upload_dir`] and [`Sftp::download_dir`].
+#[derive(Debug, Copy, Clone)]
+pub struct DirTransferOptions<'a> {
+    file: TransferOptions,
+    max_parallel: usize,
+    symlinks: SymlinkPolicy,
+    preserve_hardlinks: bool,
+    filter: Option<&'a Filter>,
+}
+
+impl Default for DirTransferOptions<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> DirTransferOptions<'a> {
+    /// Create a new [`DirTransferOptions`].
+    pub const fn new() -> Self {
+        Self {
+            file: TransferOptions::new(),
+            max_parallel: 4,
+            symlinks: SymlinkPolicy::Recreate,
+            preserve_hardlinks: false,
+            filter: None,
+        }
+    }
+
+    /// Options used to transfer each regular file.
+    #[must_use]
+    pub const fn file_options(mut self, file: TransferOptions) -> Self {
+        self.file = file;
+        self
+    }
+
+    /// Maximum number of files transferred at the same time, default is `4`.
+    #[must_use]
+    pub const fn max_parallel(mut self, max_parallel: NonZeroUsize) -> Self {
+        self.max_parallel = max_parallel.get();
+        self
+    }
+
+    /// How symlinks are handled, default is [`SymlinkPolicy::Recreate`].
+    #[must_use]
+    pub const fn symlinks(mut self, symlinks: SymlinkPolicy) -> Self {
+        self.symlinks = symlinks;
+        self
+    }
+
+    /// Recreate local files with multiple hard links as hard links on the
+    /// remote instead of uploading them once per link, default is `false`.
+    ///
+    /// It only applies to [`Sftp::upload_dir`] on unix, since sftp does not
+    /// expose inode numbers of remote files. If the server does not support
+    /// the `hardlink` extension, every link is uploaded as a separate file.
+    #[must_use]
+    pub const fn preserve_hardlinks(mut self, preserve_hardlinks: bool) -> Self {
+        self.preserve_hardlinks = preserve_hardlinks;
+        self
+    }
+
+    /// Only transfer paths included by `filter`, default is to transfer
+    /// everything.
+    #[must_use]
+    pub const fn filter(mut self, filter: &'a Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Return a [`Walker`] of the source tree `root` configured
+    /// by these options.
+    fn walker<T: Tree>(&self, tree: T, root: &Path) -> Walker<T> {
+        let walker = Walker::new(tree, root.to_path_buf())
+            .follow_root(true)
+            .symlinks(self.symlinks);
+
+        match self.filter {
+            Some(filter) => {
+                let filter = filter.clone();
+                let root = root.to_path_buf();
+
+                walker.filter(move |path, metadata| match path.strip_prefix(&root) {
+                    Ok(path) => filter.is_included(path, T::is_dir(metadata)),
+                    Err(_) => true,
+                })
+            }
+            None => walker,
+        }
+    }
+}
+
+/// Result of [`Sftp::upload_dir`] and [`Sftp::download_dir`].
+#[derive(Debug, Copy, Clone, Default)]
+pub struct DirTransferReport {
+    files: u64,
+    bytes: u64,
+    hardlinks: u64,
+}
+
+impl DirTransferReport {
+    /// Number of regular files transferred.
+    pub const fn files(&self) -> u64 {
+        self.files
+    }
+
+    /// Total number of bytes transferred.
+    pub const fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Number of hard links created instead of transferring the file again,
+    /// see [`DirTransferOptions::preserve_hardlinks`].
+    pub const fn hardlinks(&self) -> u64 {
+        self.hardlinks
+    }
+}
+
+type TransferFuture<'a> = Pin<Box<dyn Future<Output = Result<TransferReport, Error>> + Send + 'a>>;
+
+/// File transfers that are running concurrently.
+struct Transfers<'a> {
+    futures: Vec<TransferFuture<'a>>,
+    max_parallel: usize,
+    report: DirTransferReport,
+}
+
+impl<'a> Transfers<'a> {
+    fn new(max_parallel: usize) -> Self {
+        Self {
+            futures: Vec::new(),
+            max_parallel,
+            report: DirTransferReport::default(),
+        }
+    }
+
+    async fn push(&mut self, future: TransferFuture<'a>) -> Result<(), Error> {
+        if self.futures.len() >= self.max_parallel {
+            self.wait_one().await?;
+        }
+        self.futures.push(future);
+
+        Ok(())
+    }
+
+    async fn wait_one(&mut self) -> Result<(), Error> {
+        let futures = &mut self.futures;
+
+        let report = poll_fn(|cx| {
+            for i in 0..futures.len() {
+                if let Poll::Ready(res) = futures[i].as_mut().poll(cx) {
+                    drop(futures.swap_remove(i));
+                    return Poll::Ready(res);
+                }
+            }
+            Poll::Pending
+        })
+        .await?;
+
+        self.report.files += 1;
+        self.report.bytes += report.len();
+
+        Ok(())
+    }
+
+    async fn finish(mut self) -> Result<DirTransferReport, Error> {
+        while !self.futures.is_empty() {
+            self.wait_one().await?;
+        }
+        Ok(self.report)
+    }
+}
+
+fn not_a_dir() -> Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "source of directory transfer is not a directory",
+    )
+    .into()
+}
+
+async fn create_remote_dir(fs: &mut Fs, path: &Path) -> Result<(), Error> {
+    match fs.create_dir(path).await {
+        // Transferring into an existing directory is fine.
+        Err(_) if fs.is_dir(path).await => Ok(()),
+        res => res,
+    }
+}
+
+async fn create_local_dir(path: &Path) -> Result<(), Error> {
+    match fs::create_dir(path).await {
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+            if fs::metadata(path).await?.is_dir() {
+                Ok(())
+            } else {
+                Err(err.into())
+            }
+        }
+        res => Ok(res?),
+    }
+}
+
+/// The local filesystem, walked by [`Sftp::upload_dir`].
+struct LocalTree;
+
+impl Tree for LocalTree {
+    type Metadata = std::fs::Metadata;
+
+    fn symlink_metadata<'a>(
+        &'a mut self,
+        path: &'a Path,
+    ) -> BoxFuture<'a, Result<Self::Metadata, Error>> {
+        Box::pin(async move { Ok(fs::symlink_metadata(path).await?) })
+    }
+
+    fn metadata<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<Self::Metadata, Error>> {
+        Box::pin(async move { Ok(fs::metadata(path).await?) })
+    }
+
+    fn canonicalize<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<PathBuf, Error>> {
+        Box::pin(async move { Ok(fs::canonicalize(path).await?) })
+    }
+
+    fn read_dir<'a>(
+        &'a mut self,
+        path: &'a Path,
+    ) -> BoxFuture<'a, Result<Children<Self::Metadata>, Error>> {
+        Box::pin(async move {
+            let mut read_dir = fs::read_dir(path).await?;
+            let mut entries = Vec::new();
+
+            while let Some(entry) = read_dir.next_entry().await? {
+                // `DirEntry::metadata` does not follow symlinks.
+                entries.push((entry.file_name(), entry.metadata().await?));
+            }
+
+            Ok(entries)
+        })
+    }
+
+    fn is_dir(metadata: &Self::Metadata) -> bool {
+        metadata.is_dir()
+    }
+
+    fn is_symlink(metadata: &Self::Metadata) -> bool {
+        metadata.file_type().is_symlink()
+    }
+}
+
+/// Return the device and inode number of a local file if it has more
+/// than one hard link.
+#[cfg(unix)]
+fn hardlink_key(metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn hardlink_key(_metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(unix)]
+async fn create_local_symlink(target: &Path, path: &Path) -> Result<(), Error> {
+    Ok(fs::symlink(target, path).await?)
+}
+
+#[cfg(not(unix))]
+async fn create_local_symlink(_target: &Path, _path: &Path) -> Result<(), Error> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "recreating symlinks locally is only supported on unix",
+    )
+    .into())
+}
+
+impl Sftp {
+    /// Upload local directory `src` and everything in it to remote
+    /// directory `dst`.
+    ///
+    /// The local tree is walked and the remote directories are created
+    /// first, then regular files are uploaded as if by [`Sftp::upload`],
+    /// up to [`DirTransferOptions::max_parallel`] at the same time.
+    /// Files, directories and symlinks that already exist in `dst` are
+    /// replaced, merged and left as-is respectively. Other file types,
+    /// such as sockets, are ignored.
+    ///
+    /// If [`DirTransferOptions::filter`] is set, excluded paths are skipped
+    /// and excluded directories are not walked into.
+    ///
+    /// If [`DirTransferOptions::preserve_hardlinks`] is enabled, hard links
+    /// are created after all files are uploaded.
+    ///
+    /// The transfer stops at the first error, leaving whatever has
+    /// already been transferred in place.
+    pub async fn upload_dir(
+        &self,
+        src: impl AsRef<Path>,
+        dst: impl AsRef<Path>,
+        options: DirTransferOptions<'_>,
+    ) -> Result<DirTransferReport, Error> {
+        async fn inner(
+            this: &Sftp,
+            src: &Path,
+            dst: &Path,
+            options: DirTransferOptions<'_>,
+        ) -> Result<DirTransferReport, Error> {
+            if !fs::metadata(src).await?.is_dir() {
+                return Err(not_a_dir());
+            }
+
+            let mut fs = this.fs();
+            let mut walker = options.walker(LocalTree, src);
+            let mut files = Vec::new();
+
+            let preserve_hardlinks = options.preserve_hardlinks && this.support_hardlink();
+            // Remote path of the first upload of each local file
+            // with multiple hard links.
+            let mut uploaded: HashMap<(u64, u64), PathBuf> = HashMap::new();
+            let mut hardlinks = Vec::new();
+
+            while let Some(res) = walker.next().await {
+                let entry = res.map_err(walk_error)?;
+                let dst = entry.dst_path(src, dst)?;
+                let TreeEntry { path, metadata, .. } = entry;
+
+                let file_type = metadata.file_type();
+
+                if file_type.is_dir() {
+                    create_remote_dir(&mut fs, &dst).await?;
+                } else if file_type.is_symlink() {
+                    let target = fs::read_link(&path).await?;
+                    if let Err(err) = fs.symlink(&target, &dst).await {
+                        if fs.symlink_metadata(&dst).await.is_err() {
+                            return Err(err);
+                        }
+                    }
+                } else if file_type.is_file() {
+                    if preserve_hardlinks {
+                        if let Some(key) = hardlink_key(&metadata) {
+                            match uploaded.entry(key) {
+                                Entry::Occupied(entry) => {
+                                    hardlinks.push((entry.get().clone(), dst));
+                                    continue;
+                                }
+                                Entry::Vacant(entry) => {
+                                    entry.insert(dst.clone());
+                                }
+                            }
+                        }
+                    }
+
+                    files.push((path, dst));
+                }
+            }
+
+            let mut transfers = Transfers::new(options.max_parallel);
+            for (src, dst) in files {
+                transfers
+                    .push(Box::pin(async move {
+                        this.upload(src, dst, options.file).await
+                    }))
+                    .await?;
+            }
+            let mut report = transfers.finish().await?;
+
+            for (original, dst) in hardlinks {
+                if let Err(err) = fs.hard_link(&original, &dst).await {
+                    // Replace the file left by a previous upload.
+                    if fs.remove_file(&dst).await.is_err() {
+                        return Err(err);
+                    }
+                    fs.hard_link(&original, &dst).await?;
+                }
+                report.hardlinks += 1;
+            }
+
+            Ok(report)
+        }
+
+        inner(self, src.as_ref(), dst.as_ref(), options).await
+    }
+
+    /// Download remote directory `src` and everything in it to local
+    /// directory `dst`.
+    ///
+    /// It works the same as [`Sftp::upload_dir`], except that regular
+    /// files are downloaded as if by [`Sftp::download`].
+    ///
+    /// Recreating symlinks is only supported on unix.
+    pub async fn download_dir(
+        &self,
+        src: impl AsRef<Path>,
+        dst: impl AsRef<Path>,
+        options: DirTransferOptions<'_>,
+    ) -> Result<DirTransferReport, Error> {
+        async fn inner(
+            this: &Sftp,
+            src: &Path,
+            dst: &Path,
+            options: DirTransferOptions<'_>,
+        ) -> Result<DirTransferReport, Error> {
+            let mut fs = this.fs();
+
+            if !fs.is_dir(src).await {
+                return Err(not_a_dir());
+            }
+
+            let mut walker = options.walker(fs.clone(), src);
+            let mut files: Vec<(PathBuf, PathBuf)> = Vec::new();
+
+            while let Some(res) = walker.next().await {
+                let entry = res.map_err(walk_error)?;
+                let dst = entry.dst_path(src, dst)?;
+
+                match entry.metadata.file_type() {
+                    Some(file_type) if file_type.is_dir() => create_local_dir(&dst).await?,
+                    Some(file_type) if file_type.is_symlink() => {
+                        let target = fs.read_link(&entry.path).await?;
+                        if let Err(err) = create_local_symlink(&target, &dst).await {
+                            if fs::symlink_metadata(&dst).await.is_err() {
+                                return Err(err);
+                            }
+                        }
+                    }
+                    Some(file_type) if file_type.is_file() => files.push((entry.path, dst)),
+                    _ => (),
+                }
+            }
+
+            let mut transfers = Transfers::new(options.max_parallel);
+            for (src, dst) in files {
+                transfers
+                    .push(Box::pin(async move {
+                        this.download(src, dst, options.file).await
+                    }))
+                    .await?;
+            }
+            transfers.finish().await
+        }
+
+        inner(self, src.as_ref(), dst.as_ref(), options).await
+    }
+}
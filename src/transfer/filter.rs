@@ -0,0 +1,217 @@
+use std::path::{Component, Path};
+
+/// Ordered include/exclude rules deciding which paths are transferred by
+/// [`Sftp::upload_dir`](crate::Sftp::upload_dir) and
+/// [`Sftp::download_dir`](crate::Sftp::download_dir).
+///
+/// Rules are checked in the order they are added and the first matching
+/// rule decides, like the filter rules of rsync and unlike gitignore where
+/// the last matching pattern wins, so more specific rules have to be added
+/// first. Paths not matched by any rule are included. Excluded directories
+/// are not descended into.
+///
+/// Patterns use the following syntax:
+///
+/// - `*` matches anything except `/`, `?` matches any one character
+///   except `/` and `**` matches any number of path components.
+/// - A pattern ending with `/` only matches directories.
+/// - A pattern containing `/` elsewhere is matched against the path
+///   relative to the root of the transfer, otherwise it is matched
+///   against the file name at any depth.
+///
+/// For example, the following transfers everything except `*.tmp` files
+/// and `.git` directories:
+///
+/// ```
+/// use openssh_sftp_client::transfer::Filter;
+///
+/// let mut filter = Filter::new();
+/// filter.exclude("*.tmp").exclude(".git/");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    include: bool,
+    dir_only: bool,
+    anchored: bool,
+    segments: Vec<String>,
+}
+
+impl Filter {
+    /// Create a [`Filter`] with no rules, which includes everything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn add_rule(&mut self, include: bool, pattern: &str) -> &mut Self {
+        let (pattern, dir_only) = match pattern.strip_suffix('/') {
+            Some(pattern) => (pattern, true),
+            None => (pattern, false),
+        };
+        let anchored = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+
+        self.rules.push(Rule {
+            include,
+            dir_only,
+            anchored,
+            segments: pattern
+                .split('/')
+                .filter(|segment| !segment.is_empty())
+                .map(String::from)
+                .collect(),
+        });
+        self
+    }
+
+    /// Add a rule including paths matching `pattern`.
+    pub fn include(&mut self, pattern: &str) -> &mut Self {
+        self.add_rule(true, pattern)
+    }
+
+    /// Add a rule excluding paths matching `pattern`.
+    pub fn exclude(&mut self, pattern: &str) -> &mut Self {
+        self.add_rule(false, pattern)
+    }
+
+    /// Return `true` if `path`, relative to the root of the transfer,
+    /// is included.
+    pub fn is_included(&self, path: &Path, is_dir: bool) -> bool {
+        let components: Vec<_> = path
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy()),
+                _ => None,
+            })
+            .collect();
+        let components: Vec<&str> = components.iter().map(|c| &**c).collect();
+
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(&components, is_dir))
+            .map_or(true, |rule| rule.include)
+    }
+}
+
+impl Rule {
+    fn matches(&self, components: &[&str], is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+
+        if self.anchored {
+            match_segments(&self.segments, components)
+        } else {
+            match (&*self.segments, components.last()) {
+                ([pattern], Some(name)) => match_glob(pattern, name),
+                _ => false,
+            }
+        }
+    }
+}
+
+fn match_segments(segments: &[String], components: &[&str]) -> bool {
+    match segments.split_first() {
+        None => components.is_empty(),
+        Some((segment, rest)) if segment == "**" => {
+            (0..=components.len()).any(|skip| match_segments(rest, &components[skip..]))
+        }
+        Some((segment, rest)) => match components.split_first() {
+            Some((component, components)) => {
+                match_glob(segment, component) && match_segments(rest, components)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Match `name` against glob `pattern` containing `*` and `?`.
+///
+/// `*` is matched by backtracking to the last `*` seen only, which runs
+/// in `O(pattern.len() * name.len())` time.
+fn match_glob(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in `pattern` and the position in `name`
+    // it is matched up to.
+    let mut star = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // Let the last `*` match one more character.
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_glob() {
+        assert!(match_glob("*.tmp", "a.tmp"));
+        assert!(match_glob("*.tmp", ".tmp"));
+        assert!(!match_glob("*.tmp", "a.tmp.bak"));
+        assert!(match_glob("a?c", "abc"));
+        assert!(!match_glob("a?c", "ac"));
+        assert!(match_glob("a*b*c", "aXbYbZc"));
+        assert!(match_glob("**", ""));
+
+        // `?` matches one character, not one byte.
+        assert!(match_glob("caf?", "café"));
+        assert!(!match_glob("caf??", "café"));
+
+        // Would take exponential time with naive backtracking.
+        let name = "a".repeat(100);
+        assert!(!match_glob(&format!("{}b", "a*".repeat(20)), &name));
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut filter = Filter::new();
+        filter
+            .include("keep.tmp")
+            .exclude("*.tmp")
+            .exclude(".git/")
+            .exclude("/build")
+            .exclude("docs/**/*.html");
+
+        assert!(filter.is_included(Path::new("src/main.rs"), false));
+        assert!(filter.is_included(Path::new("keep.tmp"), false));
+        assert!(!filter.is_included(Path::new("a/b.tmp"), false));
+
+        assert!(!filter.is_included(Path::new("a/.git"), true));
+        assert!(filter.is_included(Path::new(".git"), false));
+
+        assert!(!filter.is_included(Path::new("build"), true));
+        assert!(filter.is_included(Path::new("src/build"), true));
+
+        assert!(!filter.is_included(Path::new("docs/index.html"), false));
+        assert!(!filter.is_included(Path::new("docs/a/b/index.html"), false));
+        assert!(filter.is_included(Path::new("src/docs/index.html"), false));
+    }
+}
@@ -0,0 +1,288 @@
+use crate::{
+    file::{OpenOptions, TokioCompatFile},
+    Error, Sftp,
+};
+
+use std::{
+    future::{self, Future},
+    io,
+    path::Path,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::{
+    fs,
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf},
+};
+
+mod crc32;
+use crc32::Crc32;
+
+mod dir;
+pub use dir::{DirTransferOptions, DirTransferReport};
+
+mod filter;
+pub use filter::Filter;
+
+mod queue;
+pub use queue::{JobEvent, JobEvents, JobHandle, JobId, JobState, TransferQueue};
+
+pub use crate::fs::SymlinkPolicy;
+
+/// Options for [`Sftp::upload`] and [`Sftp::download`].
+#[derive(Debug, Copy, Clone, Default)]
+pub struct TransferOptions {
+    preallocate: bool,
+    crc32: bool,
+}
+
+impl TransferOptions {
+    /// Create a new [`TransferOptions`].
+    pub const fn new() -> Self {
+        Self {
+            preallocate: false,
+            crc32: false,
+        }
+    }
+
+    /// Check that the remote filesystem has enough free space for the
+    /// whole file before uploading any data, default is `false`.
+    ///
+    /// If it does not, [`Sftp::upload`] fails early with an error of kind
+    /// [`io::ErrorKind::Other`] instead of midway through the transfer.
+    /// Otherwise the length of the destination file is set to the final
+    /// length before the transfer.
+    ///
+    /// The free space is queried with `fstatvfs`, the check is skipped if
+    /// the server does not support it, see [`Sftp::support_statvfs`].
+    ///
+    /// It has no effect on [`Sftp::download`].
+    #[must_use]
+    pub const fn preallocate(mut self, preallocate: bool) -> Self {
+        self.preallocate = preallocate;
+        self
+    }
+
+    /// Compute the CRC-32 (IEEE, the one used by gzip and zip) of the data
+    /// as it is transferred, default is `false`.
+    ///
+    /// The checksum is computed on the client side and thus works with
+    /// any server, it is available via [`TransferReport::crc32`].
+    #[must_use]
+    pub const fn crc32(mut self, crc32: bool) -> Self {
+        self.crc32 = crc32;
+        self
+    }
+}
+
+/// Result of [`Sftp::upload`] and [`Sftp::download`].
+#[derive(Debug, Copy, Clone)]
+pub struct TransferReport {
+    len: u64,
+    crc32: Option<u32>,
+}
+
+impl TransferReport {
+    /// Number of bytes transferred.
+    pub const fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Return `true` if no byte is transferred.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// CRC-32 of the data transferred, if [`TransferOptions::crc32`]
+    /// is enabled.
+    pub const fn crc32(&self) -> Option<u32> {
+        self.crc32
+    }
+}
+
+/// [`AsyncRead`] adapter that computes checksums of the data read.
+#[derive(Debug)]
+struct ChecksumReader<R> {
+    inner: R,
+    crc32: Option<Crc32>,
+}
+
+impl<R> ChecksumReader<R> {
+    fn new(inner: R, options: TransferOptions) -> Self {
+        Self {
+            inner,
+            crc32: options.crc32.then(Crc32::new),
+        }
+    }
+
+    fn into_report(self, len: u64) -> TransferReport {
+        TransferReport {
+            len,
+            crc32: self.crc32.map(Crc32::finish),
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for ChecksumReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+
+        let res = Pin::new(&mut this.inner).poll_read(cx, buf);
+
+        if let Some(crc32) = &mut this.crc32 {
+            crc32.update(&buf.filled()[filled..]);
+        }
+
+        res
+    }
+}
+
+/// Size of the buffer used to copy data.
+const COPY_BUFFER_LEN: usize = 32 * 1024;
+
+/// Copy `src` to `dst`, awaiting `pause` before every chunk, so that the
+/// copy can be paused in between.
+async fn copy<R, W, P, F>(src: &mut R, dst: &mut W, mut pause: P) -> io::Result<u64>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+    P: FnMut() -> F,
+    F: Future<Output = ()>,
+{
+    let mut buffer = vec![0; COPY_BUFFER_LEN];
+    let mut n = 0;
+
+    loop {
+        pause().await;
+
+        let len = src.read(&mut buffer).await?;
+        if len == 0 {
+            break;
+        }
+        dst.write_all(&buffer[..len]).await?;
+        n += len as u64;
+    }
+
+    // Make sure all writes are acknowledged by the server.
+    dst.flush().await?;
+
+    Ok(n)
+}
+
+/// Upload local file `src` to remote file `dst` opened with `open_options`,
+/// see [`copy`] for `pause`.
+async fn upload<P, F>(
+    mut open_options: OpenOptions,
+    src: &Path,
+    dst: &Path,
+    options: TransferOptions,
+    pause: P,
+) -> Result<TransferReport, Error>
+where
+    P: FnMut() -> F,
+    F: Future<Output = ()>,
+{
+    let src = fs::File::open(src).await?;
+    let mut dst = open_options
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(dst)
+        .await?;
+
+    if options.preallocate {
+        let len = src.metadata().await?.len();
+
+        match dst.statvfs().await {
+            Ok(statvfs) if statvfs.available_space() < len => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!(
+                        "not enough space on the remote filesystem: need {} bytes, {} available",
+                        len,
+                        statvfs.available_space()
+                    ),
+                )
+                .into());
+            }
+            Ok(_) | Err(Error::UnsupportedExtension(_)) => (),
+            Err(err) => return Err(err),
+        }
+
+        dst.set_len(len).await?;
+    }
+
+    let mut src = ChecksumReader::new(src, options);
+
+    // Dropping `TokioCompatFile` would close the handle
+    // in the background.
+    let dst = TokioCompatFile::new(dst);
+    tokio::pin!(dst);
+
+    let n = copy(&mut src, &mut dst, pause).await?;
+
+    Ok(src.into_report(n))
+}
+
+/// Download remote file `src` opened with `open_options` to local file
+/// `dst`, see [`copy`] for `pause`.
+async fn download<P, F>(
+    mut open_options: OpenOptions,
+    src: &Path,
+    dst: &Path,
+    options: TransferOptions,
+    pause: P,
+) -> Result<TransferReport, Error>
+where
+    P: FnMut() -> F,
+    F: Future<Output = ()>,
+{
+    let src = open_options.read(true).open(src).await?;
+    let mut dst = fs::File::create(dst).await?;
+
+    let src = TokioCompatFile::new(src);
+    tokio::pin!(src);
+    let mut src = ChecksumReader::new(src, options);
+
+    let n = copy(&mut src, &mut dst, pause).await?;
+
+    Ok(src.into_report(n))
+}
+
+impl Sftp {
+    /// Upload local file `src` to remote file `dst`.
+    ///
+    /// `dst` will be created if it does not exist and truncated if it does.
+    pub async fn upload(
+        &self,
+        src: impl AsRef<Path>,
+        dst: impl AsRef<Path>,
+        options: TransferOptions,
+    ) -> Result<TransferReport, Error> {
+        upload(self.options(), src.as_ref(), dst.as_ref(), options, || {
+            future::ready(())
+        })
+        .await
+    }
+
+    /// Download remote file `src` to local file `dst`.
+    ///
+    /// `dst` will be created if it does not exist and truncated if it does.
+    pub async fn download(
+        &self,
+        src: impl AsRef<Path>,
+        dst: impl AsRef<Path>,
+        options: TransferOptions,
+    ) -> Result<TransferReport, Error> {
+        download(self.options(), src.as_ref(), dst.as_ref(), options, || {
+            future::ready(())
+        })
+        .await
+    }
+}
@@ -0,0 +1,453 @@
+use super::{download, upload, TransferOptions, TransferReport};
+
+use crate::{file::OpenOptions, Error, Sftp};
+
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+use tokio::{
+    runtime::Handle,
+    sync::{mpsc, watch},
+};
+use tokio_util::sync::CancellationToken;
+
+/// Identifier of a job in a [`TransferQueue`], unique within the queue.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct JobId(u64);
+
+/// State of a job in a [`TransferQueue`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum JobState {
+    /// Waiting for a free slot to start.
+    Queued,
+    /// Transferring data.
+    Running,
+    /// Paused by [`JobHandle::pause`], either before or while running.
+    Paused,
+    /// Finished successfully.
+    Completed(TransferReport),
+    /// Failed with an error.
+    Failed(Arc<Error>),
+    /// Cancelled by [`JobHandle::cancel`].
+    Cancelled,
+}
+
+impl JobState {
+    /// Return `true` if the job is completed, failed or cancelled.
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobState::Completed(_) | JobState::Failed(_) | JobState::Cancelled
+        )
+    }
+}
+
+/// A change of state of a job, yielded by [`JobEvents`].
+#[derive(Debug, Clone)]
+pub struct JobEvent {
+    id: JobId,
+    state: JobState,
+}
+
+impl JobEvent {
+    /// Id of the job.
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    /// New state of the job.
+    pub fn state(&self) -> &JobState {
+        &self.state
+    }
+
+    /// Return the new state of the job.
+    pub fn into_state(self) -> JobState {
+        self.state
+    }
+}
+
+/// Stream of [`JobEvent`]s returned by [`TransferQueue::subscribe`].
+#[derive(Debug)]
+pub struct JobEvents(mpsc::UnboundedReceiver<JobEvent>);
+
+impl Stream for JobEvents {
+    type Item = JobEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0.poll_recv(cx)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum JobKind {
+    Upload,
+    Download,
+}
+
+#[derive(Debug)]
+struct Job {
+    id: JobId,
+    kind: JobKind,
+    src: PathBuf,
+    dst: PathBuf,
+    options: TransferOptions,
+    priority: AtomicI32,
+    cancel_token: CancellationToken,
+    paused: (watch::Sender<bool>, watch::Receiver<bool>),
+    state: (watch::Sender<JobState>, watch::Receiver<JobState>),
+}
+
+impl Job {
+    fn state(&self) -> JobState {
+        self.state.1.borrow().clone()
+    }
+
+    fn is_paused(&self) -> bool {
+        *self.paused.1.borrow()
+    }
+
+    async fn wait_until_resumed(&self) {
+        let mut paused = self.paused.1.clone();
+        loop {
+            if !*paused.borrow() {
+                break;
+            }
+            if paused.changed().await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct QueueState {
+    next_id: u64,
+    max_running: usize,
+    running: usize,
+    queued: Vec<Arc<Job>>,
+    listeners: Vec<mpsc::UnboundedSender<JobEvent>>,
+}
+
+impl QueueState {
+    /// Set state of `job` and notify the listeners.
+    fn set_state(&mut self, job: &Job, state: JobState) {
+        let event = JobEvent { id: job.id, state };
+
+        self.listeners
+            .retain(|listener| listener.send(event.clone()).is_ok());
+
+        // `job` holds a receiver, so it never fails.
+        job.state.0.send(event.state).ok();
+    }
+
+    /// Remove the queued job with the highest priority that is not paused,
+    /// the earliest one is picked if there is a tie.
+    fn pop_next(&mut self) -> Option<Arc<Job>> {
+        let (index, _) = self
+            .queued
+            .iter()
+            .enumerate()
+            .filter(|(_, job)| !job.is_paused())
+            .max_by(|(i, x), (j, y)| {
+                let x = x.priority.load(Ordering::Relaxed);
+                let y = y.priority.load(Ordering::Relaxed);
+                x.cmp(&y).then(j.cmp(i))
+            })?;
+
+        Some(self.queued.remove(index))
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    open_options: OpenOptions,
+    runtime: Handle,
+    state: Mutex<QueueState>,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        // The lock is only held by code that does not panic.
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Start as many queued jobs as allowed.
+    fn schedule(self: &Arc<Self>) {
+        let mut state = self.lock();
+
+        while state.running < state.max_running {
+            let job = match state.pop_next() {
+                Some(job) => job,
+                None => break,
+            };
+
+            state.running += 1;
+            state.set_state(&job, JobState::Running);
+
+            self.runtime.spawn(run(self.clone(), job));
+        }
+    }
+}
+
+async fn run(shared: Arc<Shared>, job: Arc<Job>) {
+    let transfer = async {
+        let open_options = shared.open_options.clone();
+        let pause = || job.wait_until_resumed();
+
+        match job.kind {
+            JobKind::Upload => upload(open_options, &job.src, &job.dst, job.options, pause).await,
+            JobKind::Download => {
+                download(open_options, &job.src, &job.dst, job.options, pause).await
+            }
+        }
+    };
+
+    let state = tokio::select! {
+        res = transfer => match res {
+            Ok(report) => JobState::Completed(report),
+            Err(err) => JobState::Failed(Arc::new(err)),
+        },
+        _ = job.cancel_token.cancelled() => JobState::Cancelled,
+    };
+
+    {
+        let mut queue_state = shared.lock();
+        queue_state.running -= 1;
+        queue_state.set_state(&job, state);
+    }
+
+    shared.schedule();
+}
+
+/// A queue of uploads and downloads, running up to a limited number
+/// of them at the same time.
+///
+/// Jobs are started in the order of their priority and then the order
+/// they are added in. Each job can be paused, resumed, reprioritized or
+/// cancelled via its [`JobHandle`], and changes of their states can be
+/// observed via [`TransferQueue::subscribe`].
+///
+/// Jobs keep running even if the [`TransferQueue`] is dropped.
+///
+/// It is created by [`Sftp::transfer_queue`].
+#[derive(Debug, Clone)]
+pub struct TransferQueue(Arc<Shared>);
+
+impl Sftp {
+    /// Create a [`TransferQueue`] that runs up to `max_running` jobs
+    /// at the same time.
+    ///
+    /// # Panics
+    ///
+    /// It panics if not called within a tokio runtime, which is used to
+    /// run the jobs.
+    pub fn transfer_queue(&self, max_running: NonZeroUsize) -> TransferQueue {
+        TransferQueue(Arc::new(Shared {
+            open_options: self.options(),
+            runtime: Handle::current(),
+            state: Mutex::new(QueueState {
+                next_id: 0,
+                max_running: max_running.get(),
+                running: 0,
+                queued: Vec::new(),
+                listeners: Vec::new(),
+            }),
+        }))
+    }
+}
+
+impl TransferQueue {
+    fn push(&self, kind: JobKind, src: &Path, dst: &Path, options: TransferOptions) -> JobHandle {
+        let shared = &self.0;
+
+        let job = {
+            let mut state = shared.lock();
+
+            let id = JobId(state.next_id);
+            state.next_id += 1;
+
+            let job = Arc::new(Job {
+                id,
+                kind,
+                src: src.to_path_buf(),
+                dst: dst.to_path_buf(),
+                options,
+                priority: AtomicI32::new(0),
+                cancel_token: CancellationToken::new(),
+                paused: watch::channel(false),
+                state: watch::channel(JobState::Queued),
+            });
+
+            state.queued.push(job.clone());
+            state.set_state(&job, JobState::Queued);
+
+            job
+        };
+
+        shared.schedule();
+
+        JobHandle {
+            shared: shared.clone(),
+            job,
+        }
+    }
+
+    /// Queue uploading local file `src` to remote file `dst`,
+    /// same as [`Sftp::upload`].
+    pub fn upload(
+        &self,
+        src: impl AsRef<Path>,
+        dst: impl AsRef<Path>,
+        options: TransferOptions,
+    ) -> JobHandle {
+        self.push(JobKind::Upload, src.as_ref(), dst.as_ref(), options)
+    }
+
+    /// Queue downloading remote file `src` to local file `dst`,
+    /// same as [`Sftp::download`].
+    pub fn download(
+        &self,
+        src: impl AsRef<Path>,
+        dst: impl AsRef<Path>,
+        options: TransferOptions,
+    ) -> JobHandle {
+        self.push(JobKind::Download, src.as_ref(), dst.as_ref(), options)
+    }
+
+    /// Return a stream of state changes of all jobs added afterwards
+    /// and of existing jobs from now on.
+    pub fn subscribe(&self) -> JobEvents {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.0.lock().listeners.push(sender);
+        JobEvents(receiver)
+    }
+
+    /// Change the maximum number of jobs running at the same time.
+    ///
+    /// Running jobs are not stopped if it is lowered.
+    pub fn set_max_running(&self, max_running: NonZeroUsize) {
+        self.0.lock().max_running = max_running.get();
+        self.0.schedule();
+    }
+
+    /// Number of jobs that are queued, including paused ones that
+    /// have not been started.
+    pub fn queued(&self) -> usize {
+        self.0.lock().queued.len()
+    }
+
+    /// Number of jobs that are running, including paused ones that
+    /// have been started.
+    pub fn running(&self) -> usize {
+        self.0.lock().running
+    }
+}
+
+/// Handle to a job in a [`TransferQueue`].
+///
+/// Dropping it does not affect the job.
+#[derive(Debug, Clone)]
+pub struct JobHandle {
+    shared: Arc<Shared>,
+    job: Arc<Job>,
+}
+
+impl JobHandle {
+    /// Id of the job.
+    pub fn id(&self) -> JobId {
+        self.job.id
+    }
+
+    /// Current state of the job.
+    pub fn state(&self) -> JobState {
+        self.job.state()
+    }
+
+    /// Wait for the job to finish and return its final state.
+    pub async fn wait(&self) -> JobState {
+        let mut state = self.job.state.1.clone();
+        loop {
+            {
+                let state = state.borrow();
+                if state.is_finished() {
+                    break state.clone();
+                }
+            }
+            // `job` holds a sender, so it never fails.
+            state.changed().await.ok();
+        }
+    }
+
+    /// Pause the job.
+    ///
+    /// A queued job will not be started until resumed, while a running job
+    /// stops transferring data but keeps occupying its slot in the queue.
+    pub fn pause(&self) {
+        let mut state = self.shared.lock();
+
+        if matches!(self.job.state(), JobState::Queued | JobState::Running) {
+            self.job.paused.0.send(true).ok();
+            state.set_state(&self.job, JobState::Paused);
+        }
+    }
+
+    /// Resume the job paused by [`JobHandle::pause`].
+    pub fn resume(&self) {
+        {
+            let mut state = self.shared.lock();
+
+            if !matches!(self.job.state(), JobState::Paused) {
+                return;
+            }
+
+            self.job.paused.0.send(false).ok();
+
+            let queued = state.queued.iter().any(|job| job.id == self.job.id);
+            let new_state = if queued {
+                JobState::Queued
+            } else {
+                JobState::Running
+            };
+            state.set_state(&self.job, new_state);
+        }
+
+        self.shared.schedule();
+    }
+
+    /// Cancel the job.
+    ///
+    /// A queued job is removed from the queue, while a running job is
+    /// stopped as soon as possible, leaving the partially transferred
+    /// destination in place. It does nothing if the job is finished.
+    pub fn cancel(&self) {
+        let mut state = self.shared.lock();
+
+        if let Some(index) = state.queued.iter().position(|job| job.id == self.job.id) {
+            state.queued.remove(index);
+            state.set_state(&self.job, JobState::Cancelled);
+        } else {
+            self.job.cancel_token.cancel();
+        }
+    }
+
+    /// Priority of the job, default is `0`.
+    pub fn priority(&self) -> i32 {
+        self.job.priority.load(Ordering::Relaxed)
+    }
+
+    /// Change priority of the job, queued jobs with higher priority are
+    /// started first. It has no effect on jobs that have been started.
+    pub fn set_priority(&self, priority: i32) {
+        self.job.priority.store(priority, Ordering::Relaxed);
+    }
+}
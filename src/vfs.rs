@@ -0,0 +1,333 @@
+use crate::{
+    file::TokioCompatFile,
+    fs::Fs,
+    metadata::{MetaData, Permissions},
+    Error, UnixTimeStamp,
+};
+
+use std::{
+    fs::Metadata,
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+};
+
+use bytes::Bytes;
+use tokio::{
+    fs,
+    io::{AsyncRead, AsyncSeek, AsyncWrite},
+};
+
+/// Boxed future returned by methods of [`AsyncFs`].
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// File opened by [`AsyncFs::open`] or [`AsyncFs::create`].
+pub trait AsyncFile: AsyncRead + AsyncWrite + AsyncSeek + Send {}
+
+impl<T: AsyncRead + AsyncWrite + AsyncSeek + Send + ?Sized> AsyncFile for T {}
+
+/// Boxed file returned by [`AsyncFs::open`] and [`AsyncFs::create`].
+pub type BoxFile = Pin<Box<dyn AsyncFile>>;
+
+/// Type of an entry in the filesystem.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EntryType {
+    /// Regular file.
+    File,
+    /// Directory.
+    Dir,
+    /// Symbolic link.
+    Symlink,
+    /// Anything else, e.g. sockets, fifos and devices.
+    Other,
+}
+
+impl EntryType {
+    fn new(is_file: bool, is_dir: bool, is_symlink: bool) -> Self {
+        if is_file {
+            EntryType::File
+        } else if is_dir {
+            EntryType::Dir
+        } else if is_symlink {
+            EntryType::Symlink
+        } else {
+            EntryType::Other
+        }
+    }
+}
+
+/// Metadata of an entry that every [`AsyncFs`] backend can provide.
+///
+/// Each field is `None` if the backend did not provide it.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Stat {
+    entry_type: Option<EntryType>,
+    len: Option<u64>,
+    permissions: Option<Permissions>,
+    modified: Option<UnixTimeStamp>,
+}
+
+#[allow(clippy::len_without_is_empty)]
+impl Stat {
+    /// Type of the entry.
+    pub fn entry_type(&self) -> Option<EntryType> {
+        self.entry_type
+    }
+
+    /// Return `true` if the entry is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.entry_type == Some(EntryType::Dir)
+    }
+
+    /// Return `true` if the entry is a regular file.
+    pub fn is_file(&self) -> bool {
+        self.entry_type == Some(EntryType::File)
+    }
+
+    /// Size of the entry in bytes.
+    pub fn len(&self) -> Option<u64> {
+        self.len
+    }
+
+    /// Permissions of the entry.
+    pub fn permissions(&self) -> Option<Permissions> {
+        self.permissions
+    }
+
+    /// Last modification time of the entry.
+    pub fn modified(&self) -> Option<UnixTimeStamp> {
+        self.modified
+    }
+}
+
+impl From<MetaData> for Stat {
+    fn from(metadata: MetaData) -> Self {
+        Self {
+            entry_type: metadata.file_type().map(|file_type| {
+                EntryType::new(
+                    file_type.is_file(),
+                    file_type.is_dir(),
+                    file_type.is_symlink(),
+                )
+            }),
+            len: metadata.len(),
+            permissions: metadata.permissions(),
+            modified: metadata.modified(),
+        }
+    }
+}
+
+impl From<Metadata> for Stat {
+    fn from(metadata: Metadata) -> Self {
+        let file_type = metadata.file_type();
+
+        #[cfg(unix)]
+        let permissions = {
+            use std::os::unix::fs::PermissionsExt;
+
+            Some(Permissions::from(
+                (metadata.permissions().mode() & 0o7777) as u16,
+            ))
+        };
+        #[cfg(not(unix))]
+        let permissions = None;
+
+        Self {
+            entry_type: Some(EntryType::new(
+                file_type.is_file(),
+                file_type.is_dir(),
+                file_type.is_symlink(),
+            )),
+            len: Some(metadata.len()),
+            permissions,
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|modified| UnixTimeStamp::new(modified).ok()),
+        }
+    }
+}
+
+/// Common filesystem operations, implemented by [`Fs`] for the remote
+/// and by [`LocalFs`] for the local filesystem, so that code can be
+/// written once for both and tested against either.
+///
+/// Methods return [`BoxFuture`] so that the trait can be used as
+/// a trait object, e.g. `Box<dyn AsyncFs>`.
+pub trait AsyncFs: Send {
+    /// Queries metadata of `path`, following symlinks.
+    fn metadata<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<Stat, Error>>;
+
+    /// Queries metadata of `path`, without following symlinks.
+    fn symlink_metadata<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<Stat, Error>>;
+
+    /// Opens a file in read-only mode.
+    fn open<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<BoxFile, Error>>;
+
+    /// Opens a file in write-only mode, creating it if it does not exist
+    /// and truncating it if it does.
+    fn create<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<BoxFile, Error>>;
+
+    /// Return names and metadata of entries in directory `path`,
+    /// excluding `.` and `..`.
+    fn read_dir<'a>(
+        &'a mut self,
+        path: &'a Path,
+    ) -> BoxFuture<'a, Result<Vec<(PathBuf, Stat)>, Error>>;
+
+    /// Creates a new, empty directory.
+    fn create_dir<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<(), Error>>;
+
+    /// Removes an existing, empty directory.
+    fn remove_dir<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<(), Error>>;
+
+    /// Removes a file.
+    fn remove_file<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<(), Error>>;
+
+    /// Renames `from` to `to`.
+    fn rename<'a>(&'a mut self, from: &'a Path, to: &'a Path) -> BoxFuture<'a, Result<(), Error>>;
+
+    /// Reads the entire content of a file.
+    fn read<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<Bytes, Error>>;
+
+    /// Writes `content` to a file, creating it if it does not exist
+    /// and truncating it if it does.
+    fn write<'a>(
+        &'a mut self,
+        path: &'a Path,
+        content: &'a [u8],
+    ) -> BoxFuture<'a, Result<(), Error>>;
+}
+
+impl AsyncFs for Fs {
+    fn metadata<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<Stat, Error>> {
+        Box::pin(async move { Fs::metadata(self, path).await.map(Stat::from) })
+    }
+
+    fn symlink_metadata<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<Stat, Error>> {
+        Box::pin(async move { Fs::symlink_metadata(self, path).await.map(Stat::from) })
+    }
+
+    fn open<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<BoxFile, Error>> {
+        Box::pin(async move {
+            let file = self.open_file(path, false).await?;
+            Ok(Box::pin(TokioCompatFile::new(file)) as BoxFile)
+        })
+    }
+
+    fn create<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<BoxFile, Error>> {
+        Box::pin(async move {
+            let file = self.open_file(path, true).await?;
+            Ok(Box::pin(TokioCompatFile::new(file)) as BoxFile)
+        })
+    }
+
+    fn read_dir<'a>(
+        &'a mut self,
+        path: &'a Path,
+    ) -> BoxFuture<'a, Result<Vec<(PathBuf, Stat)>, Error>> {
+        Box::pin(async move {
+            Ok(self
+                .read_dir_entries(path)
+                .await?
+                .into_iter()
+                .map(|entry| (entry.filename().to_path_buf(), entry.metadata().into()))
+                .collect())
+        })
+    }
+
+    fn create_dir<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(Fs::create_dir(self, path))
+    }
+
+    fn remove_dir<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(Fs::remove_dir(self, path))
+    }
+
+    fn remove_file<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(Fs::remove_file(self, path))
+    }
+
+    fn rename<'a>(&'a mut self, from: &'a Path, to: &'a Path) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(Fs::rename(self, from, to))
+    }
+
+    fn read<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<Bytes, Error>> {
+        Box::pin(async move { Fs::read(self, path).await.map(|content| content.freeze()) })
+    }
+
+    fn write<'a>(
+        &'a mut self,
+        path: &'a Path,
+        content: &'a [u8],
+    ) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(Fs::write(self, path, content))
+    }
+}
+
+/// [`AsyncFs`] backed by the local filesystem via [`tokio::fs`].
+#[derive(Debug, Copy, Clone, Default)]
+pub struct LocalFs;
+
+impl AsyncFs for LocalFs {
+    fn metadata<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<Stat, Error>> {
+        Box::pin(async move { Ok(fs::metadata(path).await?.into()) })
+    }
+
+    fn symlink_metadata<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<Stat, Error>> {
+        Box::pin(async move { Ok(fs::symlink_metadata(path).await?.into()) })
+    }
+
+    fn open<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<BoxFile, Error>> {
+        Box::pin(async move { Ok(Box::pin(fs::File::open(path).await?) as BoxFile) })
+    }
+
+    fn create<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<BoxFile, Error>> {
+        Box::pin(async move { Ok(Box::pin(fs::File::create(path).await?) as BoxFile) })
+    }
+
+    fn read_dir<'a>(
+        &'a mut self,
+        path: &'a Path,
+    ) -> BoxFuture<'a, Result<Vec<(PathBuf, Stat)>, Error>> {
+        Box::pin(async move {
+            let mut read_dir = fs::read_dir(path).await?;
+
+            let mut entries = Vec::new();
+            while let Some(entry) = read_dir.next_entry().await? {
+                let metadata = fs::symlink_metadata(entry.path()).await?;
+                entries.push((entry.file_name().into(), metadata.into()));
+            }
+
+            Ok(entries)
+        })
+    }
+
+    fn create_dir<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move { Ok(fs::create_dir(path).await?) })
+    }
+
+    fn remove_dir<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move { Ok(fs::remove_dir(path).await?) })
+    }
+
+    fn remove_file<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move { Ok(fs::remove_file(path).await?) })
+    }
+
+    fn rename<'a>(&'a mut self, from: &'a Path, to: &'a Path) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move { Ok(fs::rename(from, to).await?) })
+    }
+
+    fn read<'a>(&'a mut self, path: &'a Path) -> BoxFuture<'a, Result<Bytes, Error>> {
+        Box::pin(async move { Ok(fs::read(path).await?.into()) })
+    }
+
+    fn write<'a>(
+        &'a mut self,
+        path: &'a Path,
+        content: &'a [u8],
+    ) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move { Ok(fs::write(path, content).await?) })
+    }
+}
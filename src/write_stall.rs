@@ -0,0 +1,158 @@
+use crate::Error;
+
+use std::{
+    error, fmt,
+    future::Future,
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use pin_project::pin_project;
+use tokio::{
+    io::AsyncWrite,
+    time::{sleep, Sleep},
+};
+
+/// Payload of the [`io::Error`] returned by [`StallDetector`].
+#[derive(Debug)]
+struct WriteStalled(Duration);
+
+impl fmt::Display for WriteStalled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Writing made no progress in {:?}", self.0)
+    }
+}
+
+impl error::Error for WriteStalled {}
+
+/// Convert the [`io::Error`] returned by [`StallDetector`] into
+/// [`Error::WriteStalled`].
+pub(super) fn map_io_error(err: io::Error) -> Error {
+    match err.get_ref().and_then(|err| err.downcast_ref()) {
+        Some(WriteStalled(deadline)) => Error::WriteStalled {
+            deadline: *deadline,
+        },
+        None => err.into(),
+    }
+}
+
+/// Wrapper of writer that fails if no progress is made within `deadline`.
+///
+/// The timer starts when the writer first returns [`Poll::Pending`] and
+/// is reset whenever any data is written.
+#[derive(Debug)]
+#[pin_project]
+pub(super) struct StallDetector<W> {
+    #[pin]
+    writer: W,
+    deadline: Option<Duration>,
+    /// Armed when `writer` returns [`Poll::Pending`].
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl<W> StallDetector<W> {
+    pub(super) fn new(writer: W, deadline: Option<Duration>) -> Self {
+        Self {
+            writer,
+            deadline,
+            sleep: None,
+        }
+    }
+}
+
+impl<W: AsyncWrite> StallDetector<W> {
+    fn poll_with_deadline<T>(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        f: impl FnOnce(Pin<&mut W>, &mut Context<'_>) -> Poll<io::Result<T>>,
+    ) -> Poll<io::Result<T>> {
+        let this = self.project();
+
+        let deadline = match this.deadline {
+            Some(deadline) => *deadline,
+            None => return f(this.writer, cx),
+        };
+
+        match f(this.writer, cx) {
+            Poll::Ready(res) => {
+                *this.sleep = None;
+                Poll::Ready(res)
+            }
+            Poll::Pending => {
+                let sleep = this.sleep.get_or_insert_with(|| Box::pin(sleep(deadline)));
+
+                ready!(sleep.as_mut().poll(cx));
+
+                Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    WriteStalled(deadline),
+                )))
+            }
+        }
+    }
+}
+
+impl<W: AsyncWrite> AsyncWrite for StallDetector<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_with_deadline(cx, |writer, cx| writer.poll_write(cx, buf))
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_with_deadline(cx, |writer, cx| writer.poll_write_vectored(cx, bufs))
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.writer.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_with_deadline(cx, AsyncWrite::poll_flush)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_with_deadline(cx, AsyncWrite::poll_shutdown)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{map_io_error, Error, StallDetector};
+
+    use std::time::Duration;
+
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_stall_detector() {
+        let deadline = Duration::from_millis(50);
+
+        let (writer, mut reader) = duplex(4);
+        let mut writer = StallDetector::new(writer, Some(deadline));
+
+        // Writing succeeds as long as the reader makes progress.
+        let (res, buffer) = tokio::join!(writer.write_all(b"hello, world!"), async {
+            let mut buffer = [0; 13];
+            reader.read_exact(&mut buffer).await.unwrap();
+            buffer
+        });
+        res.unwrap();
+        assert_eq!(&buffer, b"hello, world!");
+
+        // Nobody reads from the pipe, so it is stalled once it is full.
+        let err = writer.write_all(b"hello, world!").await.unwrap_err();
+        assert!(matches!(
+            map_io_error(err),
+            Error::WriteStalled { deadline: d } if d == deadline
+        ));
+    }
+}
@@ -5,7 +5,7 @@ use std::{
     env, fs,
     future::ready,
     io::IoSlice,
-    num::{NonZeroU32, NonZeroU64, NonZeroUsize},
+    num::{NonZeroU16, NonZeroU32, NonZeroU64, NonZeroUsize},
     path::Path,
     path::PathBuf,
     stringify,
@@ -50,6 +50,52 @@ async fn sftp_init() {
     assert!(child.wait().await.unwrap().success());
 }
 
+#[tokio::test]
+/// Test spawning internal tasks on a user supplied runtime.
+async fn sftp_init_with_tokio_handle() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let (mut child, stdin, stdout) = launch_sftp().await;
+    let sftp = Sftp::new_with_handle(
+        stdin,
+        stdout,
+        SftpOptions::new(),
+        SftpAuxiliaryData::None,
+        runtime.handle().clone(),
+    )
+    .await
+    .unwrap();
+
+    sftp.fs().metadata(".").await.unwrap();
+
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+
+    runtime.shutdown_background();
+}
+
+#[cfg(feature = "futures-io")]
+#[tokio::test]
+/// Test Sftp::new_futures_io
+async fn sftp_init_futures_io() {
+    use tokio_util::compat::{TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
+
+    let (mut child, stdin, stdout) = launch_sftp().await;
+
+    let sftp = Sftp::new_futures_io(stdin.compat_write(), stdout.compat(), Default::default())
+        .await
+        .unwrap();
+
+    sftp.fs().metadata(".").await.unwrap();
+
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
 #[tokio::test]
 /// Test creating new file, truncating and opening existing file,
 /// basic read, write and removal.
@@ -80,7 +126,7 @@ async fn sftp_file_basics() {
             content.len()
         );
 
-        debug_assert_eq!(&*fs.read(&path).await.unwrap(), &*content);
+        debug_assert_eq!(&*fs.read(&path).await.unwrap(), content);
 
         // Create new file with Trunc and write to it.
         //
@@ -95,7 +141,7 @@ async fn sftp_file_basics() {
             content.len()
         );
 
-        debug_assert_eq!(&*fs.read(&path).await.unwrap(), &*content);
+        debug_assert_eq!(&*fs.read(&path).await.unwrap(), content);
 
         // remove the file
         fs.remove_file(path).await.unwrap();
@@ -272,7 +318,7 @@ async fn sftp_tokio_compact_file_basics() {
 
         file.flush().await.unwrap();
 
-        debug_assert_eq!(&*read_entire_file().await, &*content);
+        debug_assert_eq!(&*read_entire_file().await, content);
 
         // Create new file with Trunc and write to it.
         //
@@ -290,7 +336,7 @@ async fn sftp_tokio_compact_file_basics() {
         // since it is executed in async context.
         file.flush().await.unwrap();
 
-        debug_assert_eq!(&*read_entire_file().await, &*content);
+        debug_assert_eq!(&*read_entire_file().await, content);
 
         // remove the file
         fs.remove_file(&path).await.unwrap();
@@ -541,6 +587,188 @@ async fn sftp_fs_metadata() {
     assert!(child.wait().await.unwrap().success());
 }
 
+#[tokio::test]
+/// Test Fs::read with SftpOptions::read_cache enabled.
+async fn sftp_fs_read_cache() {
+    let path = gen_path("sftp_fs_read_cache");
+
+    let (mut child, sftp) = connect(SftpOptions::new().read_cache(
+        NonZeroUsize::new(100).unwrap(),
+        NonZeroUsize::new(1000).unwrap(),
+        Duration::from_secs(3600),
+    ))
+    .await;
+
+    {
+        let mut fs = sftp.fs();
+
+        fs.write(&path, b"hello").await.unwrap();
+        assert_eq!(&*fs.read(&path).await.unwrap(), b"hello");
+
+        // Modify the file without going through sftp, the cached
+        // content is returned.
+        std::fs::write(&path, b"world").unwrap();
+        assert_eq!(&*fs.read(&path).await.unwrap(), b"hello");
+
+        sftp.clear_read_cache();
+        assert_eq!(&*fs.read(&path).await.unwrap(), b"world");
+
+        // Writing through sftp invalidates the cache.
+        fs.write(&path, b"hello, world").await.unwrap();
+        assert_eq!(&*fs.read(&path).await.unwrap(), b"hello, world");
+
+        // Writing through a file that is already open invalidates the cache.
+        let mut file = sftp.options().write(true).open(&path).await.unwrap();
+        assert_eq!(&*fs.read(&path).await.unwrap(), b"hello, world");
+        file.write(b"HELLO").await.unwrap();
+        assert_eq!(&*fs.read(&path).await.unwrap(), b"HELLO, world");
+        file.set_len(5).await.unwrap();
+        assert_eq!(&*fs.read(&path).await.unwrap(), b"HELLO");
+        file.close().await.unwrap();
+        assert_eq!(&*fs.read(&path).await.unwrap(), b"HELLO");
+
+        // Paths that refer to the same file share the cached entry.
+        let dotted_path = path
+            .parent()
+            .unwrap()
+            .join(".")
+            .join(path.file_name().unwrap());
+        std::fs::write(&path, b"world").unwrap();
+        assert_eq!(&*fs.read(&dotted_path).await.unwrap(), b"HELLO");
+        fs.write(&dotted_path, b"hello").await.unwrap();
+        assert_eq!(&*fs.read(&path).await.unwrap(), b"hello");
+
+        fs.remove_file(&path).await.unwrap();
+        fs.read(&path).await.unwrap_err();
+
+        // Renaming a directory invalidates the files under it.
+        let dir = gen_path("sftp_fs_read_cache_dir");
+        let renamed_dir = gen_path("sftp_fs_read_cache_renamed_dir");
+        fs.create_dir(&dir).await.unwrap();
+        fs.write(dir.join("file"), b"hello").await.unwrap();
+        assert_eq!(&*fs.read(dir.join("file")).await.unwrap(), b"hello");
+        fs.rename(&dir, &renamed_dir).await.unwrap();
+        fs.read(dir.join("file")).await.unwrap_err();
+
+        fs.remove_file(renamed_dir.join("file")).await.unwrap();
+        fs.remove_dir(&renamed_dir).await.unwrap();
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test configuring and awaiting OpenBuilder directly
+async fn sftp_open_builder() {
+    let path = gen_path("sftp_open_builder");
+    let content = b"hello, world!\n";
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    {
+        let mut file = sftp.create(&path).read(true).await.unwrap();
+        file.write_all(content).await.unwrap();
+        file.rewind().await.unwrap();
+        assert_eq!(
+            &*file.read_all(content.len(), BytesMut::new()).await.unwrap(),
+            content
+        );
+
+        // Open the existing file for appending
+        let mut file = sftp.open(&path).read(false).append(true).await.unwrap();
+        file.write_all(content).await.unwrap();
+    }
+
+    assert_eq!(fs::read(&path).unwrap(), content.repeat(2));
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test SftpReadOnly, ReadOnlyFs and ReadOnlyFile
+async fn sftp_read_only() {
+    let path = gen_path("sftp_read_only");
+    let content = b"hello, world!\n";
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    sftp.fs().write(&path, content).await.unwrap();
+
+    {
+        let sftp = sftp.read_only();
+
+        let mut fs = sftp.fs();
+        assert_eq!(&*fs.read(&path).await.unwrap(), content);
+        assert_eq!(fs.metadata(&path).await.unwrap().len().unwrap(), 14);
+
+        let mut file = sftp.open(&path).await.unwrap();
+        file.seek(std::io::SeekFrom::Start(7)).await.unwrap();
+        assert_eq!(
+            &*file.read_all(7, BytesMut::new()).await.unwrap(),
+            &content[7..]
+        );
+        assert_eq!(file.offset(), 14);
+        file.close().await.unwrap();
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Sftp::upload and Sftp::download with preallocation
+async fn sftp_upload_download() {
+    let path = gen_path("sftp_upload_download");
+    let content = b"hello, world!\n".repeat(1000);
+
+    let (mut child, sftp) = connect(sftp_options_with_max_rw_len()).await;
+
+    fs::create_dir_all(&path).unwrap();
+
+    let local = path.join("local");
+    let remote = path.join("remote");
+    let downloaded = path.join("downloaded");
+
+    fs::write(&local, &content).unwrap();
+
+    let options = transfer::TransferOptions::new().preallocate(true);
+
+    let report = sftp.upload(&local, &remote, options).await.unwrap();
+    assert_eq!(report.len(), content.len() as u64);
+    assert_eq!(report.crc32(), None);
+    assert_eq!(fs::read(&remote).unwrap(), content);
+
+    let report = sftp.download(&remote, &downloaded, options).await.unwrap();
+    assert_eq!(report.len(), content.len() as u64);
+    assert_eq!(fs::read(&downloaded).unwrap(), content);
+
+    // crc32 of the data is computed on both up and download.
+    let options = options.crc32(true);
+
+    let uploaded_crc32 = sftp
+        .upload(&local, &remote, options)
+        .await
+        .unwrap()
+        .crc32()
+        .unwrap();
+    let downloaded_crc32 = sftp
+        .download(&remote, &downloaded, options)
+        .await
+        .unwrap()
+        .crc32()
+        .unwrap();
+    assert_eq!(uploaded_crc32, downloaded_crc32);
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
 #[tokio::test]
 /// Test File::copy_to
 async fn sftp_file_copy_to() {
@@ -750,3 +978,1911 @@ async fn sftp_tokio_compact_file_write_buffer_limit() {
     assert!(child.wait().await.unwrap().success());
     assert!(child2.wait().await.unwrap().success());
 }
+
+#[tokio::test]
+/// Test Sftp::manual_flush and Sftp::drain
+async fn sftp_flush_drain() {
+    let path = gen_path("sftp_flush_drain");
+    let content = b"hello, world!\n";
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    // Nothing has been sent yet apart from the hello message.
+    sftp.drain().await.unwrap();
+
+    {
+        let mut file = sftp.create(&path).await.unwrap();
+        file.write_all(content).await.unwrap();
+        sftp.manual_flush();
+    }
+
+    sftp.drain().await.unwrap();
+    assert_eq!(fs::read(&path).unwrap(), content);
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Sftp::handle_pool
+async fn sftp_handle_pool() {
+    let path = gen_path("sftp_handle_pool");
+    let content = b"hello, world!\n";
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::create_dir_all(&path).unwrap();
+
+    let a = path.join("a");
+    let b = path.join("b");
+
+    fs::write(&a, content).unwrap();
+    fs::write(&b, content).unwrap();
+
+    {
+        let pool = sftp.handle_pool(NonZeroUsize::new(1).unwrap());
+        assert_eq!(pool.capacity(), 1);
+        assert!(pool.is_empty());
+
+        for _ in 0..2 {
+            let mut file = pool.open(&a).await.unwrap();
+            assert_eq!(
+                &*file.read_all(content.len(), BytesMut::new()).await.unwrap(),
+                content
+            );
+            assert_eq!(pool.len(), 1);
+        }
+
+        // Opening another path evicts the least recently used handle.
+        let mut file = pool.open(&b).await.unwrap();
+        assert_eq!(
+            &*file.read_all(content.len(), BytesMut::new()).await.unwrap(),
+            content
+        );
+        assert_eq!(pool.len(), 1);
+
+        pool.invalidate(&b);
+        assert!(pool.is_empty());
+
+        pool.open(&a).await.unwrap();
+        pool.clear();
+        assert!(pool.is_empty());
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Fs::exists and Fs::try_exists
+async fn sftp_fs_exists() {
+    let path = gen_path("sftp_fs_exists");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::create_dir_all(&path).unwrap();
+
+    let file = path.join("file");
+    let symlink = path.join("symlink");
+    let missing = path.join("missing");
+
+    fs::write(&file, b"hello, world!\n").unwrap();
+
+    {
+        let mut fs = sftp.fs();
+
+        assert!(fs.try_exists(&file).await.unwrap());
+        assert!(fs.exists(&path).await);
+
+        assert!(!fs.try_exists(&missing).await.unwrap());
+        assert!(!fs.exists(&missing).await);
+
+        // A broken symlink still exists.
+        fs.symlink(&missing, &symlink).await.unwrap();
+        assert!(fs.try_exists(&symlink).await.unwrap());
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Fs::content_type
+async fn sftp_fs_content_type() {
+    let path = gen_path("sftp_fs_content_type");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::create_dir_all(&path).unwrap();
+
+    let png = path.join("image.txt");
+    let css = path.join("style.css");
+
+    fs::write(&png, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+    fs::write(&css, b"body { color: red; }").unwrap();
+
+    {
+        let mut fs = sftp.fs();
+
+        assert_eq!(fs.content_type(&png).await.unwrap(), "image/png");
+        assert_eq!(
+            fs.content_type(&css).await.unwrap(),
+            "text/css; charset=utf-8"
+        );
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test File::read_range and Fs::read_range
+async fn sftp_read_range() {
+    let path = gen_path("sftp_read_range");
+    let content = b"hello, world!\n".repeat(100);
+
+    let (mut child, sftp) = connect(sftp_options_with_max_rw_len()).await;
+
+    fs::write(&path, &content).unwrap();
+
+    {
+        let file = sftp.open(&path).await.unwrap();
+
+        let buffer = file.read_range(7, 1000, BytesMut::new()).await.unwrap();
+        assert_eq!(&*buffer, &content[7..1007]);
+        assert_eq!(file.offset(), 0);
+
+        // The range is clamped to EOF.
+        let buffer = file.read_range(1390, 100, BytesMut::new()).await.unwrap();
+        assert_eq!(&*buffer, &content[1390..]);
+
+        assert!(matches!(
+            file.read_range(1400, 1, BytesMut::new()).await,
+            Err(Error::RangeOutOfBounds {
+                offset: 1400,
+                len: 1
+            })
+        ));
+
+        let mut fs = sftp.fs();
+        assert_eq!(&*fs.read_range(&path, 0, 5).await.unwrap(), &content[..5]);
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test File::read_ranges
+async fn sftp_file_read_ranges() {
+    let path = gen_path("sftp_file_read_ranges");
+    let content: Vec<u8> = (0..=u8::MAX).cycle().take(10000).collect();
+
+    let (mut child, sftp) = connect(sftp_options_with_max_rw_len()).await;
+
+    fs::write(&path, &content).unwrap();
+
+    {
+        let file = sftp.open(&path).await.unwrap();
+
+        let ranges = [
+            // Out of order, adjacent and overlapping.
+            100..200,
+            0..50,
+            50..100,
+            150..300,
+            // Empty.
+            10..10,
+            // Larger than max_read_len.
+            1000..9000,
+            // Beyond EOF.
+            9990..10010,
+            20000..20010,
+        ];
+
+        let buffers = file.read_ranges(&ranges).await.unwrap();
+        assert_eq!(buffers.len(), ranges.len());
+
+        for (range, buffer) in ranges.iter().zip(buffers) {
+            let start = (range.start as usize).min(content.len());
+            let end = (range.end as usize).min(content.len());
+            assert_eq!(&*buffer, &content[start..end], "range = {range:?}");
+        }
+
+        // The offset is not changed.
+        assert_eq!(file.offset(), 0);
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test OpenOptions::open_with_metadata
+async fn sftp_open_with_metadata() {
+    let dir = gen_path("sftp_open_with_metadata");
+    let path = dir.join("file");
+    let content = b"hello, world!\n";
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(&path, content).unwrap();
+
+    let (mut file, metadata) = sftp.open(&path).with_metadata().await.unwrap();
+    assert_eq!(metadata.len().unwrap(), content.len() as u64);
+    assert_eq!(
+        &*file.read_all(content.len(), BytesMut::new()).await.unwrap(),
+        content
+    );
+    file.close().await.unwrap();
+
+    // The metadata is of the file just created.
+    let new_path = dir.join("new");
+    let (file, metadata) = sftp
+        .options()
+        .write(true)
+        .create_new(true)
+        .open_with_metadata(&new_path)
+        .await
+        .unwrap();
+    assert_eq!(metadata.len().unwrap(), 0);
+    file.close().await.unwrap();
+
+    assert!(sftp
+        .open(path.with_extension("missing"))
+        .with_metadata()
+        .await
+        .is_err());
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Fs::read_small
+async fn sftp_fs_read_small() {
+    let path = gen_path("sftp_fs_read_small");
+    let content = b"hello, world!\n".repeat(100);
+
+    let (mut child, sftp) = connect(sftp_options_with_max_rw_len()).await;
+
+    fs::write(&path, &content).unwrap();
+
+    {
+        let mut fs = sftp.fs();
+
+        assert_eq!(
+            &*fs.read_small(&path, content.len()).await.unwrap(),
+            &*content
+        );
+        assert!(matches!(
+            fs.read_small(&path, content.len() - 1).await,
+            Err(Error::FileTooLarge { max_len }) if max_len == content.len() as u64 - 1
+        ));
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Fs::create_dir_all and Fs::move_to
+async fn sftp_fs_move_to() {
+    let path = gen_path("sftp_fs_move_to");
+    let content = b"hello, world!\n";
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::create_dir_all(&path).unwrap();
+
+    let src = path.join("src");
+    let dst = path.join("a").join("b").join("dst");
+
+    fs::write(&src, content).unwrap();
+
+    {
+        let mut fs = sftp.fs();
+
+        fs.create_dir_all(path.join("c").join("d")).await.unwrap();
+        assert!(path.join("c").join("d").is_dir());
+        // Creating existing directories is not an error.
+        fs.create_dir_all(path.join("c")).await.unwrap();
+
+        fs.move_to(&src, &dst).await.unwrap();
+        assert!(!src.exists());
+        assert_eq!(fs::read(&dst).unwrap(), content);
+
+        // Relative paths are resolved against cwd exactly once.
+        fs.set_cwd(&path);
+        fs.move_to("a/b/dst", "e/dst").await.unwrap();
+        assert!(!dst.exists());
+        assert_eq!(fs::read(path.join("e").join("dst")).unwrap(), content);
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Fs::rename_with
+async fn sftp_fs_rename_with() {
+    let path = gen_path("sftp_fs_rename_with");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::create_dir_all(&path).unwrap();
+
+    let a = path.join("a");
+    let b = path.join("b");
+
+    {
+        let mut fs = sftp.fs();
+
+        fs::write(&a, b"a0").unwrap();
+        fs::write(&b, b"b").unwrap();
+
+        let err = fs
+            .rename_with(&a, &b, openssh_sftp_client::fs::OverwritePolicy::Fail)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::IOError(err) if err.kind() == std::io::ErrorKind::AlreadyExists)
+        );
+        assert_eq!(fs::read(&b).unwrap(), b"b");
+
+        fs.rename_with(
+            &a,
+            &b,
+            openssh_sftp_client::fs::OverwritePolicy::Backup(".bak"),
+        )
+        .await
+        .unwrap();
+        assert!(!a.exists());
+        assert_eq!(fs::read(&b).unwrap(), b"a0");
+        assert_eq!(fs::read(path.join("b.bak")).unwrap(), b"b");
+
+        fs::write(&a, b"a1").unwrap();
+        fs.rename_with(&a, &b, openssh_sftp_client::fs::OverwritePolicy::Overwrite)
+            .await
+            .unwrap();
+        assert!(!a.exists());
+        assert_eq!(fs::read(&b).unwrap(), b"a1");
+
+        // `to` is kept if `from` does not exist.
+        fs.rename_with(&a, &b, openssh_sftp_client::fs::OverwritePolicy::Overwrite)
+            .await
+            .unwrap_err();
+        assert_eq!(fs::read(&b).unwrap(), b"a1");
+        fs.rename_with(
+            &a,
+            &b,
+            openssh_sftp_client::fs::OverwritePolicy::Backup(".bak"),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(fs::read(&b).unwrap(), b"a1");
+
+        // An empty suffix would back up `to` onto itself.
+        fs::write(&a, b"a2").unwrap();
+        fs.rename_with(&a, &b, openssh_sftp_client::fs::OverwritePolicy::Backup(""))
+            .await
+            .unwrap_err();
+        assert_eq!(fs::read(&a).unwrap(), b"a2");
+        assert_eq!(fs::read(&b).unwrap(), b"a1");
+
+        fs.rename_with(
+            &a,
+            path.join("c"),
+            openssh_sftp_client::fs::OverwritePolicy::Fail,
+        )
+        .await
+        .unwrap();
+        assert_eq!(fs::read(path.join("c")).unwrap(), b"a2");
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Fs::set_permissions_recursive
+async fn sftp_fs_set_permissions_recursive() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = gen_path("sftp_fs_set_permissions_recursive");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::create_dir_all(path.join("sub")).unwrap();
+    fs::write(path.join("a"), b"a").unwrap();
+    fs::write(path.join("sub").join("b"), b"b").unwrap();
+    std::os::unix::fs::symlink(path.join("a"), path.join("link")).unwrap();
+
+    {
+        let mut fs = sftp.fs();
+
+        let mut perm = metadata::Permissions::new();
+        perm.set_read_by_owner(true)
+            .set_write_by_owner(true)
+            .set_execute_by_owner(true);
+
+        let report = fs
+            .set_permissions_recursive(&path, perm, NonZeroUsize::new(2).unwrap())
+            .await
+            .unwrap();
+        assert!(report.is_success(), "{:#?}", report.failures());
+        // The symlink is skipped.
+        assert_eq!(report.succeeded(), 4);
+
+        for p in [
+            path.clone(),
+            path.join("a"),
+            path.join("sub"),
+            path.join("sub").join("b"),
+        ] {
+            assert_eq!(
+                fs::metadata(&p).unwrap().permissions().mode() & 0o7777,
+                0o700,
+                "{p:?}"
+            );
+        }
+
+        let report = fs
+            .set_permissions_recursive(path.join("missing"), perm, NonZeroUsize::new(1).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(report.failures().len(), 1);
+        assert_eq!(report.succeeded(), 0);
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+async fn sftp_fs_copy_dir() {
+    use openssh_sftp_client::fs::CopyDirOptions;
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = gen_path("sftp_fs_copy_dir");
+    let src = path.join("src");
+    let dst = path.join("dst");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::create_dir_all(src.join("sub")).unwrap();
+    fs::write(src.join("a"), b"hello").unwrap();
+    fs::write(src.join("sub").join("b"), b"world").unwrap();
+    fs::set_permissions(src.join("a"), fs::Permissions::from_mode(0o640)).unwrap();
+    std::os::unix::fs::symlink("a", src.join("link")).unwrap();
+
+    {
+        let mut fs = sftp.fs();
+
+        let options = CopyDirOptions::new()
+            .preserve_permissions(true)
+            .preserve_times(true);
+        fs.copy_dir(&src, &dst, options).await.unwrap();
+
+        assert_eq!(fs::read(dst.join("a")).unwrap(), b"hello");
+        assert_eq!(fs::read(dst.join("sub").join("b")).unwrap(), b"world");
+        assert_eq!(fs::read_link(dst.join("link")).unwrap(), Path::new("a"));
+
+        let src_metadata = fs::metadata(src.join("a")).unwrap();
+        let dst_metadata = fs::metadata(dst.join("a")).unwrap();
+        assert_eq!(dst_metadata.permissions().mode() & 0o7777, 0o640);
+        // Times are sent with second precision.
+        let secs = |metadata: &fs::Metadata| {
+            metadata
+                .modified()
+                .unwrap()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+        assert_eq!(secs(&dst_metadata), secs(&src_metadata));
+
+        // Copying a file is rejected.
+        fs.copy_dir(src.join("a"), path.join("c"), options)
+            .await
+            .unwrap_err();
+
+        // `dst` nested in `src` is not copied into itself.
+        let nested = src.join("sub").join("nested");
+        fs.copy_dir(&src, &nested, CopyDirOptions::new())
+            .await
+            .unwrap();
+        assert_eq!(fs::read(nested.join("a")).unwrap(), b"hello");
+        assert_eq!(fs::read(nested.join("sub").join("b")).unwrap(), b"world");
+        assert!(!nested.join("sub").join("nested").exists());
+
+        // Copying onto itself, directly or through a symlink, is rejected
+        // and leaves `src` intact.
+        let alias = path.join("alias");
+        std::os::unix::fs::symlink(&src, &alias).unwrap();
+        for dst in [&src, &alias] {
+            let err = fs
+                .copy_dir(&src, dst, CopyDirOptions::new())
+                .await
+                .unwrap_err();
+            let kind = std::io::Error::from(err).kind();
+            assert_eq!(kind, std::io::ErrorKind::InvalidInput);
+            assert_eq!(fs::read(src.join("a")).unwrap(), b"hello");
+        }
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Sftp::upload_dir and Sftp::download_dir
+async fn sftp_upload_download_dir() {
+    use transfer::{DirTransferOptions, SymlinkPolicy};
+
+    let path = gen_path("sftp_upload_download_dir");
+    let local = path.join("local");
+    let remote = path.join("remote");
+    let downloaded = path.join("downloaded");
+
+    let (mut child, sftp) = connect(sftp_options_with_max_rw_len()).await;
+
+    fs::create_dir_all(local.join("sub").join("empty")).unwrap();
+    for i in 0..5 {
+        fs::write(local.join(i.to_string()), i.to_string().repeat(300)).unwrap();
+    }
+    fs::write(local.join("sub").join("a"), b"a").unwrap();
+    std::os::unix::fs::symlink("0", local.join("link")).unwrap();
+
+    let options = DirTransferOptions::new().max_parallel(NonZeroUsize::new(2).unwrap());
+
+    let report = sftp.upload_dir(&local, &remote, options).await.unwrap();
+    assert_eq!(report.files(), 6);
+    assert_eq!(report.bytes(), 5 * 300 + 1);
+
+    let report = sftp
+        .download_dir(&remote, &downloaded, options)
+        .await
+        .unwrap();
+    assert_eq!(report.files(), 6);
+
+    for dir in [&remote, &downloaded] {
+        for i in 0..5 {
+            assert_eq!(
+                fs::read(dir.join(i.to_string())).unwrap(),
+                i.to_string().repeat(300).as_bytes()
+            );
+        }
+        assert_eq!(fs::read(dir.join("sub").join("a")).unwrap(), b"a");
+        assert!(dir.join("sub").join("empty").is_dir());
+        assert_eq!(fs::read_link(dir.join("link")).unwrap(), Path::new("0"));
+    }
+
+    // Transferring again merges into the existing tree, following symlinks.
+    let report = sftp
+        .download_dir(
+            &remote,
+            &downloaded,
+            options.symlinks(SymlinkPolicy::Follow),
+        )
+        .await
+        .unwrap();
+    assert_eq!(report.files(), 7);
+
+    // Skipped symlinks are not transferred.
+    let report = sftp
+        .upload_dir(
+            &local,
+            path.join("skipped"),
+            options.symlinks(SymlinkPolicy::Skip),
+        )
+        .await
+        .unwrap();
+    assert_eq!(report.files(), 6);
+    assert!(fs::symlink_metadata(path.join("skipped").join("link")).is_err());
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test following symlinks with cycles in Fs::copy_dir and Sftp::upload_dir
+async fn sftp_symlink_policy_follow() {
+    use openssh_sftp_client::fs::{CopyDirOptions, SymlinkPolicy};
+
+    let path = gen_path("sftp_symlink_policy_follow");
+    let src = path.join("src");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::create_dir_all(src.join("sub")).unwrap();
+    fs::write(src.join("a"), b"a").unwrap();
+    fs::write(src.join("sub").join("b"), b"b").unwrap();
+    std::os::unix::fs::symlink("..", src.join("sub").join("up")).unwrap();
+    std::os::unix::fs::symlink("sub", src.join("linkdir")).unwrap();
+    std::os::unix::fs::symlink("a", src.join("linkfile")).unwrap();
+
+    let check = |dst: &Path| {
+        assert_eq!(fs::read(dst.join("linkfile")).unwrap(), b"a");
+        assert!(!fs::symlink_metadata(dst.join("linkdir"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read(dst.join("linkdir").join("b")).unwrap(), b"b");
+        // Following `up` would loop forever.
+        assert!(fs::symlink_metadata(dst.join("sub").join("up")).is_err());
+        assert!(fs::symlink_metadata(dst.join("linkdir").join("up")).is_err());
+    };
+
+    sftp.fs()
+        .copy_dir(
+            &src,
+            path.join("copied"),
+            CopyDirOptions::new().symlinks(SymlinkPolicy::Follow),
+        )
+        .await
+        .unwrap();
+    check(&path.join("copied"));
+
+    let report = sftp
+        .upload_dir(
+            &src,
+            path.join("uploaded"),
+            transfer::DirTransferOptions::new().symlinks(SymlinkPolicy::Follow),
+        )
+        .await
+        .unwrap();
+    assert_eq!(report.files(), 4);
+    check(&path.join("uploaded"));
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test DirTransferOptions::preserve_hardlinks
+async fn sftp_upload_dir_preserve_hardlinks() {
+    use std::os::unix::fs::MetadataExt;
+
+    let path = gen_path("sftp_upload_dir_preserve_hardlinks");
+    let local = path.join("local");
+    let remote = path.join("remote");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::create_dir_all(local.join("sub")).unwrap();
+    fs::write(local.join("a"), b"hello").unwrap();
+    fs::hard_link(local.join("a"), local.join("b")).unwrap();
+    fs::hard_link(local.join("a"), local.join("sub").join("c")).unwrap();
+
+    let options = transfer::DirTransferOptions::new().preserve_hardlinks(true);
+
+    // Uploading twice replaces the files left by the first upload.
+    for _ in 0..2 {
+        let report = sftp.upload_dir(&local, &remote, options).await.unwrap();
+        assert_eq!(report.files(), 1);
+        assert_eq!(report.hardlinks(), 2);
+        assert_eq!(report.bytes(), 5);
+
+        let metadata = [
+            remote.join("a"),
+            remote.join("b"),
+            remote.join("sub").join("c"),
+        ]
+        .map(|p| fs::metadata(p).unwrap());
+        assert_eq!(metadata[0].nlink(), 3);
+        assert!(metadata.iter().all(|m| m.ino() == metadata[0].ino()));
+    }
+    assert_eq!(fs::read(remote.join("sub").join("c")).unwrap(), b"hello");
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test CopyDirOptions::preserve_ownership with an IdMap
+async fn sftp_fs_copy_dir_id_map() {
+    use openssh_sftp_client::fs::{CopyDirOptions, IdMap};
+    use std::os::unix::fs::MetadataExt;
+
+    let path = gen_path("sftp_fs_copy_dir_id_map");
+    let src = path.join("src");
+    let dst = path.join("dst");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::create_dir_all(&src).unwrap();
+    fs::write(src.join("a"), b"a").unwrap();
+
+    let metadata = fs::metadata(src.join("a")).unwrap();
+    let (uid, gid) = (metadata.uid(), metadata.gid());
+
+    let mut id_map = IdMap::new();
+    id_map.map_uid(uid, uid).map_gid(gid.wrapping_add(1), 0);
+    assert_eq!(id_map.uid(uid), uid);
+    assert_eq!(id_map.gid(gid), gid);
+    assert_eq!(id_map.gid(gid.wrapping_add(1)), 0);
+
+    sftp.fs()
+        .copy_dir(
+            &src,
+            &dst,
+            CopyDirOptions::new()
+                .preserve_ownership(true)
+                .id_map(&id_map),
+        )
+        .await
+        .unwrap();
+
+    let metadata = fs::metadata(dst.join("a")).unwrap();
+    assert_eq!((metadata.uid(), metadata.gid()), (uid, gid));
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test CopyDirOptions::preserve_ownership with an IdMap mapping names
+async fn sftp_fs_copy_dir_id_map_by_name() {
+    use openssh_sftp_client::fs::{CopyDirOptions, IdMap};
+    use std::os::unix::fs::MetadataExt;
+
+    let path = gen_path("sftp_fs_copy_dir_id_map_by_name");
+    let src = path.join("src");
+    let dst = path.join("dst");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::create_dir_all(&src).unwrap();
+    fs::write(src.join("a"), b"a").unwrap();
+
+    let metadata = fs::metadata(src.join("a")).unwrap();
+    let (uid, gid) = (metadata.uid(), metadata.gid());
+
+    let mut fs = sftp.fs();
+    let users_groups = fs.users_groups_by_id(&[uid], &[gid]).await.unwrap();
+
+    let mut id_map = IdMap::new();
+    id_map
+        .map_user(users_groups.users[0].clone().unwrap(), uid)
+        .map_group("no-such-group-of-sftp-test", 0);
+
+    fs.copy_dir(
+        &src,
+        &dst,
+        CopyDirOptions::new()
+            .preserve_ownership(true)
+            .id_map(&id_map),
+    )
+    .await
+    .unwrap();
+
+    let metadata = fs::metadata(dst.join("a")).unwrap();
+    assert_eq!((metadata.uid(), metadata.gid()), (uid, gid));
+
+    // close sftp and child
+    drop(fs);
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+/// Serve a fake sftp-server over `stream`, where every path is a directory
+/// containing one regular file named `name`.
+async fn serve_dir_with_entry(mut stream: tokio::io::DuplexStream, name: String) {
+    fn put_string(buffer: &mut Vec<u8>, s: &[u8]) {
+        buffer.extend((s.len() as u32).to_be_bytes());
+        buffer.extend(s);
+    }
+
+    let mut readdir_done = false;
+
+    loop {
+        let mut len = [0; 4];
+        if stream.read_exact(&mut len).await.is_err() {
+            break;
+        }
+        let mut packet = vec![0; u32::from_be_bytes(len) as usize];
+        stream.read_exact(&mut packet).await.unwrap();
+
+        // The version for SSH_FXP_INIT and the request id otherwise.
+        let (packet_type, id) = (packet[0], &packet[1..5]);
+
+        let mut response = Vec::new();
+        match packet_type {
+            // SSH_FXP_INIT
+            1 => response.extend([2, 0, 0, 0, 3]),
+            // SSH_FXP_LSTAT and SSH_FXP_STAT, reply with a directory.
+            7 | 17 => {
+                response.push(105);
+                response.extend(id);
+                response.extend(4_u32.to_be_bytes());
+                response.extend(0o40755_u32.to_be_bytes());
+            }
+            // SSH_FXP_OPENDIR
+            11 => {
+                response.push(102);
+                response.extend(id);
+                put_string(&mut response, b"handle");
+            }
+            // SSH_FXP_READDIR
+            12 if !readdir_done => {
+                readdir_done = true;
+
+                response.push(104);
+                response.extend(id);
+                response.extend(1_u32.to_be_bytes());
+                put_string(&mut response, name.as_bytes());
+                put_string(&mut response, name.as_bytes());
+                response.extend(4_u32.to_be_bytes());
+                response.extend(0o100644_u32.to_be_bytes());
+            }
+            // SSH_FX_OK for SSH_FXP_CLOSE, SSH_FX_EOF for SSH_FXP_READDIR
+            // and SSH_FX_OP_UNSUPPORTED for anything else.
+            _ => {
+                let status_code: u32 = match packet_type {
+                    4 => 0,
+                    12 => 1,
+                    _ => 8,
+                };
+
+                response.push(101);
+                response.extend(id);
+                response.extend(status_code.to_be_bytes());
+                put_string(&mut response, b"");
+                put_string(&mut response, b"");
+            }
+        }
+
+        let mut packet = (response.len() as u32).to_be_bytes().to_vec();
+        packet.extend(response);
+        if stream.write_all(&packet).await.is_err() {
+            break;
+        }
+    }
+}
+
+#[tokio::test]
+/// Test Sftp::download_dir with names from the server that would escape
+/// the destination
+async fn sftp_download_dir_hostile_name() {
+    let path = gen_path("sftp_download_dir_hostile_name");
+    fs::create_dir_all(&path).unwrap();
+
+    let escaped = path.join("escaped");
+
+    for name in [
+        "../escaped".to_owned(),
+        escaped.to_str().unwrap().to_owned(),
+    ] {
+        let (client, server) = tokio::io::duplex(4096);
+        let server = tokio::spawn(serve_dir_with_entry(server, name));
+
+        let (stdout, stdin) = tokio::io::split(client);
+        let sftp = Sftp::new(stdin, stdout, Default::default()).await.unwrap();
+
+        let err = sftp
+            .download_dir("/src", path.join("dst"), Default::default())
+            .await
+            .unwrap_err();
+        match err {
+            Error::WalkDirError { path, error } => {
+                assert_eq!(path, std::path::Path::new("/src"));
+                assert!(matches!(*error, Error::InvalidResponse(_)), "{error:#?}");
+            }
+            err => panic!("Unexpected error {err:#?}"),
+        }
+        assert!(!escaped.exists());
+
+        sftp.close().await.unwrap();
+        server.await.unwrap();
+    }
+}
+
+#[tokio::test]
+/// Test DirTransferOptions::filter
+async fn sftp_upload_download_dir_filter() {
+    use transfer::{DirTransferOptions, Filter};
+
+    let path = gen_path("sftp_upload_download_dir_filter");
+    let local = path.join("local");
+    let remote = path.join("remote");
+    let downloaded = path.join("downloaded");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::create_dir_all(local.join(".git")).unwrap();
+    fs::create_dir_all(local.join("sub")).unwrap();
+    fs::write(local.join(".git").join("HEAD"), b"ref").unwrap();
+    fs::write(local.join("a"), b"a").unwrap();
+    fs::write(local.join("a.tmp"), b"a").unwrap();
+    fs::write(local.join("sub").join("b.tmp"), b"b").unwrap();
+    fs::write(local.join("sub").join("b"), b"b").unwrap();
+
+    let mut filter = Filter::new();
+    filter.exclude("*.tmp").exclude(".git/");
+    let options = DirTransferOptions::new().filter(&filter);
+
+    let report = sftp.upload_dir(&local, &remote, options).await.unwrap();
+    assert_eq!(report.files(), 2);
+    assert!(remote.join("a").exists());
+    assert!(remote.join("sub").join("b").exists());
+    assert!(!remote.join("a.tmp").exists());
+    assert!(!remote.join("sub").join("b.tmp").exists());
+    assert!(!remote.join(".git").exists());
+
+    // Paths are matched relative to the root of the transfer.
+    let mut filter = Filter::new();
+    filter.exclude("/sub/b");
+    let report = sftp
+        .download_dir(&remote, &downloaded, options.filter(&filter))
+        .await
+        .unwrap();
+    assert_eq!(report.files(), 1);
+    assert!(downloaded.join("a").exists());
+    assert!(downloaded.join("sub").is_dir());
+    assert!(!downloaded.join("sub").join("b").exists());
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test TransferQueue
+async fn sftp_transfer_queue() {
+    use transfer::{JobState, TransferOptions};
+
+    let path = gen_path("sftp_transfer_queue");
+    let content = b"hello, world!\n".repeat(1000);
+
+    let (mut child, sftp) = connect(sftp_options_with_max_rw_len()).await;
+
+    fs::create_dir_all(&path).unwrap();
+    let local = path.join("local");
+    fs::write(&local, &content).unwrap();
+
+    let queue = sftp.transfer_queue(NonZeroUsize::new(1).unwrap());
+    let mut events = queue.subscribe();
+
+    let options = TransferOptions::new();
+
+    let job1 = queue.upload(&local, path.join("1"), options);
+    let job2 = queue.upload(&local, path.join("2"), options);
+    let job3 = queue.upload(&local, path.join("3"), options);
+
+    // Only one job runs at the same time.
+    assert!(matches!(job1.state(), JobState::Running));
+    assert_eq!(queue.queued(), 2);
+
+    job2.pause();
+    assert!(matches!(job2.state(), JobState::Paused));
+    job3.set_priority(5);
+
+    assert!(matches!(job1.wait().await, JobState::Completed(_)));
+    assert!(matches!(job3.wait().await, JobState::Completed(_)));
+
+    // Paused job is not started.
+    assert!(matches!(job2.state(), JobState::Paused));
+    assert_eq!(queue.queued(), 1);
+    assert_eq!(queue.running(), 0);
+
+    job2.resume();
+    match job2.wait().await {
+        JobState::Completed(report) => assert_eq!(report.len(), content.len() as u64),
+        state => panic!("Unexpected state {:?}", state),
+    }
+
+    for i in 1..=3 {
+        assert_eq!(fs::read(path.join(i.to_string())).unwrap(), content);
+    }
+
+    // Cancel a paused running job.
+    let job4 = queue.download(path.join("1"), path.join("4"), options);
+    job4.pause();
+    job4.cancel();
+    assert!(matches!(job4.wait().await, JobState::Cancelled));
+
+    drop(queue);
+
+    let mut states = Vec::new();
+    while let Some(event) = events.next().await {
+        if event.id() == job4.id() && event.state().is_finished() {
+            break;
+        }
+        if event.id() == job3.id() {
+            states.push(event.into_state());
+        }
+    }
+    assert!(matches!(
+        &*states,
+        [JobState::Queued, JobState::Running, JobState::Completed(_)]
+    ));
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Sftp::connection_events
+async fn sftp_connection_events() {
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    let events = sftp.connection_events();
+    assert_eq!(*events.borrow(), ConnectionEvent::Connected);
+
+    sftp.fs().metadata(".").await.unwrap();
+    assert_eq!(*events.borrow(), ConnectionEvent::Connected);
+
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+
+    assert_eq!(
+        *events.borrow(),
+        ConnectionEvent::Closed(CloseReason::Requested)
+    );
+}
+
+#[tokio::test]
+/// Test SftpScope
+async fn sftp_scope() {
+    let path = gen_path("sftp_scope");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    let scope = sftp.scope();
+    let child_scope = scope.child();
+
+    assert_eq!(
+        scope.run(async { Ok(1) }).await.unwrap(),
+        1,
+        "Futures run to completion if not cancelled"
+    );
+
+    let run = scope.run(async {
+        let _file = sftp.create(&path).await?;
+        std::future::pending::<Result<(), Error>>().await
+    });
+    let (res, ()) = tokio::join!(run, async {
+        sleep(Duration::from_millis(100)).await;
+        scope.cancel();
+    });
+    assert!(matches!(res, Err(Error::Cancelled)), "{res:?}");
+    assert!(path.exists());
+
+    // Children are cancelled along with their parent.
+    assert!(child_scope.is_cancelled());
+    assert!(matches!(
+        child_scope.run(async { Ok(()) }).await,
+        Err(Error::Cancelled)
+    ));
+
+    // Dropping the scope cancels it.
+    let scope = sftp.scope();
+    let run = scope.run(std::future::pending::<Result<(), Error>>());
+    drop(scope);
+    assert!(matches!(run.await, Err(Error::Cancelled)));
+
+    // Futures that do not complete in time are timed out.
+    let scope = sftp.scope();
+    let timeout = Duration::from_millis(100);
+    assert!(matches!(
+        scope
+            .run_with_timeout(timeout, std::future::pending::<Result<(), Error>>())
+            .await,
+        Err(Error::TimedOut { timeout: t }) if t == timeout
+    ));
+    // The connection is still usable after the timeout.
+    sftp.fs().metadata(&path).await.unwrap();
+    assert_eq!(
+        scope
+            .run_with_timeout(Duration::from_secs(60), async { Ok(1) })
+            .await
+            .unwrap(),
+        1
+    );
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+async fn exercise_async_fs(fs: &mut dyn vfs::AsyncFs, path: &Path) {
+    let content = b"hello, world!\n";
+
+    fs.create_dir(path).await.unwrap();
+    assert!(fs.metadata(path).await.unwrap().is_dir());
+
+    let a = path.join("a");
+    let b = path.join("b");
+
+    fs.write(&a, content).await.unwrap();
+    assert_eq!(&*fs.read(&a).await.unwrap(), content);
+
+    let mut file = fs.create(&a).await.unwrap();
+    file.write_all(&content[..5]).await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let mut buffer = Vec::new();
+    let mut file = fs.open(&a).await.unwrap();
+    file.read_to_end(&mut buffer).await.unwrap();
+    drop(file);
+    assert_eq!(&buffer[..], &content[..5]);
+
+    fs.write(&a, content).await.unwrap();
+
+    let stat = fs.metadata(&a).await.unwrap();
+    assert!(stat.is_file());
+    assert_eq!(stat.len(), Some(content.len() as u64));
+
+    fs.rename(&a, &b).await.unwrap();
+
+    let entries = fs.read_dir(path).await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].0, Path::new("b"));
+    assert!(entries[0].1.is_file());
+
+    fs.remove_file(&b).await.unwrap();
+    fs.remove_dir(path).await.unwrap();
+    assert!(fs.metadata(path).await.is_err());
+}
+
+#[tokio::test]
+/// Test vfs::AsyncFs for Fs and vfs::LocalFs
+async fn sftp_vfs() {
+    let path = gen_path("sftp_vfs");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    exercise_async_fs(&mut sftp.fs(), &path).await;
+    exercise_async_fs(&mut vfs::LocalFs, &path).await;
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test SftpOptions::max_read_len and SftpOptions::max_write_len
+async fn sftp_max_rw_len() {
+    let (mut child, sftp) = connect(Default::default()).await;
+    let (max_read_len, max_write_len) = (sftp.max_read_len(), sftp.max_write_len());
+    assert!(sftp.max_packet_len() > max_read_len);
+    assert!(sftp.max_packet_len() > max_write_len);
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+
+    let (mut child, sftp) = connect(sftp_options_with_max_rw_len()).await;
+    assert_eq!(sftp.max_read_len(), 200);
+    assert_eq!(sftp.max_write_len(), 200);
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+
+    // The limits cannot be increased.
+    let options = SftpOptions::new()
+        .max_read_len(NonZeroU32::new(u32::MAX).unwrap())
+        .max_write_len(NonZeroU32::new(u32::MAX).unwrap());
+    let (mut child, sftp) = connect(options).await;
+    assert_eq!(sftp.max_read_len(), max_read_len);
+    assert_eq!(sftp.max_write_len(), max_write_len);
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test SftpOptions::max_outstanding_requests
+async fn sftp_max_outstanding_requests() {
+    let path = gen_path("sftp_max_outstanding_requests");
+    fs::create_dir_all(&path).unwrap();
+
+    let options = SftpOptions::new().max_outstanding_requests(NonZeroUsize::new(1).unwrap());
+    let (mut child, sftp) = connect(options).await;
+
+    let futures = (0..10).map(|_| {
+        let mut fs = sftp.fs();
+        let path = &path;
+        async move { fs.metadata(path).await }
+    });
+
+    for metadata in futures_util::future::try_join_all(futures).await.unwrap() {
+        assert!(metadata.file_type().unwrap().is_dir());
+    }
+
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test SftpOptions::request_timeout
+async fn sftp_request_timeout() {
+    let path = gen_path("sftp_request_timeout");
+    let content = b"hello, world!\n";
+
+    let options = SftpOptions::new().request_timeout(Duration::from_secs(60));
+    let (mut child, sftp) = connect(options).await;
+
+    {
+        let mut fs = sftp.fs();
+        fs.write(&path, content).await.unwrap();
+        assert_eq!(&*fs.read(&path).await.unwrap(), content);
+
+        // run_with_timeout overrides the request timeout.
+        let scope = sftp.scope();
+        let metadata = scope
+            .run_with_timeout(Duration::from_secs(120), fs.metadata(&path))
+            .await
+            .unwrap();
+        assert_eq!(metadata.len().unwrap(), content.len() as u64);
+
+        // The request timeout can be changed or disabled at runtime.
+        sftp.set_request_timeout(None);
+        assert_eq!(&*fs.read(&path).await.unwrap(), content);
+
+        sftp.set_request_timeout(Some(Duration::from_secs(30)));
+        assert_eq!(&*fs.read(&path).await.unwrap(), content);
+    }
+
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Sftp::new_with_cancellation_token
+async fn sftp_cancellation_token() {
+    let path = gen_path("sftp_cancellation_token");
+    fs::create_dir_all(&path).unwrap();
+
+    let token = tokio_util::sync::CancellationToken::new();
+    let (mut child, stdin, stdout) = launch_sftp().await;
+    let sftp = Sftp::new_with_cancellation_token(
+        stdin,
+        stdout,
+        SftpOptions::new(),
+        SftpAuxiliaryData::None,
+        tokio::runtime::Handle::current(),
+        token.clone(),
+    )
+    .await
+    .unwrap();
+
+    let mut fs = sftp.fs();
+    fs.metadata(&path).await.unwrap();
+
+    token.cancel();
+    assert!(!sftp.is_alive());
+
+    assert!(matches!(fs.metadata(&path).await, Err(Error::Cancelled)));
+
+    drop(fs);
+    sftp.close().await.unwrap_err();
+    child.wait().await.unwrap();
+}
+
+#[tokio::test]
+/// Test SftpOptions::keepalive_interval
+async fn sftp_keepalive_interval() {
+    let path = gen_path("sftp_keepalive_interval");
+    fs::create_dir_all(&path).unwrap();
+
+    let options = SftpOptions::new().keepalive_interval(Duration::from_millis(10));
+    let (mut child, sftp) = connect(options).await;
+
+    // Keepalive requests are sent while the connection is idle.
+    sleep(Duration::from_millis(100)).await;
+
+    assert!(sftp
+        .fs()
+        .metadata(&path)
+        .await
+        .unwrap()
+        .file_type()
+        .unwrap()
+        .is_dir());
+
+    sleep(Duration::from_millis(100)).await;
+
+    // Keepalive requests are answered by the server.
+    assert_eq!(
+        *sftp.connection_events().borrow(),
+        ConnectionEvent::Connected
+    );
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Sftp::{is_alive, ping}
+async fn sftp_is_alive_ping() {
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    assert!(sftp.is_alive());
+    sftp.ping().await.unwrap();
+    assert!(sftp.is_alive());
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test From<Error> for std::io::Error
+async fn sftp_error_into_io_error() {
+    let path = gen_path("sftp_error_into_io_error");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    let err = sftp.fs().metadata(&path).await.unwrap_err();
+    assert!(matches!(
+        err,
+        Error::SftpError(error::SftpErrorKind::NoSuchFile, _)
+    ));
+
+    let io_error = std::io::Error::from(err);
+    assert_eq!(io_error.kind(), std::io::ErrorKind::NotFound);
+    assert!(io_error.into_inner().unwrap().is::<Error>());
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test File::try_clone
+async fn sftp_file_try_clone() {
+    let path = gen_path("sftp_file_try_clone");
+    let content = b"hello, world!\n";
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::write(&path, content).unwrap();
+
+    {
+        let mut file = sftp.open(&path).await.unwrap();
+        file.seek(std::io::SeekFrom::Start(7)).await.unwrap();
+
+        let mut cloned = file.try_clone().await.unwrap();
+        assert_eq!(cloned.offset(), 7);
+
+        // The cloned file does not truncate the existing content.
+        let buffer = cloned
+            .read_all(content.len() - 7, BytesMut::new())
+            .await
+            .unwrap();
+        assert_eq!(&*buffer, &content[7..]);
+
+        // Cursors are independent.
+        assert_eq!(file.offset(), 7);
+        drop(file);
+
+        // The cloned file has its own handle.
+        assert!(cloned.metadata().await.is_ok());
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test changing settings of a running Sftp
+async fn sftp_runtime_settings() {
+    let path = gen_path("sftp_runtime_settings");
+    let content = b"hello, world!\n";
+
+    let (mut child, sftp) =
+        connect(SftpOptions::new().flush_interval(Duration::from_secs(60))).await;
+
+    // Without lowering flush_interval, the request would only be sent
+    // after 60s.
+    sftp.set_flush_interval(Duration::from_millis(0));
+    sftp.set_max_pending_requests(NonZeroU16::new(1).unwrap());
+    sftp.set_tokio_compat_file_write_limit(NonZeroUsize::new(1).unwrap());
+
+    tokio::time::timeout(Duration::from_secs(10), async {
+        let mut fs = sftp.fs();
+
+        fs.write(&path, content).await.unwrap();
+        assert_eq!(&*fs.read(&path).await.unwrap(), content);
+
+        let file = file::TokioCompatFile::from(sftp.create(&path).await.unwrap());
+        tokio::pin!(file);
+        file.write_all(content).await.unwrap();
+        file.flush().await.unwrap();
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(fs::read(&path).unwrap(), content);
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test File::stats and Dir::stats
+async fn sftp_io_stats() {
+    let path = gen_path("sftp_io_stats");
+    let content = b"hello, world!\n";
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::create_dir_all(&path).unwrap();
+    let file_path = path.join("file");
+
+    {
+        let mut file = sftp.create(&file_path).await.unwrap();
+        assert_eq!(file.stats(), IoStats::default());
+        assert_eq!(file.stats().average_latency(), None);
+
+        file.write_all(content).await.unwrap();
+
+        let stats = file.stats();
+        assert_eq!(stats.bytes_written(), content.len() as u64);
+        assert_eq!(stats.bytes_read(), 0);
+        assert_eq!(stats.requests(), 1);
+        assert!(stats.average_latency().is_some());
+
+        // Stats are shared with clones.
+        let mut cloned = file.clone();
+        cloned.metadata().await.unwrap();
+        assert_eq!(file.stats().requests(), 2);
+
+        let mut file = sftp.open(&file_path).await.unwrap();
+        let buffer = file.read_all(content.len(), BytesMut::new()).await.unwrap();
+        assert_eq!(&*buffer, content);
+        assert_eq!(file.stats().bytes_read(), content.len() as u64);
+        assert_eq!(file.stats().bytes_written(), 0);
+
+        let read_dir = sftp.fs().open_dir(&path).await.unwrap().read_dir();
+        tokio::pin!(read_dir);
+        while read_dir.next().await.is_some() {}
+        assert!(read_dir.stats().requests() >= 1);
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test File::copy_range_to
+async fn sftp_file_copy_range_to() {
+    let path = gen_path("sftp_file_copy_range_to");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::create_dir_all(&path).unwrap();
+    fs::write(path.join("src"), b"hello, world!\n").unwrap();
+    fs::write(path.join("dst"), b"0123").unwrap();
+
+    {
+        let mut src = sftp.open(path.join("src")).await.unwrap();
+        let dst = sftp.open(path.join("dst")).write(true).await.unwrap();
+
+        // Append "world" to dst.
+        src.copy_range_to(7, &dst, 4, NonZeroU64::new(5))
+            .await
+            .unwrap();
+        assert_eq!(fs::read(path.join("dst")).unwrap(), b"0123world");
+
+        // Copy until EOF.
+        src.copy_range_to(5, &dst, 9, None).await.unwrap();
+        assert_eq!(fs::read(path.join("dst")).unwrap(), b"0123world, world!\n");
+
+        // Offsets are unchanged.
+        assert_eq!(src.offset(), 0);
+        assert_eq!(dst.offset(), 0);
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test OpenOptions::mode
+async fn sftp_open_options_mode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = gen_path("sftp_open_options_mode");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    sftp.create(&path)
+        .mode(metadata::Permissions::from(0o600))
+        .await
+        .unwrap();
+    assert_eq!(
+        fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+        0o600
+    );
+
+    // Opening an existing file does not change its permissions.
+    sftp.options()
+        .write(true)
+        .create(true)
+        .mode(metadata::Permissions::from(0o644))
+        .open(&path)
+        .await
+        .unwrap();
+    assert_eq!(
+        fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+        0o600
+    );
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Fs::walk_dir
+async fn sftp_fs_walk_dir() {
+    use openssh_sftp_client::fs::SymlinkPolicy;
+
+    async fn walk(
+        mut walk_dir: openssh_sftp_client::fs::WalkDir,
+        root: &Path,
+    ) -> Vec<(PathBuf, usize)> {
+        let mut entries = Vec::new();
+        while let Some(entry) = walk_dir.next().await {
+            let entry = entry.unwrap();
+            let path = entry.path().strip_prefix(root).unwrap().to_path_buf();
+            entries.push((path, entry.depth()));
+        }
+        entries
+    }
+
+    fn position(entries: &[(PathBuf, usize)], path: &str) -> usize {
+        entries
+            .iter()
+            .position(|(p, _)| p == Path::new(path))
+            .unwrap()
+    }
+
+    let path = gen_path("sftp_fs_walk_dir");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    std::fs::create_dir_all(path.join("a").join("b")).unwrap();
+    std::fs::write(path.join("a").join("b").join("file"), b"").unwrap();
+    std::fs::write(path.join("c"), b"").unwrap();
+
+    {
+        let fs = sftp.fs();
+
+        let entries = walk(fs.walk_dir(&path), &path).await;
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[0], (PathBuf::new(), 0));
+        assert_eq!(entries[position(&entries, "a/b/file")].1, 3);
+        assert_eq!(entries[position(&entries, "c")].1, 1);
+        assert!(position(&entries, "a") < position(&entries, "a/b"));
+        assert!(position(&entries, "a/b") < position(&entries, "a/b/file"));
+
+        let entries = walk(fs.walk_dir(&path).contents_first(true), &path).await;
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[4], (PathBuf::new(), 0));
+        assert!(position(&entries, "a/b/file") < position(&entries, "a/b"));
+        assert!(position(&entries, "a/b") < position(&entries, "a"));
+
+        let entries = walk(fs.walk_dir(&path).max_depth(1), &path).await;
+        assert_eq!(entries.len(), 3);
+        assert!(entries.iter().all(|(_, depth)| *depth <= 1));
+
+        std::os::unix::fs::symlink(&path, path.join("a").join("up")).unwrap();
+
+        // Symlinks are yielded, but not followed by default.
+        let entries = walk(fs.walk_dir(&path), &path).await;
+        assert_eq!(entries.len(), 6);
+
+        // Following the symlink back to the root does not loop.
+        let entries = walk(fs.walk_dir(&path).symlinks(SymlinkPolicy::Follow), &path).await;
+        assert_eq!(entries.len(), 5);
+
+        let entries = walk(fs.walk_dir(&path).symlinks(SymlinkPolicy::Skip), &path).await;
+        assert_eq!(entries.len(), 5);
+
+        // Excluded directories are not walked into.
+        let entries = walk(
+            fs.walk_dir(&path)
+                .filter(|path, _metadata| !path.ends_with("b")),
+            &path,
+        )
+        .await;
+        assert_eq!(entries.len(), 4);
+        assert!(entries.iter().all(|(path, _)| !path.starts_with("a/b")));
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Fs::remove_dir_all
+async fn sftp_fs_remove_dir_all() {
+    let path = gen_path("sftp_fs_remove_dir_all");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::create_dir_all(path.join("a").join("b")).unwrap();
+    fs::write(path.join("a").join("b").join("file"), b"file").unwrap();
+    fs::write(path.join("c"), b"c").unwrap();
+    std::os::unix::fs::symlink(path.join("c"), path.join("link")).unwrap();
+
+    {
+        let mut fs = sftp.fs();
+
+        // Only the symlink is removed if the root is a symlink.
+        let root_link = path.join("root_link");
+        std::os::unix::fs::symlink(path.join("a"), &root_link).unwrap();
+        fs.remove_dir_all(&root_link, NonZeroUsize::new(2).unwrap())
+            .await
+            .unwrap();
+        assert!(root_link.symlink_metadata().is_err());
+        assert!(path.join("a").join("b").join("file").exists());
+
+        fs.remove_dir_all(&path, NonZeroUsize::new(2).unwrap())
+            .await
+            .unwrap();
+        assert!(!path.exists());
+
+        fs.remove_dir_all(&path, NonZeroUsize::new(1).unwrap())
+            .await
+            .unwrap_err();
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Fs::statvfs and File::statvfs
+async fn sftp_statvfs() {
+    let path = gen_path("sftp_statvfs");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+    assert!(sftp.support_statvfs());
+
+    fs::write(&path, b"statvfs").unwrap();
+
+    {
+        let statvfs = sftp.fs().statvfs(&path).await.unwrap();
+        assert_ne!(statvfs.block_size, 0);
+        assert!(statvfs.blocks_avail <= statvfs.blocks);
+
+        let mut file = sftp.open(&path).await.unwrap();
+        assert_eq!(file.statvfs().await.unwrap().fsid, statvfs.fsid);
+        file.close().await.unwrap();
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Fs::set_symlink_metadata
+async fn sftp_fs_set_symlink_metadata() {
+    let path = gen_path("sftp_fs_set_symlink_metadata");
+    let link = gen_path("sftp_fs_set_symlink_metadata_link");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+    assert!(sftp.support_lsetstat());
+
+    fs::write(&path, b"lsetstat").unwrap();
+    std::os::unix::fs::symlink(&path, &link).unwrap();
+
+    {
+        let time = UnixTimeStamp::from_raw(1000).unwrap();
+        let metadata = metadata::MetaDataBuilder::new().time(time, time).create();
+
+        sftp.fs()
+            .set_symlink_metadata(&link, metadata)
+            .await
+            .unwrap();
+
+        let modified = |metadata: fs::Metadata| metadata.modified().unwrap();
+        assert_eq!(
+            modified(fs::symlink_metadata(&link).unwrap()),
+            time.as_system_time()
+        );
+        // The target is left untouched.
+        assert_ne!(
+            modified(fs::metadata(&path).unwrap()),
+            time.as_system_time()
+        );
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Fs::users_groups_by_id
+async fn sftp_fs_users_groups_by_id() {
+    use std::os::unix::fs::MetadataExt;
+
+    let path = gen_path("sftp_fs_users_groups_by_id");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::write(&path, b"users-groups-by-id").unwrap();
+
+    {
+        let mut fs = sftp.fs();
+
+        let metadata = fs.metadata(&path).await.unwrap();
+        let (uid, gid) = (metadata.uid().unwrap(), metadata.gid().unwrap());
+        assert_eq!(uid, fs::metadata(&path).unwrap().uid());
+
+        let users_groups = fs.users_groups_by_id(&[uid], &[gid]).await.unwrap();
+        assert_eq!(users_groups.users.len(), 1);
+        assert_eq!(users_groups.groups.len(), 1);
+        assert!(users_groups.users[0].is_some());
+        assert!(users_groups.groups[0].is_some());
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Fs::home_dir
+async fn sftp_fs_home_dir() {
+    let home: PathBuf = env::var("HOME").unwrap().into();
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    assert_eq!(sftp.fs().home_dir("").await.unwrap(), home);
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Sftp::server_extensions
+async fn sftp_server_extensions() {
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    let extensions = sftp.server_extensions();
+    assert_eq!(
+        extensions.contains_key("posix-rename@openssh.com"),
+        sftp.support_posix_rename()
+    );
+    assert_eq!(
+        extensions.contains_key("fsync@openssh.com"),
+        sftp.support_fsync()
+    );
+    assert_eq!(
+        extensions.get("limits@openssh.com").map(String::as_str),
+        Some("1")
+    );
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test File::check_file and Fs::check_file
+async fn sftp_check_file() {
+    fn assert_unsupported(err: Error) {
+        assert!(
+            matches!(
+                err,
+                Error::SftpError(error::SftpErrorKind::OpUnsupported, _)
+            ),
+            "{err:#?}"
+        );
+    }
+
+    let path = gen_path("sftp_check_file");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::write(&path, b"check-file").unwrap();
+
+    {
+        // openssh does not support check-file.
+        let err = sftp
+            .fs()
+            .check_file(&path, "sha256", 0, 0, 0)
+            .await
+            .unwrap_err();
+        assert_unsupported(err);
+
+        let mut file = sftp.open(&path).await.unwrap();
+        assert_unsupported(file.check_file("sha256", 0, 0, 0).await.unwrap_err());
+        file.close().await.unwrap();
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test Fs::space_available
+async fn sftp_fs_space_available() {
+    let path = gen_path("sftp_fs_space_available");
+
+    let (mut child, sftp) = connect(Default::default()).await;
+
+    fs::create_dir_all(&path).unwrap();
+
+    // openssh does not support space-available.
+    let err = sftp.fs().space_available(&path).await.unwrap_err();
+    assert!(
+        matches!(
+            err,
+            Error::SftpError(error::SftpErrorKind::OpUnsupported, _)
+        ),
+        "{err:#?}"
+    );
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}
+
+#[tokio::test]
+/// Test TokioCompatFile::set_read_ahead
+async fn sftp_tokio_compact_file_read_ahead() {
+    let path = gen_path("sftp_tokio_compact_file_read_ahead");
+    let content: Vec<u8> = (0..=u8::MAX).cycle().take(10000).collect();
+
+    let (mut child, sftp) = connect(sftp_options_with_max_rw_len()).await;
+
+    fs::write(&path, &content).unwrap();
+
+    {
+        let mut file = file::TokioCompatFile::new(sftp.open(&path).await.unwrap());
+        file.set_read_ahead(8);
+        assert_eq!(file.read_ahead(), 8);
+        tokio::pin!(file);
+
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer).await.unwrap();
+        assert_eq!(buffer, content);
+
+        // Requests sent ahead are discarded on seek.
+        let mut buffer = [0; 1000];
+        for start in [5000, 100, 9000] {
+            file.seek(std::io::SeekFrom::Start(start)).await.unwrap();
+            file.read_exact(&mut buffer).await.unwrap();
+
+            let start = start as usize;
+            assert_eq!(&buffer[..], &content[start..start + 1000]);
+        }
+    }
+
+    // close sftp and child
+    sftp.close().await.unwrap();
+    assert!(child.wait().await.unwrap().success());
+}